@@ -25,9 +25,15 @@ use {
     },
     spl_token_lending::{
         self,
-        instruction::{init_lending_market, init_reserve, update_reserve_config},
+        instruction::{
+            add_swap_program, init_lending_market, init_reserve, remove_swap_program,
+            set_flash_loan_fee_exemption, update_reserve_config,
+        },
         math::WAD,
-        state::{LendingMarket, Reserve, ReserveConfig, ReserveFees},
+        state::{
+            find_flash_loan_fee_exemptions_address, FlashLoanFeeExemptions, LendingMarket, Reserve,
+            ReserveConfig, ReserveFees, ReserveRamp,
+        },
     },
     std::{borrow::Borrow, process::exit, str::FromStr},
     system_instruction::create_account,
@@ -50,6 +56,10 @@ struct PartialReserveConfig {
     pub loan_to_value_ratio: Option<u8>,
     /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage
     pub liquidation_bonus: Option<u8>,
+    /// Bonus a liquidator gets once an obligation's borrowed value reaches its deposited value
+    pub max_liquidation_bonus: Option<u8>,
+    /// Share of the liquidation bonus paid to the collateral fee receiver
+    pub protocol_liquidation_fee: Option<u8>,
     /// Loan to value ratio at which an obligation can be liquidated, as a percentage
     pub liquidation_threshold: Option<u8>,
     /// Min borrow APY
@@ -66,6 +76,12 @@ struct PartialReserveConfig {
     pub borrow_limit: Option<u64>,
     /// Liquidity fee receiver
     pub fee_receiver: Option<Pubkey>,
+    /// Collateral fee receiver
+    pub collateral_fee_receiver: Option<Pubkey>,
+    /// Loan to value ratio ramp
+    pub loan_to_value_ratio_ramp: Option<ReserveRamp>,
+    /// Liquidation threshold ramp
+    pub liquidation_threshold_ramp: Option<ReserveRamp>,
 }
 
 /// Reserve Fees with optional fields
@@ -296,6 +312,26 @@ fn main() {
                         .default_value("5")
                         .help("Bonus a liquidator gets when repaying part of an unhealthy obligation: [0, 100]"),
                 )
+                .arg(
+                    Arg::with_name("max_liquidation_bonus")
+                        .long("max-liquidation-bonus")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Bonus a liquidator gets once an obligation's borrowed value reaches its deposited value, at or below the liquidation bonus keeps the bonus flat: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("protocol_liquidation_fee")
+                        .long("protocol-liquidation-fee")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Share of the liquidation bonus paid to the collateral fee receiver: [0, 100]"),
+                )
                 .arg(
                     Arg::with_name("liquidation_threshold")
                         .long("liquidation-threshold")
@@ -445,6 +481,24 @@ fn main() {
                         .required(false)
                         .help("Bonus a liquidator gets when repaying part of an unhealthy obligation: [0, 100]"),
                 )
+                .arg(
+                    Arg::with_name("max_liquidation_bonus")
+                        .long("max-liquidation-bonus")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Bonus a liquidator gets once an obligation's borrowed value reaches its deposited value, at or below the liquidation bonus keeps the bonus flat: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("protocol_liquidation_fee")
+                        .long("protocol-liquidation-fee")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Share of the liquidation bonus paid to the collateral fee receiver: [0, 100]"),
+                )
                 .arg(
                     Arg::with_name("liquidation_threshold")
                         .long("liquidation-threshold")
@@ -535,6 +589,75 @@ fn main() {
                         .required(false)
                         .help("Fee receiver address"),
                 )
+                .arg(
+                    Arg::with_name("collateral_fee_receiver")
+                        .long("collateral-fee-receiver")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Collateral fee receiver address"),
+                )
+                .arg(
+                    Arg::with_name("loan_to_value_ratio_ramp_target")
+                        .long("loan-to-value-ratio-ramp-target")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .requires_all(&["loan_to_value_ratio_ramp_start_slot", "loan_to_value_ratio_ramp_end_slot"])
+                        .help("Loan to value ratio to ramp to: [0, 100)"),
+                )
+                .arg(
+                    Arg::with_name("loan_to_value_ratio_ramp_start_slot")
+                        .long("loan-to-value-ratio-ramp-start-slot")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("loan_to_value_ratio_ramp_target")
+                        .help("Slot the loan to value ratio ramp starts at"),
+                )
+                .arg(
+                    Arg::with_name("loan_to_value_ratio_ramp_end_slot")
+                        .long("loan-to-value-ratio-ramp-end-slot")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("loan_to_value_ratio_ramp_target")
+                        .help("Slot the loan to value ratio ramp ends at, 0 to remove the ramp"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_threshold_ramp_target")
+                        .long("liquidation-threshold-ramp-target")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .requires_all(&["liquidation_threshold_ramp_start_slot", "liquidation_threshold_ramp_end_slot"])
+                        .help("Liquidation threshold to ramp to: (LTV, 100]"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_threshold_ramp_start_slot")
+                        .long("liquidation-threshold-ramp-start-slot")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("liquidation_threshold_ramp_target")
+                        .help("Slot the liquidation threshold ramp starts at"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_threshold_ramp_end_slot")
+                        .long("liquidation-threshold-ramp-end-slot")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("liquidation_threshold_ramp_target")
+                        .help("Slot the liquidation threshold ramp ends at, 0 to remove the ramp"),
+                )
                 .arg(
                     Arg::with_name("pyth_product")
                         .long("pyth-product")
@@ -563,6 +686,156 @@ fn main() {
                         .help("Switchboard price feed account: https://switchboard.xyz/#/explorer"),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("set-flash-loan-fee-exemption")
+                .about("Reduce the flash loan fee for a receiver program or signer")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("exempt")
+                        .long("exempt")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Flash loan receiver program id or signer address"),
+                )
+                .arg(
+                    Arg::with_name("fee_discount")
+                        .long("fee-discount")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .default_value("100")
+                        .help("Discount on the reserve flash loan fees: (0, 100], 100 for no fee"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("remove-flash-loan-fee-exemption")
+                .about("Remove the flash loan fee exemption of a receiver program or signer")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("exempt")
+                        .long("exempt")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Flash loan receiver program id or signer address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("show-flash-loan-fee-exemptions")
+                .about("List the flash loan fee exemptions of a lending market")
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("add-swap-program")
+                .about("Approve a swap program for collateral swaps")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("swap_program")
+                        .long("swap-program")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Swap program id"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("remove-swap-program")
+                .about("Revoke the approval of a swap program for collateral swaps")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("swap_program")
+                        .long("swap-program")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Swap program id"),
+                )
+        )
         .get_matches();
 
     let mut wallet_manager = None;
@@ -632,6 +905,9 @@ fn main() {
                 value_of(arg_matches, "optimal_utilization_rate").unwrap();
             let loan_to_value_ratio = value_of(arg_matches, "loan_to_value_ratio").unwrap();
             let liquidation_bonus = value_of(arg_matches, "liquidation_bonus").unwrap();
+            let max_liquidation_bonus = value_of(arg_matches, "max_liquidation_bonus").unwrap();
+            let protocol_liquidation_fee =
+                value_of(arg_matches, "protocol_liquidation_fee").unwrap();
             let liquidation_threshold = value_of(arg_matches, "liquidation_threshold").unwrap();
             let min_borrow_rate = value_of(arg_matches, "min_borrow_rate").unwrap();
             let optimal_borrow_rate = value_of(arg_matches, "optimal_borrow_rate").unwrap();
@@ -646,6 +922,7 @@ fn main() {
             let flash_loan_fee_wad = (flash_loan_fee * WAD as f64) as u64;
 
             let liquidity_fee_receiver_keypair = Keypair::new();
+            let collateral_fee_receiver_keypair = Keypair::new();
 
             let source_liquidity_account = config
                 .rpc_client
@@ -671,6 +948,8 @@ fn main() {
                     optimal_utilization_rate,
                     loan_to_value_ratio,
                     liquidation_bonus,
+                    max_liquidation_bonus,
+                    protocol_liquidation_fee,
                     liquidation_threshold,
                     min_borrow_rate,
                     optimal_borrow_rate,
@@ -683,6 +962,9 @@ fn main() {
                     deposit_limit,
                     borrow_limit,
                     fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
+                    collateral_fee_receiver: collateral_fee_receiver_keypair.pubkey(),
+                    loan_to_value_ratio_ramp: ReserveRamp::default(),
+                    liquidation_threshold_ramp: ReserveRamp::default(),
                 },
                 source_liquidity_pubkey,
                 source_liquidity_owner_keypair,
@@ -692,6 +974,7 @@ fn main() {
                 pyth_price_pubkey,
                 switchboard_feed_pubkey,
                 liquidity_fee_receiver_keypair,
+                collateral_fee_receiver_keypair,
                 source_liquidity,
             )
         }
@@ -703,6 +986,8 @@ fn main() {
             let optimal_utilization_rate = value_of(arg_matches, "optimal_utilization_rate");
             let loan_to_value_ratio = value_of(arg_matches, "loan_to_value_ratio");
             let liquidation_bonus = value_of(arg_matches, "liquidation_bonus");
+            let max_liquidation_bonus = value_of(arg_matches, "max_liquidation_bonus");
+            let protocol_liquidation_fee = value_of(arg_matches, "protocol_liquidation_fee");
             let liquidation_threshold = value_of(arg_matches, "liquidation_threshold");
             let min_borrow_rate = value_of(arg_matches, "min_borrow_rate");
             let optimal_borrow_rate = value_of(arg_matches, "optimal_borrow_rate");
@@ -713,6 +998,24 @@ fn main() {
             let deposit_limit = value_of(arg_matches, "deposit_limit");
             let borrow_limit = value_of(arg_matches, "borrow_limit");
             let fee_receiver = pubkey_of(arg_matches, "fee_receiver");
+            let collateral_fee_receiver = pubkey_of(arg_matches, "collateral_fee_receiver");
+            let loan_to_value_ratio_ramp = value_of(arg_matches, "loan_to_value_ratio_ramp_target")
+                .map(|target| ReserveRamp {
+                    target,
+                    start_slot: value_of(arg_matches, "loan_to_value_ratio_ramp_start_slot")
+                        .unwrap(),
+                    end_slot: value_of(arg_matches, "loan_to_value_ratio_ramp_end_slot").unwrap(),
+                });
+            let liquidation_threshold_ramp =
+                value_of(arg_matches, "liquidation_threshold_ramp_target").map(|target| {
+                    ReserveRamp {
+                        target,
+                        start_slot: value_of(arg_matches, "liquidation_threshold_ramp_start_slot")
+                            .unwrap(),
+                        end_slot: value_of(arg_matches, "liquidation_threshold_ramp_end_slot")
+                            .unwrap(),
+                    }
+                });
             let pyth_product_pubkey = pubkey_of(arg_matches, "pyth_product");
             let pyth_price_pubkey = pubkey_of(arg_matches, "pyth_price");
             let switchboard_feed_pubkey = pubkey_of(arg_matches, "switchboard_feed");
@@ -726,6 +1029,8 @@ fn main() {
                     optimal_utilization_rate,
                     loan_to_value_ratio,
                     liquidation_bonus,
+                    max_liquidation_bonus,
+                    protocol_liquidation_fee,
                     liquidation_threshold,
                     min_borrow_rate,
                     optimal_borrow_rate,
@@ -738,6 +1043,9 @@ fn main() {
                     deposit_limit,
                     borrow_limit,
                     fee_receiver,
+                    collateral_fee_receiver,
+                    loan_to_value_ratio_ramp,
+                    liquidation_threshold_ramp,
                 },
                 pyth_product_pubkey,
                 pyth_price_pubkey,
@@ -747,6 +1055,68 @@ fn main() {
                 lending_market_owner_keypair,
             )
         }
+        ("set-flash-loan-fee-exemption", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let exempt_pubkey = pubkey_of(arg_matches, "exempt").unwrap();
+            let fee_discount = value_of(arg_matches, "fee_discount").unwrap();
+
+            command_set_flash_loan_fee_exemption(
+                &config,
+                exempt_pubkey,
+                fee_discount,
+                lending_market_pubkey,
+                lending_market_owner_keypair,
+            )
+        }
+        ("remove-flash-loan-fee-exemption", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let exempt_pubkey = pubkey_of(arg_matches, "exempt").unwrap();
+
+            command_set_flash_loan_fee_exemption(
+                &config,
+                exempt_pubkey,
+                0,
+                lending_market_pubkey,
+                lending_market_owner_keypair,
+            )
+        }
+        ("show-flash-loan-fee-exemptions", Some(arg_matches)) => {
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+
+            command_show_flash_loan_fee_exemptions(&config, lending_market_pubkey)
+        }
+        ("add-swap-program", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let swap_program_id = pubkey_of(arg_matches, "swap_program").unwrap();
+
+            command_set_swap_program_approval(
+                &config,
+                swap_program_id,
+                true,
+                lending_market_pubkey,
+                lending_market_owner_keypair,
+            )
+        }
+        ("remove-swap-program", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let swap_program_id = pubkey_of(arg_matches, "swap_program").unwrap();
+
+            command_set_swap_program_approval(
+                &config,
+                swap_program_id,
+                false,
+                lending_market_pubkey,
+                lending_market_owner_keypair,
+            )
+        }
         _ => unreachable!(),
     }
     .map_err(|err| {
@@ -832,6 +1202,7 @@ fn command_add_reserve(
     pyth_price_pubkey: Pubkey,
     switchboard_feed_pubkey: Pubkey,
     liquidity_fee_receiver_keypair: Keypair,
+    collateral_fee_receiver_keypair: Keypair,
     source_liquidity: Token,
 ) -> CommandResult {
     let reserve_keypair = Keypair::new();
@@ -859,6 +1230,10 @@ fn command_add_reserve(
             "Adding liquidity fee receiver {}",
             liquidity_fee_receiver_keypair.pubkey()
         );
+        println!(
+            "Adding collateral fee receiver {}",
+            collateral_fee_receiver_keypair.pubkey()
+        );
         println!(
             "Adding user collateral {}",
             user_collateral_keypair.pubkey()
@@ -882,13 +1257,15 @@ fn command_add_reserve(
     let user_collateral_balance = token_account_balance;
     let liquidity_supply_balance = token_account_balance;
     let liquidity_fee_receiver_balance = token_account_balance;
+    let collateral_fee_receiver_balance = token_account_balance;
 
     let total_balance = reserve_balance
         + collateral_mint_balance
         + collateral_supply_balance
         + user_collateral_balance
         + liquidity_supply_balance
-        + liquidity_fee_receiver_balance;
+        + liquidity_fee_receiver_balance
+        + collateral_fee_receiver_balance;
 
     let mut transaction_1 = Transaction::new_with_payer(
         &[
@@ -940,6 +1317,13 @@ fn command_add_reserve(
                 Token::LEN as u64,
                 &spl_token::id(),
             ),
+            create_account(
+                &config.fee_payer.pubkey(),
+                &collateral_fee_receiver_keypair.pubkey(),
+                collateral_fee_receiver_balance,
+                Token::LEN as u64,
+                &spl_token::id(),
+            ),
         ],
         Some(&config.fee_payer.pubkey()),
     );
@@ -1007,6 +1391,7 @@ fn command_add_reserve(
             config.fee_payer.as_ref(),
             &liquidity_supply_keypair,
             &liquidity_fee_receiver_keypair,
+            &collateral_fee_receiver_keypair,
         ],
         recent_blockhash,
     );
@@ -1065,6 +1450,24 @@ fn command_update_reserve(
         reserve.config.liquidation_bonus = reserve_config.liquidation_bonus.unwrap();
     }
 
+    if reserve_config.max_liquidation_bonus.is_some() {
+        println!(
+            "Updating max_liquidation_bonus from {} to {}",
+            reserve.config.max_liquidation_bonus,
+            reserve_config.max_liquidation_bonus.unwrap(),
+        );
+        reserve.config.max_liquidation_bonus = reserve_config.max_liquidation_bonus.unwrap();
+    }
+
+    if reserve_config.protocol_liquidation_fee.is_some() {
+        println!(
+            "Updating protocol_liquidation_fee from {} to {}",
+            reserve.config.protocol_liquidation_fee,
+            reserve_config.protocol_liquidation_fee.unwrap(),
+        );
+        reserve.config.protocol_liquidation_fee = reserve_config.protocol_liquidation_fee.unwrap();
+    }
+
     if reserve_config.liquidation_threshold.is_some() {
         println!(
             "Updating liquidation_threshold from {} to {}",
@@ -1164,6 +1567,34 @@ fn command_update_reserve(
         reserve.config.fee_receiver = reserve_config.fee_receiver.unwrap();
     }
 
+    if reserve_config.collateral_fee_receiver.is_some() {
+        println!(
+            "Updating collateral_fee_receiver from {} to {}",
+            reserve.config.collateral_fee_receiver,
+            reserve_config.collateral_fee_receiver.unwrap(),
+        );
+        reserve.config.collateral_fee_receiver = reserve_config.collateral_fee_receiver.unwrap();
+    }
+
+    if reserve_config.loan_to_value_ratio_ramp.is_some() {
+        println!(
+            "Updating loan_to_value_ratio_ramp from {:?} to {:?}",
+            reserve.config.loan_to_value_ratio_ramp,
+            reserve_config.loan_to_value_ratio_ramp.unwrap(),
+        );
+        reserve.config.loan_to_value_ratio_ramp = reserve_config.loan_to_value_ratio_ramp.unwrap();
+    }
+
+    if reserve_config.liquidation_threshold_ramp.is_some() {
+        println!(
+            "Updating liquidation_threshold_ramp from {:?} to {:?}",
+            reserve.config.liquidation_threshold_ramp,
+            reserve_config.liquidation_threshold_ramp.unwrap(),
+        );
+        reserve.config.liquidation_threshold_ramp =
+            reserve_config.liquidation_threshold_ramp.unwrap();
+    }
+
     let mut new_pyth_product_pubkey = spl_token_lending::NULL_PUBKEY;
     if pyth_price_pubkey.is_some() {
         println!(
@@ -1209,6 +1640,116 @@ fn command_update_reserve(
     Ok(())
 }
 
+fn command_set_flash_loan_fee_exemption(
+    config: &Config,
+    exempt_pubkey: Pubkey,
+    fee_discount: u8,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    if fee_discount > 0 {
+        println!(
+            "Setting flash loan fee discount of {} to {}%",
+            exempt_pubkey, fee_discount
+        );
+    } else {
+        println!("Removing flash loan fee exemption of {}", exempt_pubkey);
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_flash_loan_fee_exemption(
+            config.lending_program_id,
+            exempt_pubkey,
+            fee_discount,
+            lending_market_pubkey,
+            lending_market_owner_keypair.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(transaction.message()))?;
+
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_show_flash_loan_fee_exemptions(
+    config: &Config,
+    lending_market_pubkey: Pubkey,
+) -> CommandResult {
+    let (flash_loan_fee_exemptions_pubkey, _bump_seed) =
+        find_flash_loan_fee_exemptions_address(&config.lending_program_id, &lending_market_pubkey);
+    println!(
+        "Flash loan fee exemptions {}",
+        flash_loan_fee_exemptions_pubkey
+    );
+
+    let flash_loan_fee_exemptions_account = config
+        .rpc_client
+        .get_account(&flash_loan_fee_exemptions_pubkey);
+    let flash_loan_fee_exemptions = match flash_loan_fee_exemptions_account {
+        Ok(account) => FlashLoanFeeExemptions::unpack_from_slice(account.data.borrow())?,
+        Err(_) => {
+            println!("No flash loan fee exemptions");
+            return Ok(());
+        }
+    };
+    if flash_loan_fee_exemptions.exemptions.is_empty() {
+        println!("No flash loan fee exemptions");
+    }
+    for exemption in flash_loan_fee_exemptions.exemptions {
+        println!(
+            "{}: {}% flash loan fee discount",
+            exemption.exempt, exemption.fee_discount
+        );
+    }
+    Ok(())
+}
+
+fn command_set_swap_program_approval(
+    config: &Config,
+    swap_program_id: Pubkey,
+    approved: bool,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    let instruction = if approved {
+        println!("Approving swap program {}", swap_program_id);
+        add_swap_program(
+            config.lending_program_id,
+            lending_market_pubkey,
+            lending_market_owner_keypair.pubkey(),
+            swap_program_id,
+        )
+    } else {
+        println!("Revoking swap program {}", swap_program_id);
+        remove_swap_program(
+            config.lending_program_id,
+            lending_market_pubkey,
+            lending_market_owner_keypair.pubkey(),
+            swap_program_id,
+        )
+    };
+
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction], Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(transaction.message()))?;
+
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
 // HELPERS
 
 fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
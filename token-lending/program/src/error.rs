@@ -163,6 +163,94 @@ pub enum LendingError {
     /// Null oracle config
     #[error("Null oracle config")]
     NullOracleConfig,
+    /// Reserve config change must be queued
+    #[error("Reserve config change must be queued until the lending market delay has elapsed")]
+    ReserveConfigTimelocked,
+    /// Reserve config change is not pending
+    #[error("Reserve config change is not pending")]
+    ReserveConfigNotPending,
+    /// Reserve config delay has not elapsed
+    #[error("Reserve config delay has not elapsed")]
+    ReserveConfigDelayNotElapsed,
+    /// Reserve config change exceeds the lending market bounds
+    #[error("Reserve config change exceeds the lending market bounds")]
+    ReserveConfigChangeOutOfBounds,
+
+    // 50
+    /// Lending market membership is invalid
+    #[error("Lending market membership is invalid")]
+    InvalidLendingMarketMembership,
+    /// Reserve status is invalid
+    #[error("Reserve status is invalid")]
+    InvalidReserveStatus,
+    /// Reserve does not accept new deposits or borrows
+    #[error("Reserve is not active")]
+    ReserveNotActive,
+    /// Reserve is frozen
+    #[error("Reserve is frozen")]
+    ReserveFrozen,
+    /// Reserve must be deprecated before it can be closed
+    #[error("Reserve is active")]
+    ReserveActive,
+
+    // 55
+    /// Reserve still holds deposits or borrows
+    #[error("Reserve is not empty")]
+    ReserveNotEmpty,
+    /// Token close account failed
+    #[error("Token close account failed")]
+    TokenCloseAccountFailed,
+    /// Amount received is below the minimum or amount paid is above the maximum requested
+    #[error("Amount is outside of the slippage tolerance")]
+    ExceededSlippage,
+    /// Flash borrow is not followed by a matching flash repay
+    #[error("Flash borrow has no matching flash repay")]
+    InvalidFlashBorrow,
+    /// Flash repay does not match the flash borrow it references
+    #[error("Flash repay does not match a flash borrow")]
+    InvalidFlashRepay,
+
+    // 60
+    /// Flash borrows cannot be nested
+    #[error("Flash borrows cannot be nested")]
+    NestedFlashBorrow,
+    /// Flash borrow and repay must be top-level transaction instructions
+    #[error("Flash borrow and repay cannot be called via CPI")]
+    FlashLoanCpi,
+    /// Flash loan fee exemptions cannot exceed the maximum
+    #[error("Flash loan fee exemptions limit exceeded")]
+    FlashLoanFeeExemptionsLimit,
+    /// Swap program is not approved by the lending market
+    #[error("Swap program is not approved")]
+    InvalidSwapProgram,
+    /// Obligation leverage is above the maximum
+    #[error("Leverage exceeds the maximum")]
+    LeverageTooHigh,
+
+    // 65
+    /// Obligation borrowed value is above its allowed borrow value
+    #[error("Obligation is unhealthy")]
+    ObligationUnhealthy,
+    /// Obligation delegation is invalid or does not grant the permission
+    #[error("Obligation delegation is invalid")]
+    InvalidObligationDelegation,
+    /// Obligation delegation value limit is exceeded
+    #[error("Obligation delegation value limit exceeded")]
+    ObligationDelegationLimit,
+    /// Obligation has delegations, which must be revoked before ownership can be transferred
+    #[error("Obligation has delegations")]
+    ObligationDelegated,
+    /// Borrow allowance is invalid or does not belong to the obligation, delegate and reserve
+    #[error("Borrow allowance is invalid")]
+    InvalidBorrowAllowance,
+
+    // 70
+    /// Borrow allowance is too small for the borrow amount
+    #[error("Borrow allowance exceeded")]
+    BorrowAllowanceExceeded,
+    /// Reserve loan to value ratio ramp has not finished
+    #[error("Reserve ramp in progress")]
+    ReserveRampInProgress,
 }
 
 impl From<LendingError> for ProgramError {
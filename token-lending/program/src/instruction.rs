@@ -2,16 +2,37 @@
 
 use crate::{
     error::LendingError,
-    state::{ReserveConfig, ReserveFees},
+    state::{
+        find_borrow_allowance_address, find_flash_loan_fee_exemptions_address,
+        find_lending_market_membership_address, find_obligation_address,
+        find_obligation_delegation_address, find_pending_reserve_config_address,
+        find_swap_program_approval_address, LiquidationConfig, ReserveConfig, ReserveConfigBounds,
+        ReserveFees, ReserveRamp, ReserveStatus,
+    },
 };
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     msg,
     program_error::ProgramError,
     pubkey::{Pubkey, PUBKEY_BYTES},
-    sysvar,
+    system_program, sysvar,
+};
+use std::{
+    convert::{TryFrom, TryInto},
+    mem::size_of,
 };
-use std::{convert::TryInto, mem::size_of};
+
+/// Maximum length of the instruction prefix used to call a flash loan receiver program
+pub const MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN: usize = 32;
+
+/// Flash loan receiver instruction prefix used when none is provided, a single tag of `0`
+pub const DEFAULT_FLASH_LOAN_RECEIVER_PREFIX: [u8; 1] = [0];
+
+/// Maximum number of reserves that can be lent from in a single `FlashLoanMultiple` instruction
+pub const MAX_FLASH_LOAN_RESERVES: usize = 5;
+
+/// Maximum number of operations in a single `Batch` instruction
+pub const MAX_BATCH_OPS: usize = 8;
 
 /// Instructions supported by the lending program.
 #[derive(Clone, Debug, PartialEq)]
@@ -64,13 +85,15 @@ pub enum LendingInstruction {
     ///   8. `[]` Switchboard price feed account. used as a backup oracle
     ///   9. `[writable]` Reserve collateral SPL Token mint - uninitialized.
     ///   10 `[writable]` Reserve collateral token supply - uninitialized.
-    ///   11 `[]` Lending market account.
-    ///   12 `[]` Derived lending market authority.
-    ///   13 `[signer]` Lending market owner.
-    ///   14 `[signer]` User transfer authority ($authority).
-    ///   15 `[]` Clock sysvar.
-    ///   16 `[]` Rent sysvar.
-    ///   17 `[]` Token program id.
+    ///   11 `[writable]` Reserve collateral fee receiver - uninitialized.
+    ///                     Owned by the lending market owner.
+    ///   12 `[]` Lending market account.
+    ///   13 `[]` Derived lending market authority.
+    ///   14 `[signer]` Lending market owner.
+    ///   15 `[signer]` User transfer authority ($authority).
+    ///   16 `[]` Clock sysvar.
+    ///   17 `[]` Rent sysvar.
+    ///   18 `[]` Token program id.
     InitReserve {
         /// Initial amount of liquidity to deposit into the new reserve
         liquidity_amount: u64,
@@ -108,6 +131,7 @@ pub enum LendingInstruction {
     ///   7. `[signer]` User transfer authority ($authority).
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
+    ///   10 `[optional]` Lending market membership of $authority, if the market is permissioned.
     DepositReserveLiquidity {
         /// Amount of liquidity to deposit in exchange for collateral tokens
         liquidity_amount: u64,
@@ -132,6 +156,8 @@ pub enum LendingInstruction {
     RedeemReserveCollateral {
         /// Amount of collateral tokens to redeem in exchange for liquidity
         collateral_amount: u64,
+        /// Minimum amount of liquidity to receive - 0 for no minimum
+        min_liquidity_amount: u64,
     },
 
     // 6
@@ -140,12 +166,21 @@ pub enum LendingInstruction {
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Obligation account - uninitialized.
+    ///                     PDA of the lending market, owner, seed and "obligation" if seeded.
     ///   1. `[]` Lending market account.
     ///   2. `[signer]` Obligation owner.
     ///   3. `[]` Clock sysvar.
     ///   4. `[]` Rent sysvar.
     ///   5. `[]` Token program id.
-    InitObligation,
+    ///   6. `[optional]` Lending market membership of the obligation owner, if the market is
+    ///                     permissioned. Required if seeded, but only checked if permissioned.
+    ///   7. `[signer, writable]` Payer for the seeded obligation account, if seeded.
+    ///   8. `[]` System program, if seeded.
+    InitObligation {
+        /// Seed index of a program-derived obligation - none for an obligation account created
+        /// by the client
+        seed: Option<u64>,
+    },
 
     // 7
     /// Refresh an obligation's accrued interest and collateral and liquidity prices. Requires
@@ -193,9 +228,10 @@ pub enum LendingInstruction {
     ///   3. `[writable]` Obligation account - refreshed.
     ///   4. `[]` Lending market account.
     ///   5. `[]` Derived lending market authority.
-    ///   6. `[signer]` Obligation owner.
+    ///   6. `[signer]` Obligation owner or delegate.
     ///   7. `[]` Clock sysvar.
     ///   8. `[]` Token program id.
+    ///   9. `[optional, writable]` Obligation delegation, if signed by a delegate.
     WithdrawObligationCollateral {
         /// Amount of collateral tokens to withdraw - u64::MAX for up to 100% of deposited amount
         collateral_amount: u64,
@@ -223,7 +259,10 @@ pub enum LendingInstruction {
     BorrowObligationLiquidity {
         /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
         liquidity_amount: u64,
-        // @TODO: slippage constraint - https://git.io/JmV67
+        /// Minimum amount of liquidity to receive after fees - 0 for no minimum
+        min_receive_amount: u64,
+        /// Maximum amount of liquidity to borrow including fees - u64::MAX for no maximum
+        max_borrow_amount_including_fees: u64,
     },
 
     // 11
@@ -261,15 +300,19 @@ pub enum LendingInstruction {
     ///   3. `[writable]` Repay reserve liquidity supply SPL Token account.
     ///   4. `[]` Withdraw reserve account - refreshed.
     ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account.
-    ///   6. `[writable]` Obligation account - refreshed.
-    ///   7. `[]` Lending market account.
-    ///   8. `[]` Derived lending market authority.
-    ///   9. `[signer]` User transfer authority ($authority).
-    ///   10 `[]` Clock sysvar.
-    ///   11 `[]` Token program id.
+    ///   6. `[writable]` Withdraw reserve collateral fee receiver account.
+    ///                     Receives the protocol liquidation fee.
+    ///   7. `[writable]` Obligation account - refreshed.
+    ///   8. `[]` Lending market account.
+    ///   9. `[]` Derived lending market authority.
+    ///   10 `[signer]` User transfer authority ($authority).
+    ///   11 `[]` Clock sysvar.
+    ///   12 `[]` Token program id.
     LiquidateObligation {
         /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
         liquidity_amount: u64,
+        /// Minimum amount of collateral to receive - 0 for no minimum
+        min_collateral_amount: u64,
     },
 
     // 13
@@ -290,13 +333,18 @@ pub enum LendingInstruction {
     ///   6. `[]` Derived lending market authority.
     ///   7. `[]` Token program id.
     ///   8. `[]` Flash loan receiver program id.
-    ///             Must implement an instruction that has tag of 0 and a signature of `(amount: u64)`
+    ///             Must implement an instruction whose data is the receiver instruction prefix
+    ///             followed by `(amount: u64)`.
     ///             This instruction must return the amount to the source liquidity account.
+    ///   9. `[optional]` Flash loan fee exemptions of the lending market, required if and only if
+    ///                     `apply_fee_exemptions` is set, to reduce the fee for an exempt receiver
+    ///                     program or signer. Not passed to the receiver program.
     ///   .. `[any]` Additional accounts expected by the receiving program's `ReceiveFlashLoan` instruction.
     ///
-    ///   The flash loan receiver program that is to be invoked should contain an instruction with
-    ///   tag `0` and accept the total amount (including fee) that needs to be returned back after
-    ///   its execution has completed.
+    ///   The flash loan receiver program that is to be invoked should contain an instruction
+    ///   starting with the receiver instruction prefix, e.g. a tag of `0` or an 8-byte sighash, and
+    ///   accept the total amount (including fee) that needs to be returned back after its execution
+    ///   has completed.
     ///
     ///   Flash loan receiver should have an instruction with the following signature:
     ///
@@ -311,6 +359,13 @@ pub enum LendingInstruction {
     FlashLoan {
         /// The amount that is to be borrowed - u64::MAX for up to 100% of available liquidity
         amount: u64,
+        /// Instruction data the amount to return is appended to when calling the receiver program,
+        /// up to MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN bytes - DEFAULT_FLASH_LOAN_RECEIVER_PREFIX if
+        /// omitted
+        receiver_instruction_prefix: Vec<u8>,
+        /// Whether the flash loan fee exemptions account follows the receiver program id - false
+        /// if omitted
+        apply_fee_exemptions: bool,
     },
 
     // 14
@@ -334,6 +389,7 @@ pub enum LendingInstruction {
     ///   12 `[signer]` User transfer authority ($authority).
     ///   13 `[]` Clock sysvar.
     ///   14 `[]` Token program id.
+    ///   15 `[optional]` Lending market membership of $authority, if the market is permissioned.
     DepositReserveLiquidityAndObligationCollateral {
         /// Amount of liquidity to deposit in exchange
         liquidity_amount: u64,
@@ -354,13 +410,16 @@ pub enum LendingInstruction {
     ///   6. `[writable]` User liquidity token account.
     ///   7. `[writable]` Reserve collateral SPL Token mint.
     ///   8. `[writable]` Reserve liquidity supply SPL Token account.
-    ///   9. `[signer]` Obligation owner
+    ///   9. `[signer]` Obligation owner or delegate.
     ///   10 `[signer]` User transfer authority ($authority).
     ///   11. `[]` Clock sysvar.
     ///   12. `[]` Token program id.
+    ///   13 `[optional, writable]` Obligation delegation, if signed by a delegate.
     WithdrawObligationCollateralAndRedeemReserveCollateral {
         /// liquidity_amount is the amount of collateral tokens to withdraw
         collateral_amount: u64,
+        /// Minimum amount of liquidity to receive - 0 for no minimum
+        min_liquidity_amount: u64,
     },
 
     // 16
@@ -375,844 +434,3646 @@ pub enum LendingInstruction {
     ///   5 `[]` Pyth product key.
     ///   6 `[]` Pyth price key.
     ///   7 `[]` Switchboard key.
+    ///   8 `[optional]` Reserve collateral fee receiver SPL Token account.
+    ///                    Required if the config changes the collateral fee receiver.
     UpdateReserveConfig {
         /// Reserve config to update to
         config: ReserveConfig,
     },
-}
 
-impl LendingInstruction {
-    /// Unpacks a byte buffer into a [LendingInstruction](enum.LendingInstruction.html).
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (&tag, rest) = input
-            .split_first()
-            .ok_or(LendingError::InstructionUnpackError)?;
-        Ok(match tag {
-            0 => {
-                let (owner, rest) = Self::unpack_pubkey(rest)?;
-                let (quote_currency, _rest) = Self::unpack_bytes32(rest)?;
-                Self::InitLendingMarket {
-                    owner,
-                    quote_currency: *quote_currency,
-                }
-            }
-            1 => {
-                let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
-                Self::SetLendingMarketOwner { new_owner }
-            }
-            2 => {
-                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
-                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
-                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
-                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
-                let (deposit_limit, rest) = Self::unpack_u64(rest)?;
-                let (borrow_limit, rest) = Self::unpack_u64(rest)?;
-                let (fee_receiver, _) = Self::unpack_pubkey(rest)?;
-                Self::InitReserve {
-                    liquidity_amount,
-                    config: ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees: ReserveFees {
-                            borrow_fee_wad,
-                            flash_loan_fee_wad,
-                            host_fee_percentage,
-                        },
-                        deposit_limit,
-                        borrow_limit,
-                        fee_receiver,
-                    },
-                }
-            }
-            3 => Self::RefreshReserve,
-            4 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositReserveLiquidity { liquidity_amount }
-            }
-            5 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::RedeemReserveCollateral { collateral_amount }
-            }
-            6 => Self::InitObligation,
-            7 => Self::RefreshObligation,
-            8 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositObligationCollateral { collateral_amount }
-            }
-            9 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawObligationCollateral { collateral_amount }
-            }
-            10 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::BorrowObligationLiquidity { liquidity_amount }
-            }
-            11 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::RepayObligationLiquidity { liquidity_amount }
-            }
-            12 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::LiquidateObligation { liquidity_amount }
-            }
-            13 => {
-                let (amount, _rest) = Self::unpack_u64(rest)?;
-                Self::FlashLoan { amount }
-            }
-            14 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount }
-            }
-            15 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount }
-            }
-            16 => {
-                let (optimal_utilization_rate, _rest) = Self::unpack_u8(rest)?;
-                let (loan_to_value_ratio, _rest) = Self::unpack_u8(_rest)?;
-                let (liquidation_bonus, _rest) = Self::unpack_u8(_rest)?;
-                let (liquidation_threshold, _rest) = Self::unpack_u8(_rest)?;
-                let (min_borrow_rate, _rest) = Self::unpack_u8(_rest)?;
-                let (optimal_borrow_rate, _rest) = Self::unpack_u8(_rest)?;
-                let (max_borrow_rate, _rest) = Self::unpack_u8(_rest)?;
-                let (borrow_fee_wad, _rest) = Self::unpack_u64(_rest)?;
-                let (flash_loan_fee_wad, _rest) = Self::unpack_u64(_rest)?;
-                let (host_fee_percentage, _rest) = Self::unpack_u8(_rest)?;
-                let (deposit_limit, _rest) = Self::unpack_u64(_rest)?;
-                let (borrow_limit, _rest) = Self::unpack_u64(_rest)?;
-                let (fee_receiver, _) = Self::unpack_pubkey(_rest)?;
-
-                Self::UpdateReserveConfig {
-                    config: ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees: ReserveFees {
-                            borrow_fee_wad,
-                            flash_loan_fee_wad,
-                            host_fee_percentage,
-                        },
-                        deposit_limit,
-                        borrow_limit,
-                        fee_receiver,
-                    },
-                }
-            }
-            _ => {
-                msg!("Instruction cannot be unpacked");
-                return Err(LendingError::InstructionUnpackError.into());
-            }
-        })
-    }
+    // 17
+    /// Sets the minimum number of slots between queueing and applying a reserve config change.
+    /// Increases take effect immediately, decreases once the current delay has elapsed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    ///   2. `[]` Clock sysvar.
+    SetReserveConfigDelay {
+        /// Delay in slots
+        delay_slots: u64,
+    },
 
-    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
-        if input.len() < 8 {
-            msg!("u64 cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (bytes, rest) = input.split_at(8);
-        let value = bytes
-            .get(..8)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u64::from_le_bytes)
-            .ok_or(LendingError::InstructionUnpackError)?;
-        Ok((value, rest))
-    }
+    // 18
+    /// Queues a reserve config and price oracle change, replacing any pending change.
+    /// Risk reducing changes can be applied immediately, others once the lending market
+    /// delay has elapsed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` Reserve account.
+    ///   1. `[writable]` Pending reserve config account.
+    ///                     PDA of the reserve address and "pending_config".
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer, writable]` Lending market owner.
+    ///   4. `[]` Pyth product key.
+    ///   5. `[]` Pyth price key.
+    ///   6. `[]` Switchboard key.
+    ///   7. `[]` Clock sysvar.
+    ///   8. `[]` Rent sysvar.
+    ///   9. `[]` System program.
+    ///   10 `[optional]` Reserve collateral fee receiver SPL Token account.
+    ///                     Required if the config changes the collateral fee receiver.
+    QueueReserveConfig {
+        /// Reserve config to update to
+        config: ReserveConfig,
+    },
 
-    fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
-        if input.is_empty() {
-            msg!("u8 cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (bytes, rest) = input.split_at(1);
-        let value = bytes
-            .get(..1)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u8::from_le_bytes)
-            .ok_or(LendingError::InstructionUnpackError)?;
-        Ok((value, rest))
-    }
+    // 19
+    /// Applies a pending reserve config change once its delay has elapsed, closing the pending
+    /// reserve config account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[writable]` Pending reserve config account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[writable]` Lending market owner, which receives the reclaimed lamports.
+    ///   4. `[]` Clock sysvar.
+    ApplyReserveConfig,
 
-    fn unpack_bytes32(input: &[u8]) -> Result<(&[u8; 32], &[u8]), ProgramError> {
-        if input.len() < 32 {
-            msg!("32 bytes cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (bytes, rest) = input.split_at(32);
-        Ok((
-            bytes
-                .try_into()
-                .map_err(|_| LendingError::InstructionUnpackError)?,
-            rest,
-        ))
-    }
+    // 20
+    /// Cancels a pending reserve config change, closing the pending reserve config account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` Reserve account.
+    ///   1. `[writable]` Pending reserve config account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer, writable]` Lending market owner.
+    CancelReserveConfig,
 
-    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
-        if input.len() < PUBKEY_BYTES {
-            msg!("Pubkey cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (key, rest) = input.split_at(PUBKEY_BYTES);
-        let pk = Pubkey::new(key);
-        Ok((pk, rest))
-    }
+    // 21
+    /// Sets the limits on how far and how often reserve risk parameters can change. Tightened
+    /// bounds take effect immediately, while loosened or removed bounds only take effect after the
+    /// current minimum update interval has elapsed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetReserveConfigBounds {
+        /// Reserve config bounds
+        bounds: ReserveConfigBounds,
+    },
 
-    /// Packs a [LendingInstruction](enum.LendingInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match *self {
-            Self::InitLendingMarket {
-                owner,
-                quote_currency,
-            } => {
-                buf.push(0);
-                buf.extend_from_slice(owner.as_ref());
-                buf.extend_from_slice(quote_currency.as_ref());
-            }
-            Self::SetLendingMarketOwner { new_owner } => {
-                buf.push(1);
-                buf.extend_from_slice(new_owner.as_ref());
-            }
-            Self::InitReserve {
-                liquidity_amount,
-                config:
-                    ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees:
-                            ReserveFees {
-                                borrow_fee_wad,
-                                flash_loan_fee_wad,
-                                host_fee_percentage,
-                            },
-                        deposit_limit,
-                        borrow_limit,
-                        fee_receiver,
-                    },
-            } => {
-                buf.push(2);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-                buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
-                buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
-                buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
-                buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&flash_loan_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
-                buf.extend_from_slice(&deposit_limit.to_le_bytes());
-                buf.extend_from_slice(&borrow_limit.to_le_bytes());
-                buf.extend_from_slice(&fee_receiver.to_bytes());
-            }
-            Self::RefreshReserve => {
-                buf.push(3);
-            }
-            Self::DepositReserveLiquidity { liquidity_amount } => {
-                buf.push(4);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::RedeemReserveCollateral { collateral_amount } => {
-                buf.push(5);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::InitObligation => {
-                buf.push(6);
-            }
-            Self::RefreshObligation => {
-                buf.push(7);
-            }
-            Self::DepositObligationCollateral { collateral_amount } => {
-                buf.push(8);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::WithdrawObligationCollateral { collateral_amount } => {
-                buf.push(9);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::BorrowObligationLiquidity { liquidity_amount } => {
-                buf.push(10);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::RepayObligationLiquidity { liquidity_amount } => {
-                buf.push(11);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::LiquidateObligation { liquidity_amount } => {
-                buf.push(12);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::FlashLoan { amount } => {
-                buf.push(13);
-                buf.extend_from_slice(&amount.to_le_bytes());
-            }
-            Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
-                buf.push(14);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount } => {
-                buf.push(15);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::UpdateReserveConfig { config } => {
-                buf.push(16);
-                buf.extend_from_slice(&config.optimal_utilization_rate.to_le_bytes());
-                buf.extend_from_slice(&config.loan_to_value_ratio.to_le_bytes());
-                buf.extend_from_slice(&config.liquidation_bonus.to_le_bytes());
-                buf.extend_from_slice(&config.liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&config.min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&config.optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&config.max_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&config.fees.borrow_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&config.fees.flash_loan_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&config.fees.host_fee_percentage.to_le_bytes());
-                buf.extend_from_slice(&config.deposit_limit.to_le_bytes());
-                buf.extend_from_slice(&config.borrow_limit.to_le_bytes());
-                buf.extend_from_slice(&config.fee_receiver.to_bytes());
-            }
-        }
-        buf
-    }
-}
+    // 22
+    /// Turns the lending market allow-list on or off. When on, opening obligations and
+    /// depositing requires a lending market membership.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetLendingMarketPermissioned {
+        /// Whether the lending market requires membership
+        permissioned: bool,
+    },
 
-/// Creates an 'InitLendingMarket' instruction.
-pub fn init_lending_market(
-    program_id: Pubkey,
-    owner: Pubkey,
-    quote_currency: [u8; 32],
-    lending_market_pubkey: Pubkey,
-    oracle_program_id: Pubkey,
-    switchboard_oracle_program_id: Pubkey,
-) -> Instruction {
-    Instruction {
-        program_id,
-        accounts: vec![
-            AccountMeta::new(lending_market_pubkey, false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-            AccountMeta::new_readonly(oracle_program_id, false),
-            AccountMeta::new_readonly(switchboard_oracle_program_id, false),
-        ],
-        data: LendingInstruction::InitLendingMarket {
-            owner,
+    // 23
+    /// Adds a member to the lending market allow-list.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market membership account.
+    ///                     PDA of the lending market address, member address and "membership".
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer, writable]` Lending market owner.
+    ///   3. `[]` Rent sysvar.
+    ///   4. `[]` System program.
+    AddLendingMarketMember {
+        /// Member address
+        member: Pubkey,
+    },
+
+    // 24
+    /// Removes a member from the lending market allow-list and closes its membership account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market membership account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer, writable]` Lending market owner.
+    RemoveLendingMarketMember,
+
+    // 25
+    /// Sets the lifecycle status of a reserve. Reduce-only reserves reject new deposits and
+    /// borrows and ramp their loan to value ratio down to a target, within the lending market
+    /// reserve config bounds. Frozen reserves only accept repayments and liquidations. Scheduled
+    /// ramps are kept when the status changes.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    ///   3. `[]` Clock sysvar.
+    SetReserveStatus {
+        /// New reserve status
+        status: ReserveStatus,
+        /// Loan to value ratio reached at the end of the ramp, used when reduce-only
+        loan_to_value_ratio_target: u8,
+        /// Number of slots the loan to value ratio ramps down over, used when reduce-only
+        ramp_slots: u64,
+    },
+
+    // 26
+    /// Closes a reserve which is no longer active and holds no deposits or borrows. Remaining
+    /// liquidity is sent to the reserve liquidity fee receiver. A pending reserve config change is
+    /// closed with the reserve. SPL Token mints cannot be closed, so the reserve collateral mint is
+    /// left in place.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Reserve liquidity fee receiver account.
+    ///   3. `[writable]` Reserve collateral supply SPL Token account.
+    ///   4. `[]` Lending market account.
+    ///   5. `[]` Derived lending market authority.
+    ///   6. `[signer]` Lending market owner.
+    ///   7. `[writable]` Destination account for the reclaimed lamports.
+    ///   8. `[]` Token program id.
+    ///   9. `[writable]` Pending reserve config account.
+    ///                     PDA of the reserve address and "pending_config".
+    CloseReserve,
+
+    // 27
+    /// Sets the lending market close factor, full liquidation thresholds and close amount.
+    /// Changes are limited by the reserve config bounds and take effect once the reserve config
+    /// delay has elapsed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    ///   2. `[]` Clock sysvar.
+    SetLiquidationConfig {
+        /// Liquidation config
+        config: LiquidationConfig,
+    },
+
+    // 28
+    /// Combines LiquidateObligation and RedeemReserveCollateral, paying out the withdraw reserve
+    /// underlying liquidity to the liquidator. Fails if the withdraw reserve does not have enough
+    /// available liquidity to redeem the collateral received.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///                     $authority can burn the collateral received.
+    ///   2. `[writable]` Destination liquidity token account.
+    ///                     Minted by withdraw reserve liquidity mint.
+    ///   3. `[writable]` Repay reserve account - refreshed.
+    ///   4. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   5. `[writable]` Withdraw reserve account - refreshed.
+    ///   6. `[writable]` Withdraw reserve collateral SPL Token mint.
+    ///   7. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   8. `[writable]` Withdraw reserve liquidity supply SPL Token account.
+    ///   9. `[writable]` Withdraw reserve collateral fee receiver account.
+    ///                     Receives the protocol liquidation fee.
+    ///   10 `[writable]` Obligation account - refreshed.
+    ///   11 `[]` Lending market account.
+    ///   12 `[]` Derived lending market authority.
+    ///   13 `[signer]` User transfer authority ($authority).
+    ///   14 `[]` Clock sysvar.
+    ///   15 `[]` Token program id.
+    LiquidateObligationAndRedeemReserveCollateral {
+        /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
+        liquidity_amount: u64,
+        /// Minimum amount of withdraw reserve liquidity to receive - 0 for no minimum
+        min_liquidity_amount: u64,
+    },
+
+    // 29
+    /// Flash borrow reserve liquidity. Must be followed in the same transaction by a
+    /// FlashRepayReserveLiquidity instruction for the same reserve and amount. Cannot be nested
+    /// or called via CPI.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Must match the reserve liquidity supply.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Instructions sysvar.
+    ///   6. `[]` Token program id.
+    FlashBorrowReserveLiquidity {
+        /// Amount of liquidity to flash borrow
+        liquidity_amount: u64,
+    },
+
+    // 30
+    /// Repay reserve liquidity flash borrowed earlier in the same transaction, plus the flash loan
+    /// fee. Cannot be called via CPI.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount plus fees.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Must match the reserve liquidity supply.
+    ///   2. `[writable]` Flash loan fee receiver account.
+    ///                     Must match the reserve liquidity fee receiver.
+    ///   3. `[writable]` Host fee receiver.
+    ///   4. `[writable]` Reserve account.
+    ///   5. `[]` Lending market account.
+    ///   6. `[signer]` User transfer authority ($authority).
+    ///   7. `[]` Instructions sysvar.
+    ///   8. `[]` Token program id.
+    FlashRepayReserveLiquidity {
+        /// Amount of liquidity flash borrowed, excluding fees
+        liquidity_amount: u64,
+        /// Index of the FlashBorrowReserveLiquidity instruction in the transaction
+        borrow_instruction_index: u8,
+    },
+
+    // 31
+    /// Make a flash loan from several reserves at once, calling the receiver program a single time.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` Lending market account.
+    ///   1. `[]` Derived lending market authority.
+    ///   2. `[]` Token program id.
+    ///   3. `[]` Flash loan receiver program id.
+    ///             Must implement an instruction whose data is the receiver instruction prefix
+    ///             followed by `(amount: u64)` for each reserve, in order.
+    ///             This instruction must return each amount to its source liquidity account.
+    ///   For each reserve, in the same order as the amounts:
+    ///     `[writable]` Source liquidity token account.
+    ///                    Must match the reserve liquidity supply.
+    ///     `[writable]` Destination liquidity token account.
+    ///                    Minted by reserve liquidity mint.
+    ///     `[writable]` Reserve account.
+    ///     `[writable]` Flash loan fee receiver account.
+    ///                    Must match the reserve liquidity fee receiver.
+    ///     `[writable]` Host fee receiver.
+    ///   .. `[optional]` Flash loan fee exemptions of the lending market, required if and only if
+    ///                     `apply_fee_exemptions` is set, to reduce the fee for an exempt receiver
+    ///                     program or signer. Not passed to the receiver program.
+    ///   .. `[any]` Additional accounts expected by the receiving program's `ReceiveFlashLoan` instruction.
+    ///
+    ///   Flash loan receiver should have an instruction with the following signature:
+    ///
+    ///   For each reserve, in the same order as the amounts:
+    ///     `[writable]` Source liquidity (matching the destination from above).
+    ///     `[writable]` Destination liquidity (matching the source from above).
+    ///   .. `[]` Token program id
+    ///   .. `[any]` Additional accounts provided to the lending program's `FlashLoanMultiple`
+    ///              instruction above.
+    ///   ReceiveFlashLoan {
+    ///       // Amount that must be repaid by the receiver program, for each reserve
+    ///       amounts: [u64]
+    ///   }
+    FlashLoanMultiple {
+        /// The amount to borrow from each reserve, up to MAX_FLASH_LOAN_RESERVES - u64::MAX for up
+        /// to 100% of a reserve's available liquidity
+        amounts: Vec<u64>,
+        /// Instruction data the amounts to return are appended to when calling the receiver
+        /// program, up to MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN bytes
+        receiver_instruction_prefix: Vec<u8>,
+        /// Whether the flash loan fee exemptions account follows the reserve accounts - false if
+        /// omitted
+        apply_fee_exemptions: bool,
+    },
+
+    // 32
+    /// Sets the flash loan fee discount of a receiver program or signer, creating the lending
+    /// market's flash loan fee exemptions if needed. A discount of 0 removes the exemption.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Flash loan fee exemptions account.
+    ///                     PDA of the lending market address and "flash_loan_fee_exemptions".
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer, writable]` Lending market owner.
+    ///   3. `[]` Rent sysvar.
+    ///   4. `[]` System program.
+    SetFlashLoanFeeExemption {
+        /// Flash loan receiver program id or signer address
+        exempt: Pubkey,
+        /// Discount on the reserve flash loan fee, as a percentage - 100 for no fee
+        fee_discount: u8,
+    },
+
+    // 33
+    /// Repay borrowed liquidity with collateral deposited in the same reserve. The collateral is
+    /// burned at the current collateral exchange rate to settle the borrow, without moving
+    /// liquidity out of the reserve supply.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account - refreshed.
+    ///   1. `[writable]` Reserve collateral supply SPL Token account.
+    ///   2. `[writable]` Reserve collateral SPL Token mint.
+    ///   3. `[writable]` Obligation account - refreshed.
+    ///   4. `[]` Lending market account.
+    ///   5. `[]` Derived lending market authority.
+    ///   6. `[signer]` Obligation owner or delegate.
+    ///   7. `[]` Clock sysvar.
+    ///   8. `[]` Token program id.
+    ///   9. `[optional]` Obligation delegation, if signed by a delegate.
+    RepayWithCollateral {
+        /// Amount of collateral to burn - u64::MAX for as much as is needed to repay the borrow
+        collateral_amount: u64,
+    },
+
+    // 34
+    /// Swap obligation collateral from one reserve into another through an approved swap
+    /// program. The withdrawn collateral is redeemed, the liquidity is swapped using the SPL
+    /// token-swap `Swap` instruction layout, and the output is deposited as collateral. Obligation
+    /// health is only checked once the swap is complete.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Withdraw reserve account - refreshed.
+    ///   1. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   2. `[writable]` Withdraw reserve collateral SPL Token mint.
+    ///   3. `[writable]` Withdraw reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Deposit reserve account - refreshed.
+    ///   5. `[writable]` Deposit reserve liquidity supply SPL Token account.
+    ///   6. `[writable]` Deposit reserve collateral SPL Token mint.
+    ///   7. `[writable]` Deposit reserve collateral supply SPL Token account.
+    ///   8. `[writable]` Source liquidity token account.
+    ///                     Receives the redeemed liquidity and is swapped from.
+    ///   9. `[writable]` Destination liquidity token account.
+    ///                     Swapped into, $authority can transfer $amount_out.
+    ///   10 `[writable]` Obligation account - refreshed.
+    ///   11 `[]` Lending market account.
+    ///   12 `[]` Derived lending market authority.
+    ///   13 `[signer]` Obligation owner, also the swap user transfer authority.
+    ///   14 `[]` Clock sysvar.
+    ///   15 `[]` Token program id.
+    ///   16 `[]` Lending market membership of the obligation owner.
+    ///                     Only checked if the market is permissioned.
+    ///   17 `[]` Swap program approval account.
+    ///                     PDA of the lending market address, swap program id and "swap_program".
+    ///   18 `[]` Swap program id.
+    ///   .. `[any]` Accounts expected by the swap program's `Swap` instruction, in order.
+    SwapCollateral {
+        /// Amount of collateral to swap - u64::MAX for all of the deposited collateral
+        collateral_amount: u64,
+        /// Minimum amount of deposit reserve liquidity to receive from the swap
+        minimum_amount_out: u64,
+    },
+
+    // 35
+    /// Approves a swap program for collateral swaps in the lending market.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Swap program approval account.
+    ///                     PDA of the lending market address, swap program id and "swap_program".
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer, writable]` Lending market owner.
+    ///   3. `[]` Rent sysvar.
+    ///   4. `[]` System program.
+    AddSwapProgram {
+        /// Swap program id
+        swap_program_id: Pubkey,
+    },
+
+    // 36
+    /// Revokes a swap program approval and closes its approval account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Swap program approval account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer, writable]` Lending market owner.
+    RemoveSwapProgram,
+
+    // 37
+    /// Move a borrow from one reserve to another reserve of the same liquidity mint. Borrows from
+    /// the borrow reserve, charging its borrow fee, to repay the repay reserve within the same
+    /// obligation. Obligation health is only checked after both legs.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Repay reserve account - refreshed.
+    ///   1. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Borrow reserve account - refreshed.
+    ///   3. `[writable]` Borrow reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   5. `[writable]` Obligation account - refreshed.
+    ///   6. `[]` Lending market account.
+    ///   7. `[]` Derived lending market authority.
+    ///   8. `[signer]` Obligation owner.
+    ///   9. `[]` Clock sysvar.
+    ///   10 `[]` Token program id.
+    ///   11 `[optional, writable]` Host fee receiver account.
+    RefinanceBorrow {
+        /// Amount of liquidity to refinance - u64::MAX for 100% of borrowed amount
+        liquidity_amount: u64,
+        /// Maximum amount to borrow from the borrow reserve, including fees
+        max_borrow_amount_including_fees: u64,
+    },
+
+    // 38
+    /// Open or increase a leveraged position in a single instruction. Liquidity is borrowed from
+    /// the borrow reserve before any collateral backs it, swapped through an approved swap program
+    /// and the output is deposited as obligation collateral. Obligation health and leverage are
+    /// only checked once the swapped collateral is deposited.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Borrow reserve account - refreshed.
+    ///   1. `[writable]` Borrow reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   3. `[writable]` Host fee receiver.
+    ///   4. `[writable]` Deposit reserve account - refreshed.
+    ///   5. `[writable]` Deposit reserve liquidity supply SPL Token account.
+    ///   6. `[writable]` Deposit reserve collateral SPL Token mint.
+    ///   7. `[writable]` Deposit reserve collateral supply SPL Token account.
+    ///   8. `[writable]` Source liquidity token account.
+    ///                     Receives the borrowed liquidity and is swapped from.
+    ///   9. `[writable]` Destination liquidity token account.
+    ///                     Swapped into, $authority can transfer $amount_out.
+    ///   10 `[writable]` Obligation account - refreshed.
+    ///   11 `[]` Lending market account.
+    ///   12 `[]` Derived lending market authority.
+    ///   13 `[signer]` Obligation owner, also the swap user transfer authority.
+    ///   14 `[]` Clock sysvar.
+    ///   15 `[]` Token program id.
+    ///   16 `[]` Lending market membership of the obligation owner.
+    ///                     Only checked if the market is permissioned.
+    ///   17 `[]` Swap program approval account.
+    ///                     PDA of the lending market address, swap program id and "swap_program".
+    ///   18 `[]` Swap program id.
+    ///   .. `[any]` Accounts expected by the swap program's `Swap` instruction, in order.
+    OpenLeveragedPosition {
+        /// Amount of liquidity to borrow and swap
+        liquidity_amount: u64,
+        /// Minimum amount of deposit reserve liquidity to receive from the swap
+        minimum_amount_out: u64,
+        /// Maximum leverage of the obligation after the position is opened, as a percentage -
+        /// deposited value over deposited value less borrowed value
+        max_leverage_percent: u16,
+    },
+
+    // 39
+    /// Close or reduce a leveraged position in a single instruction. Obligation collateral is
+    /// withdrawn and redeemed, swapped through an approved swap program and the output repays
+    /// the borrow. Any output above the borrowed amount is left in the destination liquidity
+    /// account. Obligation health is only checked once the borrow is repaid.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Withdraw reserve account - refreshed.
+    ///   1. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   2. `[writable]` Withdraw reserve collateral SPL Token mint.
+    ///   3. `[writable]` Withdraw reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Repay reserve account - refreshed.
+    ///   5. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   6. `[writable]` Source liquidity token account.
+    ///                     Receives the redeemed liquidity and is swapped from.
+    ///   7. `[writable]` Destination liquidity token account.
+    ///                     Swapped into, $authority can transfer the repay amount.
+    ///   8. `[writable]` Obligation account - refreshed.
+    ///   9. `[]` Lending market account.
+    ///   10 `[]` Derived lending market authority.
+    ///   11 `[signer]` Obligation owner, also the swap user transfer authority.
+    ///   12 `[]` Clock sysvar.
+    ///   13 `[]` Token program id.
+    ///   14 `[]` Swap program approval account.
+    ///                     PDA of the lending market address, swap program id and "swap_program".
+    ///   15 `[]` Swap program id.
+    ///   .. `[any]` Accounts expected by the swap program's `Swap` instruction, in order.
+    CloseLeveragedPosition {
+        /// Amount of collateral to withdraw and swap - u64::MAX for all of the deposited collateral
+        collateral_amount: u64,
+        /// Minimum amount of repay reserve liquidity to receive from the swap
+        minimum_amount_out: u64,
+    },
+
+    // 40
+    /// Combines DepositReserveLiquidity, DepositObligationCollateral, RefreshObligation and
+    /// BorrowObligationLiquidity. Obligation health is only checked by the borrow.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     $authority can transfer $deposit_liquidity_amount.
+    ///   1. `[writable]` User collateral token account.
+    ///                     Receives the minted collateral before it is deposited.
+    ///   2. `[writable]` Deposit reserve account - refreshed.
+    ///   3. `[writable]` Deposit reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Deposit reserve collateral SPL Token mint.
+    ///   5. `[writable]` Deposit reserve collateral supply SPL Token account.
+    ///   6. `[writable]` Borrow reserve account - refreshed.
+    ///   7. `[writable]` Borrow reserve liquidity supply SPL Token account.
+    ///   8. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   9. `[writable]` Destination liquidity token account.
+    ///   10 `[writable]` Obligation account.
+    ///   11 `[]` Lending market account.
+    ///   12 `[]` Derived lending market authority.
+    ///   13 `[signer]` Obligation owner.
+    ///   14 `[signer]` User transfer authority ($authority).
+    ///   15 `[]` Clock sysvar.
+    ///   16 `[]` Token program id.
+    ///   17 `[]` Lending market membership of $authority.
+    ///                     Only checked if the market is permissioned.
+    ///   .. `[]` Obligation deposit reserves followed by borrow reserves - refreshed, as for
+    ///             RefreshObligation once the collateral is deposited.
+    DepositReserveLiquidityAndBorrowObligationLiquidity {
+        /// Amount of liquidity to deposit as collateral
+        deposit_liquidity_amount: u64,
+        /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
+        borrow_liquidity_amount: u64,
+        /// Minimum amount of liquidity to receive, after fees
+        min_receive_amount: u64,
+        /// Maximum amount to borrow, including fees
+        max_borrow_amount_including_fees: u64,
+    },
+
+    // 41
+    /// Combines RepayObligationLiquidity, RefreshObligation, WithdrawObligationCollateral and
+    /// RedeemReserveCollateral. Obligation health is only checked by the withdraw.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     $authority can transfer $repay_liquidity_amount.
+    ///   1. `[writable]` Repay reserve account - refreshed.
+    ///   2. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   3. `[writable]` Withdraw reserve account - refreshed.
+    ///   4. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   5. `[writable]` Withdraw reserve collateral SPL Token mint.
+    ///   6. `[writable]` Withdraw reserve liquidity supply SPL Token account.
+    ///   7. `[writable]` User collateral token account.
+    ///                     Receives the withdrawn collateral before it is redeemed.
+    ///   8. `[writable]` Destination liquidity token account.
+    ///   9. `[writable]` Obligation account.
+    ///   10 `[]` Lending market account.
+    ///   11 `[]` Derived lending market authority.
+    ///   12 `[signer]` Obligation owner.
+    ///   13 `[signer]` User transfer authority ($authority).
+    ///   14 `[]` Clock sysvar.
+    ///   15 `[]` Token program id.
+    ///   .. `[]` Obligation deposit reserves followed by borrow reserves - refreshed, as for
+    ///             RefreshObligation once the liquidity is repaid.
+    RepayObligationLiquidityAndWithdrawObligationCollateral {
+        /// Amount of liquidity to repay - u64::MAX for 100% of borrowed amount
+        repay_liquidity_amount: u64,
+        /// Amount of collateral to withdraw - u64::MAX for up to 100% of deposited amount
+        withdraw_collateral_amount: u64,
+        /// Minimum amount of liquidity to receive from redeeming the collateral
+        min_liquidity_amount: u64,
+    },
+
+    // 42
+    /// Runs a list of deposit, withdraw, borrow and repay operations against one obligation.
+    /// Each reserve is refreshed once, the operations run in order and obligation health is
+    /// only checked once all of them have run.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[]` Derived lending market authority.
+    ///   3. `[signer]` Obligation owner.
+    ///   4. `[signer]` User transfer authority ($authority).
+    ///   5. `[]` Clock sysvar.
+    ///   6. `[]` Token program id.
+    ///   7. `[]` Lending market membership of $authority.
+    ///                     Only checked for deposits if the market is permissioned.
+    ///
+    ///   For each reserve, including every obligation deposit and borrow reserve:
+    ///     `[writable]` Reserve account.
+    ///     `[]` Reserve liquidity pyth oracle account.
+    ///     `[]` Reserve liquidity switchboard oracle account.
+    ///     `[writable]` Reserve liquidity supply SPL Token account.
+    ///     `[writable]` Reserve liquidity fee receiver account.
+    ///     `[writable]` Reserve collateral SPL Token mint.
+    ///     `[writable]` Reserve collateral supply SPL Token account.
+    ///     `[writable]` User liquidity token account.
+    ///                    $authority can transfer deposit and repay amounts.
+    Batch {
+        /// Operations to run in order, up to MAX_BATCH_OPS
+        ops: Vec<BatchOp>,
+    },
+
+    // 43
+    /// Grants a delegate limited rights on an obligation, replacing any previous grant to the
+    /// same delegate. The delegate signs in place of the obligation owner and provides the
+    /// obligation delegation to RepayWithCollateral, BorrowObligationLiquidityWithDelegation,
+    /// WithdrawObligationCollateral or WithdrawObligationCollateralAndRedeemReserveCollateral.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation delegation account.
+    ///                     PDA of the obligation address, delegate and "obligation_delegation".
+    ///   1. `[writable]` Obligation account.
+    ///   2. `[signer, writable]` Obligation owner.
+    ///   3. `[]` Rent sysvar.
+    ///   4. `[]` System program.
+    SetObligationDelegation {
+        /// Delegate signer address
+        delegate: Pubkey,
+        /// Granted permissions, a combination of the `DELEGATE_*` flags
+        permissions: u8,
+        /// Market value in quote currency the delegate can borrow and withdraw over the lifetime
+        /// of the delegation
+        value_limit: u64,
+    },
+
+    // 44
+    /// Revokes an obligation delegation, closing its account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation delegation account.
+    ///   1. `[writable]` Obligation account.
+    ///   2. `[signer, writable]` Obligation owner.
+    ///                     Receives the obligation delegation account lamports.
+    RevokeObligationDelegation,
+
+    // 45
+    /// Transfers an obligation to a new owner, or proposes the new owner if acceptance is
+    /// required. Proposing the current owner cancels a pending transfer. Obligations with
+    /// delegations or borrow allowances and seeded obligations cannot be transferred.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer]` Obligation owner.
+    ///   2. `[]` Lending market account.
+    ///   3. `[optional]` Lending market membership of the new owner, if the market is
+    ///                     permissioned. Only checked if acceptance is not required.
+    TransferObligationOwnership {
+        /// The new owner
+        new_owner: Pubkey,
+        /// Whether the new owner must sign an AcceptObligationOwnership instruction
+        require_acceptance: bool,
+    },
+
+    // 46
+    /// Accepts the ownership of an obligation proposed by TransferObligationOwnership.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer]` Pending obligation owner.
+    ///   2. `[]` Lending market account.
+    ///   3. `[optional]` Lending market membership of the pending owner, if the market is
+    ///                     permissioned.
+    AcceptObligationOwnership,
+
+    // 47
+    /// Allows a delegate to borrow liquidity from a reserve against the obligation collateral with
+    /// BorrowObligationLiquidityWithAllowance. The debt is recorded on the obligation and repaying
+    /// it does not restore the allowance. Approving an existing allowance replaces the remaining
+    /// amount and keeps the cumulative amount borrowed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Borrow allowance account.
+    ///                     PDA of the obligation, delegate, borrow reserve and "borrow_allowance".
+    ///   1. `[writable]` Obligation account.
+    ///   2. `[]` Borrow reserve account.
+    ///   3. `[signer, writable]` Obligation owner.
+    ///   4. `[]` Rent sysvar.
+    ///   5. `[]` System program.
+    ///   6. `[]` Lending market account.
+    ///   7. `[optional]` Lending market membership of the delegate, if the market is
+    ///                     permissioned.
+    ApproveBorrowAllowance {
+        /// Delegate signer address
+        delegate: Pubkey,
+        /// Amount of liquidity the delegate can borrow, including fees
+        allowance: u64,
+    },
+
+    // 48
+    /// Revokes a borrow allowance, closing its account. Debt already borrowed by the delegate
+    /// stays on the obligation.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Borrow allowance account.
+    ///   1. `[writable]` Obligation account.
+    ///   2. `[signer, writable]` Obligation owner.
+    ///                     Receives the borrow allowance account lamports.
+    RevokeBorrowAllowance,
+
+    // 49
+    /// Borrow liquidity from a reserve against another owner's obligation, up to the borrow
+    /// allowance of the delegate. Requires a refreshed obligation and reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source borrow reserve liquidity supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by borrow reserve liquidity mint.
+    ///   2. `[writable]` Borrow reserve account - refreshed.
+    ///   3. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   4. `[writable]` Obligation account - refreshed.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[signer]` Delegate.
+    ///   8. `[]` Clock sysvar.
+    ///   9. `[]` Token program id.
+    ///   10 `[writable]` Borrow allowance account.
+    ///   11 `[]` Lending market membership of the delegate.
+    ///   12 `[optional, writable]` Host fee receiver account.
+    BorrowObligationLiquidityWithAllowance {
+        /// Amount of liquidity to borrow
+        liquidity_amount: u64,
+        /// Minimum amount of liquidity to receive after fees - 0 for no minimum
+        min_receive_amount: u64,
+        /// Maximum amount of liquidity to borrow including fees - u64::MAX for no maximum
+        max_borrow_amount_including_fees: u64,
+    },
+
+    // 50
+    /// Borrow liquidity from a reserve as a delegate of the obligation owner, with an obligation
+    /// delegation granting DELEGATE_BORROW. Requires a refreshed obligation and reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source borrow reserve liquidity supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by borrow reserve liquidity mint.
+    ///   2. `[writable]` Borrow reserve account - refreshed.
+    ///   3. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   4. `[writable]` Obligation account - refreshed.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[signer]` Delegate.
+    ///   8. `[]` Clock sysvar.
+    ///   9. `[]` Token program id.
+    ///   10 `[writable]` Obligation delegation account.
+    ///   11 `[optional, writable]` Host fee receiver account.
+    BorrowObligationLiquidityWithDelegation {
+        /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
+        liquidity_amount: u64,
+        /// Minimum amount of liquidity to receive after fees - 0 for no minimum
+        min_receive_amount: u64,
+        /// Maximum amount of liquidity to borrow including fees - u64::MAX for no maximum
+        max_borrow_amount_including_fees: u64,
+    },
+}
+
+/// Operation of a `Batch` instruction, against the reserve at `reserve_index` of the accounts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchOp {
+    /// Deposit liquidity and add the minted collateral to the obligation
+    Deposit {
+        /// Index of the reserve accounts
+        reserve_index: u8,
+        /// Amount of liquidity to deposit
+        liquidity_amount: u64,
+    },
+    /// Withdraw collateral from the obligation and redeem it for liquidity
+    Withdraw {
+        /// Index of the reserve accounts
+        reserve_index: u8,
+        /// Amount of collateral to withdraw - u64::MAX for all of the deposited collateral
+        collateral_amount: u64,
+    },
+    /// Borrow liquidity, paying the borrow fee on top of the amount
+    Borrow {
+        /// Index of the reserve accounts
+        reserve_index: u8,
+        /// Amount of liquidity to receive
+        liquidity_amount: u64,
+    },
+    /// Repay borrowed liquidity
+    Repay {
+        /// Index of the reserve accounts
+        reserve_index: u8,
+        /// Amount of liquidity to repay - u64::MAX for 100% of borrowed amount
+        liquidity_amount: u64,
+    },
+}
+
+impl BatchOp {
+    /// Index of the reserve accounts the operation runs against
+    pub fn reserve_index(&self) -> u8 {
+        match *self {
+            Self::Deposit { reserve_index, .. }
+            | Self::Withdraw { reserve_index, .. }
+            | Self::Borrow { reserve_index, .. }
+            | Self::Repay { reserve_index, .. } => reserve_index,
+        }
+    }
+}
+
+impl LendingInstruction {
+    /// Unpacks a byte buffer into a [LendingInstruction](enum.LendingInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok(match tag {
+            0 => {
+                let (owner, rest) = Self::unpack_pubkey(rest)?;
+                let (quote_currency, _rest) = Self::unpack_bytes32(rest)?;
+                Self::InitLendingMarket {
+                    owner,
+                    quote_currency: *quote_currency,
+                }
+            }
+            1 => {
+                let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetLendingMarketOwner { new_owner }
+            }
+            2 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
+                Self::InitReserve {
+                    liquidity_amount,
+                    config,
+                }
+            }
+            3 => Self::RefreshReserve,
+            4 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositReserveLiquidity { liquidity_amount }
+            }
+            5 => {
+                let (collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_liquidity_amount, _rest) = Self::unpack_optional_u64(rest, 0)?;
+                Self::RedeemReserveCollateral {
+                    collateral_amount,
+                    min_liquidity_amount,
+                }
+            }
+            6 => {
+                let seed = if rest.is_empty() {
+                    None
+                } else {
+                    let (seed, _rest) = Self::unpack_u64(rest)?;
+                    Some(seed)
+                };
+                Self::InitObligation { seed }
+            }
+            7 => Self::RefreshObligation,
+            8 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositObligationCollateral { collateral_amount }
+            }
+            9 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawObligationCollateral { collateral_amount }
+            }
+            10 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_receive_amount, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (max_borrow_amount_including_fees, _rest) =
+                    Self::unpack_optional_u64(rest, u64::MAX)?;
+                Self::BorrowObligationLiquidity {
+                    liquidity_amount,
+                    min_receive_amount,
+                    max_borrow_amount_including_fees,
+                }
+            }
+            11 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RepayObligationLiquidity { liquidity_amount }
+            }
+            12 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_collateral_amount, _rest) = Self::unpack_optional_u64(rest, 0)?;
+                Self::LiquidateObligation {
+                    liquidity_amount,
+                    min_collateral_amount,
+                }
+            }
+            13 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (receiver_instruction_prefix, rest) = if rest.is_empty() {
+                    (DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(), rest)
+                } else {
+                    let (prefix_len, rest) = Self::unpack_u8(rest)?;
+                    let prefix_len = prefix_len as usize;
+                    if prefix_len > MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN || rest.len() < prefix_len {
+                        msg!("Flash loan receiver instruction prefix cannot be unpacked");
+                        return Err(LendingError::InstructionUnpackError.into());
+                    }
+                    (rest[..prefix_len].to_vec(), &rest[prefix_len..])
+                };
+                let (apply_fee_exemptions, _rest) = Self::unpack_optional_bool(rest, false)?;
+                Self::FlashLoan {
+                    amount,
+                    receiver_instruction_prefix,
+                    apply_fee_exemptions,
+                }
+            }
+            14 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount }
+            }
+            15 => {
+                let (collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_liquidity_amount, _rest) = Self::unpack_optional_u64(rest, 0)?;
+                Self::WithdrawObligationCollateralAndRedeemReserveCollateral {
+                    collateral_amount,
+                    min_liquidity_amount,
+                }
+            }
+            16 => {
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
+                Self::UpdateReserveConfig { config }
+            }
+            17 => {
+                let (delay_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetReserveConfigDelay { delay_slots }
+            }
+            18 => {
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
+                Self::QueueReserveConfig { config }
+            }
+            19 => Self::ApplyReserveConfig,
+            20 => Self::CancelReserveConfig,
+            21 => {
+                let (max_loan_to_value_ratio_step, rest) = Self::unpack_u8(rest)?;
+                let (max_liquidation_threshold_step, rest) = Self::unpack_u8(rest)?;
+                let (max_liquidation_bonus_step, rest) = Self::unpack_u8(rest)?;
+                let (max_borrow_rate_step, rest) = Self::unpack_u8(rest)?;
+                let (min_update_interval_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetReserveConfigBounds {
+                    bounds: ReserveConfigBounds {
+                        max_loan_to_value_ratio_step,
+                        max_liquidation_threshold_step,
+                        max_liquidation_bonus_step,
+                        max_borrow_rate_step,
+                        min_update_interval_slots,
+                    },
+                }
+            }
+            22 => {
+                let (permissioned, _rest) = Self::unpack_bool(rest)?;
+                Self::SetLendingMarketPermissioned { permissioned }
+            }
+            23 => {
+                let (member, _rest) = Self::unpack_pubkey(rest)?;
+                Self::AddLendingMarketMember { member }
+            }
+            24 => Self::RemoveLendingMarketMember,
+            25 => {
+                let (status, rest) = Self::unpack_u8(rest)?;
+                let status = ReserveStatus::try_from(status)?;
+                let (loan_to_value_ratio_target, rest) = Self::unpack_u8(rest)?;
+                let (ramp_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetReserveStatus {
+                    status,
+                    loan_to_value_ratio_target,
+                    ramp_slots,
+                }
+            }
+            26 => Self::CloseReserve,
+            27 => {
+                let (close_factor, rest) = Self::unpack_u8(rest)?;
+                let (full_liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+                let (full_liquidation_dust_value, rest) = Self::unpack_u64(rest)?;
+                let (close_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::SetLiquidationConfig {
+                    config: LiquidationConfig {
+                        close_factor,
+                        full_liquidation_threshold,
+                        full_liquidation_dust_value,
+                        close_amount,
+                    },
+                }
+            }
+            28 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_liquidity_amount, _rest) = Self::unpack_optional_u64(rest, 0)?;
+                Self::LiquidateObligationAndRedeemReserveCollateral {
+                    liquidity_amount,
+                    min_liquidity_amount,
+                }
+            }
+            29 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashBorrowReserveLiquidity { liquidity_amount }
+            }
+            30 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (borrow_instruction_index, _rest) = Self::unpack_u8(rest)?;
+                Self::FlashRepayReserveLiquidity {
+                    liquidity_amount,
+                    borrow_instruction_index,
+                }
+            }
+            31 => {
+                let (amounts_len, mut rest) = Self::unpack_u8(rest)?;
+                let amounts_len = amounts_len as usize;
+                if amounts_len == 0 || amounts_len > MAX_FLASH_LOAN_RESERVES {
+                    msg!("Flash loan reserve count cannot be unpacked");
+                    return Err(LendingError::InstructionUnpackError.into());
+                }
+                let mut amounts = Vec::with_capacity(amounts_len);
+                for _ in 0..amounts_len {
+                    let (amount, next) = Self::unpack_u64(rest)?;
+                    amounts.push(amount);
+                    rest = next;
+                }
+                let (prefix_len, rest) = Self::unpack_u8(rest)?;
+                let prefix_len = prefix_len as usize;
+                if prefix_len > MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN || rest.len() < prefix_len {
+                    msg!("Flash loan receiver instruction prefix cannot be unpacked");
+                    return Err(LendingError::InstructionUnpackError.into());
+                }
+                let (apply_fee_exemptions, _rest) =
+                    Self::unpack_optional_bool(&rest[prefix_len..], false)?;
+                Self::FlashLoanMultiple {
+                    amounts,
+                    receiver_instruction_prefix: rest[..prefix_len].to_vec(),
+                    apply_fee_exemptions,
+                }
+            }
+            32 => {
+                let (exempt, rest) = Self::unpack_pubkey(rest)?;
+                let (fee_discount, _rest) = Self::unpack_u8(rest)?;
+                Self::SetFlashLoanFeeExemption {
+                    exempt,
+                    fee_discount,
+                }
+            }
+            33 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RepayWithCollateral { collateral_amount }
+            }
+            34 => {
+                let (collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::SwapCollateral {
+                    collateral_amount,
+                    minimum_amount_out,
+                }
+            }
+            35 => {
+                let (swap_program_id, _rest) = Self::unpack_pubkey(rest)?;
+                Self::AddSwapProgram { swap_program_id }
+            }
+            36 => Self::RemoveSwapProgram,
+            37 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (max_borrow_amount_including_fees, _rest) = Self::unpack_u64(rest)?;
+                Self::RefinanceBorrow {
+                    liquidity_amount,
+                    max_borrow_amount_including_fees,
+                }
+            }
+            38 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (max_leverage_percent, _rest) = Self::unpack_u16(rest)?;
+                Self::OpenLeveragedPosition {
+                    liquidity_amount,
+                    minimum_amount_out,
+                    max_leverage_percent,
+                }
+            }
+            39 => {
+                let (collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::CloseLeveragedPosition {
+                    collateral_amount,
+                    minimum_amount_out,
+                }
+            }
+            40 => {
+                let (deposit_liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (borrow_liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_receive_amount, rest) = Self::unpack_u64(rest)?;
+                let (max_borrow_amount_including_fees, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositReserveLiquidityAndBorrowObligationLiquidity {
+                    deposit_liquidity_amount,
+                    borrow_liquidity_amount,
+                    min_receive_amount,
+                    max_borrow_amount_including_fees,
+                }
+            }
+            41 => {
+                let (repay_liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (withdraw_collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RepayObligationLiquidityAndWithdrawObligationCollateral {
+                    repay_liquidity_amount,
+                    withdraw_collateral_amount,
+                    min_liquidity_amount,
+                }
+            }
+            42 => {
+                let (ops_len, mut rest) = Self::unpack_u8(rest)?;
+                let ops_len = ops_len as usize;
+                if ops_len == 0 || ops_len > MAX_BATCH_OPS {
+                    msg!("Batch operation count cannot be unpacked");
+                    return Err(LendingError::InstructionUnpackError.into());
+                }
+                let mut ops = Vec::with_capacity(ops_len);
+                for _ in 0..ops_len {
+                    let (op_tag, next) = Self::unpack_u8(rest)?;
+                    let (reserve_index, next) = Self::unpack_u8(next)?;
+                    let (amount, next) = Self::unpack_u64(next)?;
+                    ops.push(match op_tag {
+                        0 => BatchOp::Deposit {
+                            reserve_index,
+                            liquidity_amount: amount,
+                        },
+                        1 => BatchOp::Withdraw {
+                            reserve_index,
+                            collateral_amount: amount,
+                        },
+                        2 => BatchOp::Borrow {
+                            reserve_index,
+                            liquidity_amount: amount,
+                        },
+                        3 => BatchOp::Repay {
+                            reserve_index,
+                            liquidity_amount: amount,
+                        },
+                        _ => {
+                            msg!("Batch operation cannot be unpacked");
+                            return Err(LendingError::InstructionUnpackError.into());
+                        }
+                    });
+                    rest = next;
+                }
+                Self::Batch { ops }
+            }
+            43 => {
+                let (delegate, rest) = Self::unpack_pubkey(rest)?;
+                let (permissions, rest) = Self::unpack_u8(rest)?;
+                let (value_limit, _rest) = Self::unpack_u64(rest)?;
+                Self::SetObligationDelegation {
+                    delegate,
+                    permissions,
+                    value_limit,
+                }
+            }
+            44 => Self::RevokeObligationDelegation,
+            45 => {
+                let (new_owner, rest) = Self::unpack_pubkey(rest)?;
+                let (require_acceptance, _rest) = Self::unpack_bool(rest)?;
+                Self::TransferObligationOwnership {
+                    new_owner,
+                    require_acceptance,
+                }
+            }
+            46 => Self::AcceptObligationOwnership,
+            47 => {
+                let (delegate, rest) = Self::unpack_pubkey(rest)?;
+                let (allowance, _rest) = Self::unpack_u64(rest)?;
+                Self::ApproveBorrowAllowance {
+                    delegate,
+                    allowance,
+                }
+            }
+            48 => Self::RevokeBorrowAllowance,
+            49 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_receive_amount, rest) = Self::unpack_u64(rest)?;
+                let (max_borrow_amount_including_fees, _rest) = Self::unpack_u64(rest)?;
+                Self::BorrowObligationLiquidityWithAllowance {
+                    liquidity_amount,
+                    min_receive_amount,
+                    max_borrow_amount_including_fees,
+                }
+            }
+            50 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_receive_amount, rest) = Self::unpack_u64(rest)?;
+                let (max_borrow_amount_including_fees, _rest) = Self::unpack_u64(rest)?;
+                Self::BorrowObligationLiquidityWithDelegation {
+                    liquidity_amount,
+                    min_receive_amount,
+                    max_borrow_amount_including_fees,
+                }
+            }
+            _ => {
+                msg!("Instruction cannot be unpacked");
+                return Err(LendingError::InstructionUnpackError.into());
+            }
+        })
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() < 8 {
+            msg!("u64 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(8);
+        let value = bytes
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
+    /// Unpacks a trailing u64 which older clients may leave out
+    fn unpack_optional_u64(input: &[u8], default: u64) -> Result<(u64, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((default, input));
+        }
+        Self::unpack_u64(input)
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() < 2 {
+            msg!("u16 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(2);
+        let value = bytes
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
+    fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
+        if input.is_empty() {
+            msg!("u8 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(1);
+        let value = bytes
+            .get(..1)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u8::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        let (value, rest) = Self::unpack_u8(input)?;
+        match value {
+            0 => Ok((false, rest)),
+            1 => Ok((true, rest)),
+            _ => {
+                msg!("Boolean cannot be unpacked");
+                Err(LendingError::InstructionUnpackError.into())
+            }
+        }
+    }
+
+    fn unpack_optional_bool(input: &[u8], default: bool) -> Result<(bool, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((default, input));
+        }
+        Self::unpack_bool(input)
+    }
+
+    fn unpack_bytes32(input: &[u8]) -> Result<(&[u8; 32], &[u8]), ProgramError> {
+        if input.len() < 32 {
+            msg!("32 bytes cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(32);
+        Ok((
+            bytes
+                .try_into()
+                .map_err(|_| LendingError::InstructionUnpackError)?,
+            rest,
+        ))
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() < PUBKEY_BYTES {
+            msg!("Pubkey cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (key, rest) = input.split_at(PUBKEY_BYTES);
+        let pk = Pubkey::new(key);
+        Ok((pk, rest))
+    }
+
+    fn unpack_reserve_config(input: &[u8]) -> Result<(ReserveConfig, &[u8]), ProgramError> {
+        let (optimal_utilization_rate, rest) = Self::unpack_u8(input)?;
+        let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+        let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
+        let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
+        let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
+        let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
+        let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
+        let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+        let (deposit_limit, rest) = Self::unpack_u64(rest)?;
+        let (borrow_limit, rest) = Self::unpack_u64(rest)?;
+        let (fee_receiver, rest) = Self::unpack_pubkey(rest)?;
+        let (loan_to_value_ratio_ramp, rest) = Self::unpack_reserve_ramp(rest)?;
+        let (liquidation_threshold_ramp, rest) = Self::unpack_reserve_ramp(rest)?;
+        let (max_liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+        let (protocol_liquidation_fee, rest) = Self::unpack_u8(rest)?;
+        let (collateral_fee_receiver, rest) = Self::unpack_pubkey(rest)?;
+        Ok((
+            ReserveConfig {
+                optimal_utilization_rate,
+                loan_to_value_ratio,
+                liquidation_bonus,
+                max_liquidation_bonus,
+                protocol_liquidation_fee,
+                liquidation_threshold,
+                min_borrow_rate,
+                optimal_borrow_rate,
+                max_borrow_rate,
+                fees: ReserveFees {
+                    borrow_fee_wad,
+                    flash_loan_fee_wad,
+                    host_fee_percentage,
+                },
+                deposit_limit,
+                borrow_limit,
+                fee_receiver,
+                collateral_fee_receiver,
+                loan_to_value_ratio_ramp,
+                liquidation_threshold_ramp,
+            },
+            rest,
+        ))
+    }
+
+    fn unpack_reserve_ramp(input: &[u8]) -> Result<(ReserveRamp, &[u8]), ProgramError> {
+        let (target, rest) = Self::unpack_u8(input)?;
+        let (start_slot, rest) = Self::unpack_u64(rest)?;
+        let (end_slot, rest) = Self::unpack_u64(rest)?;
+        Ok((
+            ReserveRamp {
+                target,
+                start_slot,
+                end_slot,
+            },
+            rest,
+        ))
+    }
+
+    fn pack_reserve_config(config: &ReserveConfig, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&config.optimal_utilization_rate.to_le_bytes());
+        buf.extend_from_slice(&config.loan_to_value_ratio.to_le_bytes());
+        buf.extend_from_slice(&config.liquidation_bonus.to_le_bytes());
+        buf.extend_from_slice(&config.liquidation_threshold.to_le_bytes());
+        buf.extend_from_slice(&config.min_borrow_rate.to_le_bytes());
+        buf.extend_from_slice(&config.optimal_borrow_rate.to_le_bytes());
+        buf.extend_from_slice(&config.max_borrow_rate.to_le_bytes());
+        buf.extend_from_slice(&config.fees.borrow_fee_wad.to_le_bytes());
+        buf.extend_from_slice(&config.fees.flash_loan_fee_wad.to_le_bytes());
+        buf.extend_from_slice(&config.fees.host_fee_percentage.to_le_bytes());
+        buf.extend_from_slice(&config.deposit_limit.to_le_bytes());
+        buf.extend_from_slice(&config.borrow_limit.to_le_bytes());
+        buf.extend_from_slice(&config.fee_receiver.to_bytes());
+        Self::pack_reserve_ramp(&config.loan_to_value_ratio_ramp, buf);
+        Self::pack_reserve_ramp(&config.liquidation_threshold_ramp, buf);
+        buf.extend_from_slice(&config.max_liquidation_bonus.to_le_bytes());
+        buf.extend_from_slice(&config.protocol_liquidation_fee.to_le_bytes());
+        buf.extend_from_slice(&config.collateral_fee_receiver.to_bytes());
+    }
+
+    fn pack_reserve_ramp(ramp: &ReserveRamp, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&ramp.target.to_le_bytes());
+        buf.extend_from_slice(&ramp.start_slot.to_le_bytes());
+        buf.extend_from_slice(&ramp.end_slot.to_le_bytes());
+    }
+
+    /// Packs a [LendingInstruction](enum.LendingInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match *self {
+            Self::InitLendingMarket {
+                owner,
+                quote_currency,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(owner.as_ref());
+                buf.extend_from_slice(quote_currency.as_ref());
+            }
+            Self::SetLendingMarketOwner { new_owner } => {
+                buf.push(1);
+                buf.extend_from_slice(new_owner.as_ref());
+            }
+            Self::InitReserve {
+                liquidity_amount,
+                ref config,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                Self::pack_reserve_config(config, &mut buf);
+            }
+            Self::RefreshReserve => {
+                buf.push(3);
+            }
+            Self::DepositReserveLiquidity { liquidity_amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::RedeemReserveCollateral {
+                collateral_amount,
+                min_liquidity_amount,
+            } => {
+                buf.push(5);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&min_liquidity_amount.to_le_bytes());
+            }
+            Self::InitObligation { seed } => {
+                buf.push(6);
+                if let Some(seed) = seed {
+                    buf.extend_from_slice(&seed.to_le_bytes());
+                }
+            }
+            Self::RefreshObligation => {
+                buf.push(7);
+            }
+            Self::DepositObligationCollateral { collateral_amount } => {
+                buf.push(8);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::WithdrawObligationCollateral { collateral_amount } => {
+                buf.push(9);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::BorrowObligationLiquidity {
+                liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+            } => {
+                buf.push(10);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_receive_amount.to_le_bytes());
+                buf.extend_from_slice(&max_borrow_amount_including_fees.to_le_bytes());
+            }
+            Self::RepayObligationLiquidity { liquidity_amount } => {
+                buf.push(11);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::LiquidateObligation {
+                liquidity_amount,
+                min_collateral_amount,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_collateral_amount.to_le_bytes());
+            }
+            Self::FlashLoan {
+                amount,
+                ref receiver_instruction_prefix,
+                apply_fee_exemptions,
+            } => {
+                buf.push(13);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                assert!(
+                    receiver_instruction_prefix.len() <= MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN,
+                    "flash loan receiver instruction prefix is too long"
+                );
+                buf.push(receiver_instruction_prefix.len() as u8);
+                buf.extend_from_slice(receiver_instruction_prefix);
+                buf.push(apply_fee_exemptions as u8);
+            }
+            Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
+                buf.push(14);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::WithdrawObligationCollateralAndRedeemReserveCollateral {
+                collateral_amount,
+                min_liquidity_amount,
+            } => {
+                buf.push(15);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&min_liquidity_amount.to_le_bytes());
+            }
+            Self::UpdateReserveConfig { ref config } => {
+                buf.push(16);
+                Self::pack_reserve_config(config, &mut buf);
+            }
+            Self::SetReserveConfigDelay { delay_slots } => {
+                buf.push(17);
+                buf.extend_from_slice(&delay_slots.to_le_bytes());
+            }
+            Self::QueueReserveConfig { ref config } => {
+                buf.push(18);
+                Self::pack_reserve_config(config, &mut buf);
+            }
+            Self::ApplyReserveConfig => {
+                buf.push(19);
+            }
+            Self::CancelReserveConfig => {
+                buf.push(20);
+            }
+            Self::SetReserveConfigBounds { bounds } => {
+                buf.push(21);
+                buf.extend_from_slice(&bounds.max_loan_to_value_ratio_step.to_le_bytes());
+                buf.extend_from_slice(&bounds.max_liquidation_threshold_step.to_le_bytes());
+                buf.extend_from_slice(&bounds.max_liquidation_bonus_step.to_le_bytes());
+                buf.extend_from_slice(&bounds.max_borrow_rate_step.to_le_bytes());
+                buf.extend_from_slice(&bounds.min_update_interval_slots.to_le_bytes());
+            }
+            Self::SetLendingMarketPermissioned { permissioned } => {
+                buf.push(22);
+                buf.push(permissioned as u8);
+            }
+            Self::AddLendingMarketMember { member } => {
+                buf.push(23);
+                buf.extend_from_slice(member.as_ref());
+            }
+            Self::RemoveLendingMarketMember => {
+                buf.push(24);
+            }
+            Self::SetReserveStatus {
+                status,
+                loan_to_value_ratio_target,
+                ramp_slots,
+            } => {
+                buf.push(25);
+                buf.push(status as u8);
+                buf.push(loan_to_value_ratio_target);
+                buf.extend_from_slice(&ramp_slots.to_le_bytes());
+            }
+            Self::CloseReserve => {
+                buf.push(26);
+            }
+            Self::SetLiquidationConfig { config } => {
+                buf.push(27);
+                buf.extend_from_slice(&config.close_factor.to_le_bytes());
+                buf.extend_from_slice(&config.full_liquidation_threshold.to_le_bytes());
+                buf.extend_from_slice(&config.full_liquidation_dust_value.to_le_bytes());
+                buf.extend_from_slice(&config.close_amount.to_le_bytes());
+            }
+            Self::LiquidateObligationAndRedeemReserveCollateral {
+                liquidity_amount,
+                min_liquidity_amount,
+            } => {
+                buf.push(28);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_liquidity_amount.to_le_bytes());
+            }
+            Self::FlashBorrowReserveLiquidity { liquidity_amount } => {
+                buf.push(29);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::FlashRepayReserveLiquidity {
+                liquidity_amount,
+                borrow_instruction_index,
+            } => {
+                buf.push(30);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&borrow_instruction_index.to_le_bytes());
+            }
+            Self::FlashLoanMultiple {
+                ref amounts,
+                ref receiver_instruction_prefix,
+                apply_fee_exemptions,
+            } => {
+                buf.push(31);
+                assert!(
+                    amounts.len() <= MAX_FLASH_LOAN_RESERVES,
+                    "flash loan cannot borrow from this many reserves"
+                );
+                buf.push(amounts.len() as u8);
+                for amount in amounts {
+                    buf.extend_from_slice(&amount.to_le_bytes());
+                }
+                assert!(
+                    receiver_instruction_prefix.len() <= MAX_FLASH_LOAN_RECEIVER_PREFIX_LEN,
+                    "flash loan receiver instruction prefix is too long"
+                );
+                buf.push(receiver_instruction_prefix.len() as u8);
+                buf.extend_from_slice(receiver_instruction_prefix);
+                buf.push(apply_fee_exemptions as u8);
+            }
+            Self::SetFlashLoanFeeExemption {
+                exempt,
+                fee_discount,
+            } => {
+                buf.push(32);
+                buf.extend_from_slice(exempt.as_ref());
+                buf.extend_from_slice(&fee_discount.to_le_bytes());
+            }
+            Self::RepayWithCollateral { collateral_amount } => {
+                buf.push(33);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::SwapCollateral {
+                collateral_amount,
+                minimum_amount_out,
+            } => {
+                buf.push(34);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::AddSwapProgram { swap_program_id } => {
+                buf.push(35);
+                buf.extend_from_slice(swap_program_id.as_ref());
+            }
+            Self::RemoveSwapProgram => {
+                buf.push(36);
+            }
+            Self::RefinanceBorrow {
+                liquidity_amount,
+                max_borrow_amount_including_fees,
+            } => {
+                buf.push(37);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&max_borrow_amount_including_fees.to_le_bytes());
+            }
+            Self::OpenLeveragedPosition {
+                liquidity_amount,
+                minimum_amount_out,
+                max_leverage_percent,
+            } => {
+                buf.push(38);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&max_leverage_percent.to_le_bytes());
+            }
+            Self::CloseLeveragedPosition {
+                collateral_amount,
+                minimum_amount_out,
+            } => {
+                buf.push(39);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::DepositReserveLiquidityAndBorrowObligationLiquidity {
+                deposit_liquidity_amount,
+                borrow_liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+            } => {
+                buf.push(40);
+                buf.extend_from_slice(&deposit_liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&borrow_liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_receive_amount.to_le_bytes());
+                buf.extend_from_slice(&max_borrow_amount_including_fees.to_le_bytes());
+            }
+            Self::RepayObligationLiquidityAndWithdrawObligationCollateral {
+                repay_liquidity_amount,
+                withdraw_collateral_amount,
+                min_liquidity_amount,
+            } => {
+                buf.push(41);
+                buf.extend_from_slice(&repay_liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&withdraw_collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&min_liquidity_amount.to_le_bytes());
+            }
+            Self::Batch { ref ops } => {
+                buf.push(42);
+                buf.push(ops.len() as u8);
+                for op in ops {
+                    let (op_tag, reserve_index, amount) = match *op {
+                        BatchOp::Deposit {
+                            reserve_index,
+                            liquidity_amount,
+                        } => (0u8, reserve_index, liquidity_amount),
+                        BatchOp::Withdraw {
+                            reserve_index,
+                            collateral_amount,
+                        } => (1, reserve_index, collateral_amount),
+                        BatchOp::Borrow {
+                            reserve_index,
+                            liquidity_amount,
+                        } => (2, reserve_index, liquidity_amount),
+                        BatchOp::Repay {
+                            reserve_index,
+                            liquidity_amount,
+                        } => (3, reserve_index, liquidity_amount),
+                    };
+                    buf.push(op_tag);
+                    buf.push(reserve_index);
+                    buf.extend_from_slice(&amount.to_le_bytes());
+                }
+            }
+            Self::SetObligationDelegation {
+                delegate,
+                permissions,
+                value_limit,
+            } => {
+                buf.push(43);
+                buf.extend_from_slice(delegate.as_ref());
+                buf.extend_from_slice(&permissions.to_le_bytes());
+                buf.extend_from_slice(&value_limit.to_le_bytes());
+            }
+            Self::RevokeObligationDelegation => {
+                buf.push(44);
+            }
+            Self::TransferObligationOwnership {
+                new_owner,
+                require_acceptance,
+            } => {
+                buf.push(45);
+                buf.extend_from_slice(new_owner.as_ref());
+                buf.push(require_acceptance as u8);
+            }
+            Self::AcceptObligationOwnership => {
+                buf.push(46);
+            }
+            Self::ApproveBorrowAllowance {
+                delegate,
+                allowance,
+            } => {
+                buf.push(47);
+                buf.extend_from_slice(delegate.as_ref());
+                buf.extend_from_slice(&allowance.to_le_bytes());
+            }
+            Self::RevokeBorrowAllowance => {
+                buf.push(48);
+            }
+            Self::BorrowObligationLiquidityWithAllowance {
+                liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+            } => {
+                buf.push(49);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_receive_amount.to_le_bytes());
+                buf.extend_from_slice(&max_borrow_amount_including_fees.to_le_bytes());
+            }
+            Self::BorrowObligationLiquidityWithDelegation {
+                liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+            } => {
+                buf.push(50);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_receive_amount.to_le_bytes());
+                buf.extend_from_slice(&max_borrow_amount_including_fees.to_le_bytes());
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an 'InitLendingMarket' instruction.
+pub fn init_lending_market(
+    program_id: Pubkey,
+    owner: Pubkey,
+    quote_currency: [u8; 32],
+    lending_market_pubkey: Pubkey,
+    oracle_program_id: Pubkey,
+    switchboard_oracle_program_id: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(oracle_program_id, false),
+            AccountMeta::new_readonly(switchboard_oracle_program_id, false),
+        ],
+        data: LendingInstruction::InitLendingMarket {
+            owner,
             quote_currency,
         }
         .pack(),
     }
 }
 
-/// Creates a 'SetLendingMarketOwner' instruction.
-pub fn set_lending_market_owner(
+/// Creates a 'SetLendingMarketOwner' instruction.
+pub fn set_lending_market_owner(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    new_owner: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
+    }
+}
+
+/// Creates an 'InitReserve' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn init_reserve(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    config: ReserveConfig,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    pyth_product_pubkey: Pubkey,
+    pyth_price_pubkey: Pubkey,
+    switchboard_feed_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_collateral_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(config.fee_receiver, false),
+        AccountMeta::new(reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(config.collateral_fee_receiver, false),
+        AccountMeta::new_readonly(pyth_product_pubkey, false),
+        AccountMeta::new_readonly(pyth_price_pubkey, false),
+        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::InitReserve {
+            liquidity_amount,
+            config,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `RefreshReserve` instruction
+pub fn refresh_reserve(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
+    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshReserve.pack(),
+    }
+}
+
+/// Creates a 'DepositReserveLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_reserve_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &user_transfer_authority_pubkey,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+        ],
+        data: LendingInstruction::DepositReserveLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a 'RedeemReserveCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_reserve_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    min_liquidity_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::RedeemReserveCollateral {
+            collateral_amount,
+            min_liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates an 'InitObligation' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn init_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &obligation_owner_pubkey,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+        ],
+        data: LendingInstruction::InitObligation { seed: None }.pack(),
+    }
+}
+
+/// Creates an 'InitObligation' instruction for a seeded obligation, whose address is derived
+/// with `find_obligation_address`.
+pub fn init_seeded_obligation(
+    program_id: Pubkey,
+    seed: u64,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    payer_pubkey: Pubkey,
+) -> Instruction {
+    let (obligation_pubkey, _bump_seed) = find_obligation_address(
+        &program_id,
+        &lending_market_pubkey,
+        &obligation_owner_pubkey,
+        seed,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &obligation_owner_pubkey,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+            AccountMeta::new(payer_pubkey, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::InitObligation { seed: Some(seed) }.pack(),
+    }
+}
+
+/// Creates a 'RefreshObligation' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshObligation.pack(),
+    }
+}
+
+/// Creates a 'DepositObligationCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_obligation_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(deposit_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::DepositObligationCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a 'DepositReserveLiquidityAndObligationCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_reserve_liquidity_and_obligation_collateral(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    destination_deposit_collateral_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
+    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &user_transfer_authority_pubkey,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(user_collateral_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new(destination_deposit_collateral_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
+            AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+        ],
+        data: LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
+            liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'WithdrawObligationCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_obligation_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::WithdrawObligationCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a 'BorrowObligationLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn borrow_obligation_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::BorrowObligationLiquidity {
+            liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'BorrowObligationLiquidityWithDelegation' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn borrow_obligation_liquidity_with_delegation(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    delegate_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (obligation_delegation_pubkey, _bump_seed) =
+        find_obligation_delegation_address(&program_id, &obligation_pubkey, &delegate_pubkey);
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(delegate_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(obligation_delegation_pubkey, false),
+    ];
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::BorrowObligationLiquidityWithDelegation {
+            liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `RepayObligationLiquidity` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn repay_obligation_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `LiquidateObligation` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    min_collateral_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_collateral_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_fee_receiver_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::LiquidateObligation {
+            liquidity_amount,
+            min_collateral_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `FlashLoan` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn flash_loan(
+    program_id: Pubkey,
+    amount: u64,
+    receiver_instruction_prefix: Vec<u8>,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    apply_fee_exemptions: bool,
+    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(host_fee_receiver_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+    ];
+    if apply_fee_exemptions {
+        let (flash_loan_fee_exemptions_pubkey, _bump_seed) =
+            find_flash_loan_fee_exemptions_address(&program_id, &lending_market_pubkey);
+        accounts.push(AccountMeta::new_readonly(
+            flash_loan_fee_exemptions_pubkey,
+            false,
+        ));
+    }
+    accounts.extend(flash_loan_receiver_program_accounts);
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::FlashLoan {
+            amount,
+            receiver_instruction_prefix,
+            apply_fee_exemptions,
+        }
+        .pack(),
+    }
+}
+
+/// Creates an 'UpdateReserveConfig' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn update_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    pyth_product_pubkey: Pubkey,
+    pyth_price_pubkey: Pubkey,
+    switchboard_feed_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(pyth_product_pubkey, false),
+        AccountMeta::new_readonly(pyth_price_pubkey, false),
+        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+        AccountMeta::new_readonly(config.collateral_fee_receiver, false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::UpdateReserveConfig { config }.pack(),
+    }
+}
+
+/// Creates a 'SetReserveConfigDelay' instruction.
+pub fn set_reserve_config_delay(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    delay_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::SetReserveConfigDelay { delay_slots }.pack(),
+    }
+}
+
+/// Creates a 'QueueReserveConfig' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn queue_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    pyth_product_pubkey: Pubkey,
+    pyth_price_pubkey: Pubkey,
+    switchboard_feed_pubkey: Pubkey,
+) -> Instruction {
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(&program_id, &reserve_pubkey);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(pyth_product_pubkey, false),
+            AccountMeta::new_readonly(pyth_price_pubkey, false),
+            AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(config.collateral_fee_receiver, false),
+        ],
+        data: LendingInstruction::QueueReserveConfig { config }.pack(),
+    }
+}
+
+/// Creates an 'ApplyReserveConfig' instruction.
+pub fn apply_reserve_config(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(&program_id, &reserve_pubkey);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::ApplyReserveConfig.pack(),
+    }
+}
+
+/// Creates a 'CancelReserveConfig' instruction.
+pub fn cancel_reserve_config(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(&program_id, &reserve_pubkey);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::CancelReserveConfig.pack(),
+    }
+}
+
+/// Creates a 'SetReserveConfigBounds' instruction.
+pub fn set_reserve_config_bounds(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    bounds: ReserveConfigBounds,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::SetReserveConfigBounds { bounds }.pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketPermissioned' instruction.
+pub fn set_lending_market_permissioned(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    permissioned: bool,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::SetLendingMarketPermissioned { permissioned }.pack(),
+    }
+}
+
+/// Creates an 'AddLendingMarketMember' instruction.
+pub fn add_lending_market_member(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    member: Pubkey,
+) -> Instruction {
+    let (lending_market_membership_pubkey, _bump_seed) =
+        find_lending_market_membership_address(&program_id, &lending_market_pubkey, &member);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_membership_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::AddLendingMarketMember { member }.pack(),
+    }
+}
+
+/// Creates a 'RemoveLendingMarketMember' instruction.
+pub fn remove_lending_market_member(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    member: Pubkey,
+) -> Instruction {
+    let (lending_market_membership_pubkey, _bump_seed) =
+        find_lending_market_membership_address(&program_id, &lending_market_pubkey, &member);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_membership_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::RemoveLendingMarketMember.pack(),
+    }
+}
+
+/// Creates a 'SetReserveStatus' instruction.
+pub fn set_reserve_status(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    status: ReserveStatus,
+    loan_to_value_ratio_target: u8,
+    ramp_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::SetReserveStatus {
+            status,
+            loan_to_value_ratio_target,
+            ramp_slots,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'CloseReserve' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn close_reserve(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    destination_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(&program_id, &reserve_pubkey);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(reserve_collateral_supply_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new(destination_pubkey, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+        ],
+        data: LendingInstruction::CloseReserve.pack(),
+    }
+}
+
+/// Creates a 'SetLiquidationConfig' instruction.
+pub fn set_liquidation_config(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    config: LiquidationConfig,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::SetLiquidationConfig { config }.pack(),
+    }
+}
+
+/// Creates a `LiquidateObligationAndRedeemReserveCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation_and_redeem_reserve_collateral(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    min_liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_mint_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_collateral_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_fee_receiver_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::LiquidateObligationAndRedeemReserveCollateral {
+            liquidity_amount,
+            min_liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `FlashBorrowReserveLiquidity` instruction
+pub fn flash_borrow_reserve_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    reserve_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_liquidity_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::FlashBorrowReserveLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `FlashRepayReserveLiquidity` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn flash_repay_reserve_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    borrow_instruction_index: u8,
+    source_liquidity_pubkey: Pubkey,
+    reserve_liquidity_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(reserve_liquidity_pubkey, false),
+            AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(host_fee_receiver_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::FlashRepayReserveLiquidity {
+            liquidity_amount,
+            borrow_instruction_index,
+        }
+        .pack(),
+    }
+}
+
+/// Reserve accounts and amount for one leg of a `FlashLoanMultiple` instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlashLoanReserve {
+    /// Amount to borrow - u64::MAX for up to 100% of available liquidity
+    pub amount: u64,
+    /// Reserve liquidity supply
+    pub source_liquidity_pubkey: Pubkey,
+    /// Token account to receive the borrowed liquidity
+    pub destination_liquidity_pubkey: Pubkey,
+    /// Reserve to borrow from
+    pub reserve_pubkey: Pubkey,
+    /// Reserve liquidity fee receiver
+    pub reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    /// Host fee receiver
+    pub host_fee_receiver_pubkey: Pubkey,
+}
+
+/// Creates a `FlashLoanMultiple` instruction.
+pub fn flash_loan_multiple(
+    program_id: Pubkey,
+    flash_loan_reserves: Vec<FlashLoanReserve>,
+    receiver_instruction_prefix: Vec<u8>,
+    lending_market_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    apply_fee_exemptions: bool,
+    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+    ];
+    let mut amounts = Vec::with_capacity(flash_loan_reserves.len());
+    for flash_loan_reserve in flash_loan_reserves {
+        amounts.push(flash_loan_reserve.amount);
+        accounts.extend(vec![
+            AccountMeta::new(flash_loan_reserve.source_liquidity_pubkey, false),
+            AccountMeta::new(flash_loan_reserve.destination_liquidity_pubkey, false),
+            AccountMeta::new(flash_loan_reserve.reserve_pubkey, false),
+            AccountMeta::new(
+                flash_loan_reserve.reserve_liquidity_fee_receiver_pubkey,
+                false,
+            ),
+            AccountMeta::new(flash_loan_reserve.host_fee_receiver_pubkey, false),
+        ]);
+    }
+    if apply_fee_exemptions {
+        let (flash_loan_fee_exemptions_pubkey, _bump_seed) =
+            find_flash_loan_fee_exemptions_address(&program_id, &lending_market_pubkey);
+        accounts.push(AccountMeta::new_readonly(
+            flash_loan_fee_exemptions_pubkey,
+            false,
+        ));
+    }
+    accounts.extend(flash_loan_receiver_program_accounts);
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::FlashLoanMultiple {
+            amounts,
+            receiver_instruction_prefix,
+            apply_fee_exemptions,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetFlashLoanFeeExemption` instruction
+pub fn set_flash_loan_fee_exemption(
+    program_id: Pubkey,
+    exempt: Pubkey,
+    fee_discount: u8,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (flash_loan_fee_exemptions_pubkey, _bump_seed) =
+        find_flash_loan_fee_exemptions_address(&program_id, &lending_market_pubkey);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(flash_loan_fee_exemptions_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::SetFlashLoanFeeExemption {
+            exempt,
+            fee_discount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `RepayWithCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn repay_with_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::RepayWithCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a `SwapCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn swap_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    minimum_amount_out: u64,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_collateral_mint_pubkey: Pubkey,
+    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    deposit_reserve_liquidity_supply_pubkey: Pubkey,
+    deposit_reserve_collateral_mint_pubkey: Pubkey,
+    deposit_reserve_collateral_supply_pubkey: Pubkey,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    swap_program_id: Pubkey,
+    swap_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &obligation_owner_pubkey,
+    );
+    let (swap_program_approval_pubkey, _bump_seed) =
+        find_swap_program_approval_address(&program_id, &lending_market_pubkey, &swap_program_id);
+    let mut accounts = vec![
+        AccountMeta::new(withdraw_reserve_pubkey, false),
+        AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(deposit_reserve_pubkey, false),
+        AccountMeta::new(deposit_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+        AccountMeta::new_readonly(swap_program_approval_pubkey, false),
+        AccountMeta::new_readonly(swap_program_id, false),
+    ];
+    accounts.extend(swap_accounts);
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::SwapCollateral {
+            collateral_amount,
+            minimum_amount_out,
+        }
+        .pack(),
+    }
+}
+
+/// Creates an 'AddSwapProgram' instruction.
+pub fn add_swap_program(
     program_id: Pubkey,
     lending_market_pubkey: Pubkey,
-    lending_market_owner: Pubkey,
-    new_owner: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    swap_program_id: Pubkey,
 ) -> Instruction {
+    let (swap_program_approval_pubkey, _bump_seed) =
+        find_swap_program_approval_address(&program_id, &lending_market_pubkey, &swap_program_id);
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_owner, true),
+            AccountMeta::new(swap_program_approval_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
         ],
-        data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
+        data: LendingInstruction::AddSwapProgram { swap_program_id }.pack(),
     }
 }
 
-/// Creates an 'InitReserve' instruction.
+/// Creates a 'RemoveSwapProgram' instruction.
+pub fn remove_swap_program(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    swap_program_id: Pubkey,
+) -> Instruction {
+    let (swap_program_approval_pubkey, _bump_seed) =
+        find_swap_program_approval_address(&program_id, &lending_market_pubkey, &swap_program_id);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(swap_program_approval_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::RemoveSwapProgram.pack(),
+    }
+}
+
+/// Creates a `RefinanceBorrow` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn init_reserve(
+pub fn refinance_borrow(
     program_id: Pubkey,
     liquidity_amount: u64,
-    config: ReserveConfig,
-    source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_mint_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
-    reserve_collateral_supply_pubkey: Pubkey,
-    pyth_product_pubkey: Pubkey,
-    pyth_price_pubkey: Pubkey,
-    switchboard_feed_pubkey: Pubkey,
+    max_borrow_amount_including_fees: u64,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    lending_market_owner_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let accounts = vec![
-        AccountMeta::new(source_liquidity_pubkey, false),
-        AccountMeta::new(destination_collateral_pubkey, false),
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
-        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-        AccountMeta::new(config.fee_receiver, false),
-        AccountMeta::new(reserve_collateral_mint_pubkey, false),
-        AccountMeta::new(reserve_collateral_supply_pubkey, false),
-        AccountMeta::new_readonly(pyth_product_pubkey, false),
-        AccountMeta::new_readonly(pyth_price_pubkey, false),
-        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+    let mut accounts = vec![
+        AccountMeta::new(repay_reserve_pubkey, false),
+        AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
-        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
+    }
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::InitReserve {
+        data: LendingInstruction::RefinanceBorrow {
             liquidity_amount,
-            config,
+            max_borrow_amount_including_fees,
         }
         .pack(),
     }
 }
 
-/// Creates a `RefreshReserve` instruction
-pub fn refresh_reserve(
+/// Creates an `OpenLeveragedPosition` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn open_leveraged_position(
     program_id: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
-    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
+    liquidity_amount: u64,
+    minimum_amount_out: u64,
+    max_leverage_percent: u16,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    deposit_reserve_liquidity_supply_pubkey: Pubkey,
+    deposit_reserve_collateral_mint_pubkey: Pubkey,
+    deposit_reserve_collateral_supply_pubkey: Pubkey,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    swap_program_id: Pubkey,
+    swap_accounts: Vec<AccountMeta>,
 ) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
-        AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &obligation_owner_pubkey,
+    );
+    let (swap_program_approval_pubkey, _bump_seed) =
+        find_swap_program_approval_address(&program_id, &lending_market_pubkey, &swap_program_id);
+    let mut accounts = vec![
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(host_fee_receiver_pubkey, false),
+        AccountMeta::new(deposit_reserve_pubkey, false),
+        AccountMeta::new(deposit_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+        AccountMeta::new_readonly(swap_program_approval_pubkey, false),
+        AccountMeta::new_readonly(swap_program_id, false),
     ];
+    accounts.extend(swap_accounts);
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::RefreshReserve.pack(),
+        data: LendingInstruction::OpenLeveragedPosition {
+            liquidity_amount,
+            minimum_amount_out,
+            max_leverage_percent,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'DepositReserveLiquidity' instruction.
+/// Creates a `CloseLeveragedPosition` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn deposit_reserve_liquidity(
+pub fn close_leveraged_position(
     program_id: Pubkey,
-    liquidity_amount: u64,
+    collateral_amount: u64,
+    minimum_amount_out: u64,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_collateral_mint_pubkey: Pubkey,
+    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
     source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    swap_program_id: Pubkey,
+    swap_accounts: Vec<AccountMeta>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
+    let (swap_program_approval_pubkey, _bump_seed) =
+        find_swap_program_approval_address(&program_id, &lending_market_pubkey, &swap_program_id);
+    let mut accounts = vec![
+        AccountMeta::new(withdraw_reserve_pubkey, false),
+        AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(repay_reserve_pubkey, false),
+        AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(swap_program_approval_pubkey, false),
+        AccountMeta::new_readonly(swap_program_id, false),
+    ];
+    accounts.extend(swap_accounts);
     Instruction {
         program_id,
-        accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(reserve_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ],
-        data: LendingInstruction::DepositReserveLiquidity { liquidity_amount }.pack(),
+        accounts,
+        data: LendingInstruction::CloseLeveragedPosition {
+            collateral_amount,
+            minimum_amount_out,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'RedeemReserveCollateral' instruction.
+/// Creates a `DepositReserveLiquidityAndBorrowObligationLiquidity` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn redeem_reserve_collateral(
+pub fn deposit_reserve_liquidity_and_borrow_obligation_liquidity(
     program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
+    deposit_liquidity_amount: u64,
+    borrow_liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    source_liquidity_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    deposit_reserve_liquidity_supply_pubkey: Pubkey,
+    deposit_reserve_collateral_mint_pubkey: Pubkey,
+    deposit_reserve_collateral_supply_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
     destination_liquidity_pubkey: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &user_transfer_authority_pubkey,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(user_collateral_pubkey, false),
+        AccountMeta::new(deposit_reserve_pubkey, false),
+        AccountMeta::new(deposit_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
     Instruction {
         program_id,
-        accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
-            AccountMeta::new(destination_liquidity_pubkey, false),
-            AccountMeta::new(reserve_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ],
-        data: LendingInstruction::RedeemReserveCollateral { collateral_amount }.pack(),
+        accounts,
+        data: LendingInstruction::DepositReserveLiquidityAndBorrowObligationLiquidity {
+            deposit_liquidity_amount,
+            borrow_liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        }
+        .pack(),
     }
 }
 
-/// Creates an 'InitObligation' instruction.
+/// Creates a `RepayObligationLiquidityAndWithdrawObligationCollateral` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn init_obligation(
+pub fn repay_obligation_liquidity_and_withdraw_obligation_collateral(
     program_id: Pubkey,
+    repay_liquidity_amount: u64,
+    withdraw_collateral_amount: u64,
+    min_liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_collateral_mint_pubkey: Pubkey,
+    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
 ) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(repay_reserve_pubkey, false),
+        AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(withdraw_reserve_pubkey, false),
+        AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+        AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(user_collateral_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
     Instruction {
-        program_id,
-        accounts: vec![
-            AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ],
-        data: LendingInstruction::InitObligation.pack(),
+        program_id,
+        accounts,
+        data: LendingInstruction::RepayObligationLiquidityAndWithdrawObligationCollateral {
+            repay_liquidity_amount,
+            withdraw_collateral_amount,
+            min_liquidity_amount,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'RefreshObligation' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn refresh_obligation(
+/// Reserve accounts for a `Batch` instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchReserve {
+    /// Reserve account
+    pub reserve_pubkey: Pubkey,
+    /// Reserve liquidity pyth oracle
+    pub reserve_liquidity_pyth_oracle_pubkey: Pubkey,
+    /// Reserve liquidity switchboard oracle
+    pub reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
+    /// Reserve liquidity supply
+    pub reserve_liquidity_supply_pubkey: Pubkey,
+    /// Reserve liquidity fee receiver
+    pub reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    /// Reserve collateral mint
+    pub reserve_collateral_mint_pubkey: Pubkey,
+    /// Reserve collateral supply
+    pub reserve_collateral_supply_pubkey: Pubkey,
+    /// User token account to deposit and repay from and to receive withdrawn and borrowed
+    /// liquidity
+    pub user_liquidity_pubkey: Pubkey,
+}
+
+/// Creates a `Batch` instruction
+pub fn batch(
     program_id: Pubkey,
+    ops: Vec<BatchOp>,
+    batch_reserves: Vec<BatchReserve>,
     obligation_pubkey: Pubkey,
-    reserve_pubkeys: Vec<Pubkey>,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &user_transfer_authority_pubkey,
+    );
     let mut accounts = vec![
         AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(lending_market_membership_pubkey, false),
     ];
-    accounts.extend(
-        reserve_pubkeys
-            .into_iter()
-            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
-    );
+    for batch_reserve in batch_reserves {
+        accounts.extend(vec![
+            AccountMeta::new(batch_reserve.reserve_pubkey, false),
+            AccountMeta::new_readonly(batch_reserve.reserve_liquidity_pyth_oracle_pubkey, false),
+            AccountMeta::new_readonly(
+                batch_reserve.reserve_liquidity_switchboard_oracle_pubkey,
+                false,
+            ),
+            AccountMeta::new(batch_reserve.reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(batch_reserve.reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(batch_reserve.reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(batch_reserve.reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(batch_reserve.user_liquidity_pubkey, false),
+        ]);
+    }
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::RefreshObligation.pack(),
+        data: LendingInstruction::Batch { ops }.pack(),
     }
 }
 
-/// Creates a 'DepositObligationCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn deposit_obligation_collateral(
+/// Creates a `SetObligationDelegation` instruction
+pub fn set_obligation_delegation(
     program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    deposit_reserve_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
-    lending_market_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    delegate: Pubkey,
+    permissions: u8,
+    value_limit: u64,
 ) -> Instruction {
+    let (obligation_delegation_pubkey, _bump_seed) =
+        find_obligation_delegation_address(&program_id, &obligation_pubkey, &delegate);
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(deposit_reserve_pubkey, false),
+            AccountMeta::new(obligation_delegation_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
         ],
-        data: LendingInstruction::DepositObligationCollateral { collateral_amount }.pack(),
+        data: LendingInstruction::SetObligationDelegation {
+            delegate,
+            permissions,
+            value_limit,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'DepositReserveLiquidityAndObligationCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn deposit_reserve_liquidity_and_obligation_collateral(
+/// Creates a `RevokeObligationDelegation` instruction
+pub fn revoke_obligation_delegation(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    user_collateral_pubkey: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
-    lending_market_pubkey: Pubkey,
-    destination_deposit_collateral_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
-    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
-    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    delegate: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
-        &program_id,
-    );
+    let (obligation_delegation_pubkey, _bump_seed) =
+        find_obligation_delegation_address(&program_id, &obligation_pubkey, &delegate);
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(user_collateral_pubkey, false),
-            AccountMeta::new(reserve_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new(destination_deposit_collateral_pubkey, false),
+            AccountMeta::new(obligation_delegation_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
             AccountMeta::new(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
-            AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
-            liquidity_amount,
-        }
-        .pack(),
+        data: LendingInstruction::RevokeObligationDelegation.pack(),
     }
 }
 
-/// Creates a 'WithdrawObligationCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn withdraw_obligation_collateral(
+/// Creates a `TransferObligationOwnership` instruction
+pub fn transfer_obligation_ownership(
     program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
+    new_owner: Pubkey,
+    require_acceptance: bool,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
-        &program_id,
-    );
+    let (lending_market_membership_pubkey, _bump_seed) =
+        find_lending_market_membership_address(&program_id, &lending_market_pubkey, &new_owner);
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
         ],
-        data: LendingInstruction::WithdrawObligationCollateral { collateral_amount }.pack(),
+        data: LendingInstruction::TransferObligationOwnership {
+            new_owner,
+            require_acceptance,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'BorrowObligationLiquidity' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn borrow_obligation_liquidity(
+/// Creates an `AcceptObligationOwnership` instruction
+pub fn accept_obligation_ownership(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_liquidity_pubkey: Pubkey,
-    borrow_reserve_pubkey: Pubkey,
-    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
-    host_fee_receiver_pubkey: Option<Pubkey>,
+    pending_owner_pubkey: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
         &program_id,
+        &lending_market_pubkey,
+        &pending_owner_pubkey,
     );
-    let mut accounts = vec![
-        AccountMeta::new(source_liquidity_pubkey, false),
-        AccountMeta::new(destination_liquidity_pubkey, false),
-        AccountMeta::new(borrow_reserve_pubkey, false),
-        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
-        AccountMeta::new(obligation_pubkey, false),
-        AccountMeta::new_readonly(lending_market_pubkey, false),
-        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(obligation_owner_pubkey, true),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(spl_token::id(), false),
-    ];
-    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
-        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
-    }
     Instruction {
         program_id,
-        accounts,
-        data: LendingInstruction::BorrowObligationLiquidity { liquidity_amount }.pack(),
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(pending_owner_pubkey, true),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
+        ],
+        data: LendingInstruction::AcceptObligationOwnership.pack(),
     }
 }
 
-/// Creates a `RepayObligationLiquidity` instruction
-#[allow(clippy::too_many_arguments)]
-pub fn repay_obligation_liquidity(
+/// Creates an `ApproveBorrowAllowance` instruction
+pub fn approve_borrow_allowance(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_liquidity_pubkey: Pubkey,
-    repay_reserve_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    delegate: Pubkey,
+    allowance: u64,
 ) -> Instruction {
+    let (borrow_allowance_pubkey, _bump_seed) = find_borrow_allowance_address(
+        &program_id,
+        &obligation_pubkey,
+        &delegate,
+        &borrow_reserve_pubkey,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) =
+        find_lending_market_membership_address(&program_id, &lending_market_pubkey, &delegate);
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_liquidity_pubkey, false),
-            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(borrow_allowance_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(borrow_reserve_pubkey, false),
+            AccountMeta::new(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_membership_pubkey, false),
         ],
-        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
+        data: LendingInstruction::ApproveBorrowAllowance {
+            delegate,
+            allowance,
+        }
+        .pack(),
     }
 }
 
-/// Creates a `LiquidateObligation` instruction
-#[allow(clippy::too_many_arguments)]
-pub fn liquidate_obligation(
+/// Creates a `RevokeBorrowAllowance` instruction
+pub fn revoke_borrow_allowance(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    repay_reserve_pubkey: Pubkey,
-    repay_reserve_liquidity_supply_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
-    withdraw_reserve_collateral_supply_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
-    lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    delegate: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+    let (borrow_allowance_pubkey, _bump_seed) = find_borrow_allowance_address(
         &program_id,
+        &obligation_pubkey,
+        &delegate,
+        &borrow_reserve_pubkey,
     );
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(repay_reserve_pubkey, false),
-            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
-            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(borrow_allowance_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(obligation_owner_pubkey, true),
         ],
-        data: LendingInstruction::LiquidateObligation { liquidity_amount }.pack(),
+        data: LendingInstruction::RevokeBorrowAllowance.pack(),
     }
 }
 
-/// Creates a `FlashLoan` instruction.
+/// Creates a `BorrowObligationLiquidityWithAllowance` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn flash_loan(
+pub fn borrow_obligation_liquidity_with_allowance(
     program_id: Pubkey,
-    amount: u64,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
     source_liquidity_pubkey: Pubkey,
     destination_liquidity_pubkey: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_fee_receiver_pubkey: Pubkey,
-    host_fee_receiver_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    flash_loan_receiver_program_id: Pubkey,
-    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+    delegate_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
+    let (borrow_allowance_pubkey, _bump_seed) = find_borrow_allowance_address(
+        &program_id,
+        &obligation_pubkey,
+        &delegate_pubkey,
+        &borrow_reserve_pubkey,
+    );
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &program_id,
+        &lending_market_pubkey,
+        &delegate_pubkey,
+    );
     let mut accounts = vec![
         AccountMeta::new(source_liquidity_pubkey, false),
         AccountMeta::new(destination_liquidity_pubkey, false),
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
-        AccountMeta::new(host_fee_receiver_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(delegate_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
-        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+        AccountMeta::new(borrow_allowance_pubkey, false),
+        AccountMeta::new_readonly(lending_market_membership_pubkey, false),
     ];
-    accounts.extend(flash_loan_receiver_program_accounts);
-    Instruction {
-        program_id,
-        accounts,
-        data: LendingInstruction::FlashLoan { amount }.pack(),
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
     }
-}
-
-/// Creates an 'UpdateReserveConfig' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn update_reserve_config(
-    program_id: Pubkey,
-    config: ReserveConfig,
-    reserve_pubkey: Pubkey,
-    lending_market_pubkey: Pubkey,
-    lending_market_owner_pubkey: Pubkey,
-    pyth_product_pubkey: Pubkey,
-    pyth_price_pubkey: Pubkey,
-    switchboard_feed_pubkey: Pubkey,
-) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
-        &program_id,
-    );
-    let accounts = vec![
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new_readonly(lending_market_pubkey, false),
-        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
-        AccountMeta::new_readonly(pyth_product_pubkey, false),
-        AccountMeta::new_readonly(pyth_price_pubkey, false),
-        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
-    ];
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::UpdateReserveConfig { config }.pack(),
+        data: LendingInstruction::BorrowObligationLiquidityWithAllowance {
+            liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        }
+        .pack(),
     }
 }
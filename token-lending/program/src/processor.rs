@@ -3,14 +3,26 @@
 use crate::{
     self as spl_token_lending,
     error::LendingError,
-    instruction::LendingInstruction,
+    instruction::{BatchOp, LendingInstruction},
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub, WAD},
     pyth,
     state::{
-        CalculateBorrowResult, CalculateLiquidationResult, CalculateRepayResult,
-        InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
-        NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, Reserve,
-        ReserveCollateral, ReserveConfig, ReserveLiquidity,
+        find_borrow_allowance_address, find_flash_loan_fee_exemptions_address,
+        find_lending_market_membership_address, find_obligation_address,
+        find_obligation_delegation_address, find_pending_reserve_config_address,
+        find_swap_program_approval_address, BorrowAllowance, CalculateBorrowResult,
+        CalculateLiquidationResult, CalculateRepayResult, CalculateRepayWithCollateralResult,
+        FeeCalculation, FlashLoanFeeExemptions, InitBorrowAllowanceParams,
+        InitFlashLoanFeeExemptionsParams, InitLendingMarketMembershipParams,
+        InitLendingMarketParams, InitObligationDelegationParams, InitObligationParams,
+        InitPendingReserveConfigParams, InitReserveParams, InitSwapProgramApprovalParams,
+        LendingMarket, LendingMarketMembership, LiquidationConfig, NewReserveCollateralParams,
+        NewReserveLiquidityParams, Obligation, ObligationDelegation, PendingReserveConfig, Reserve,
+        ReserveCollateral, ReserveConfig, ReserveConfigBounds, ReserveLiquidity, ReserveStatus,
+        SwapProgramApproval, BORROW_ALLOWANCE_SEED, DELEGATE_BORROW,
+        DELEGATE_REPAY_WITH_COLLATERAL, DELEGATE_WITHDRAW, FLASH_LOAN_FEE_EXEMPTIONS_SEED,
+        LENDING_MARKET_MEMBERSHIP_SEED, OBLIGATION_DELEGATION_SEED, OBLIGATION_SEED,
+        PENDING_RESERVE_CONFIG_SEED, SWAP_PROGRAM_APPROVAL_SEED,
     },
 };
 use num_traits::FromPrimitive;
@@ -24,11 +36,18 @@ use solana_program::{
     program_error::{PrintProgramError, ProgramError},
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{clock::Clock, rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{
+        self,
+        clock::Clock,
+        instructions::{load_current_index, load_instruction_at},
+        rent::Rent,
+        Sysvar,
+    },
 };
 use spl_token::solana_program::instruction::AccountMeta;
 use spl_token::state::{Account, Mint};
-use std::{convert::TryInto, result::Result};
+use std::{convert::TryInto, iter, result::Result};
 use switchboard_program::{
     get_aggregator, get_aggregator_result, AggregatorState, RoundResult, SwitchboardAccountType,
 };
@@ -67,13 +86,21 @@ pub fn process_instruction(
             msg!("Instruction: Deposit Reserve Liquidity");
             process_deposit_reserve_liquidity(program_id, liquidity_amount, accounts)
         }
-        LendingInstruction::RedeemReserveCollateral { collateral_amount } => {
+        LendingInstruction::RedeemReserveCollateral {
+            collateral_amount,
+            min_liquidity_amount,
+        } => {
             msg!("Instruction: Redeem Reserve Collateral");
-            process_redeem_reserve_collateral(program_id, collateral_amount, accounts)
+            process_redeem_reserve_collateral(
+                program_id,
+                collateral_amount,
+                min_liquidity_amount,
+                accounts,
+            )
         }
-        LendingInstruction::InitObligation => {
+        LendingInstruction::InitObligation { seed } => {
             msg!("Instruction: Init Obligation");
-            process_init_obligation(program_id, accounts)
+            process_init_obligation(program_id, seed, accounts)
         }
         LendingInstruction::RefreshObligation => {
             msg!("Instruction: Refresh Obligation");
@@ -87,21 +114,49 @@ pub fn process_instruction(
             msg!("Instruction: Withdraw Obligation Collateral");
             process_withdraw_obligation_collateral(program_id, collateral_amount, accounts)
         }
-        LendingInstruction::BorrowObligationLiquidity { liquidity_amount } => {
+        LendingInstruction::BorrowObligationLiquidity {
+            liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        } => {
             msg!("Instruction: Borrow Obligation Liquidity");
-            process_borrow_obligation_liquidity(program_id, liquidity_amount, accounts)
+            process_borrow_obligation_liquidity(
+                program_id,
+                liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+                accounts,
+            )
         }
         LendingInstruction::RepayObligationLiquidity { liquidity_amount } => {
             msg!("Instruction: Repay Obligation Liquidity");
             process_repay_obligation_liquidity(program_id, liquidity_amount, accounts)
         }
-        LendingInstruction::LiquidateObligation { liquidity_amount } => {
+        LendingInstruction::LiquidateObligation {
+            liquidity_amount,
+            min_collateral_amount,
+        } => {
             msg!("Instruction: Liquidate Obligation");
-            process_liquidate_obligation(program_id, liquidity_amount, accounts)
+            process_liquidate_obligation(
+                program_id,
+                liquidity_amount,
+                min_collateral_amount,
+                accounts,
+            )
         }
-        LendingInstruction::FlashLoan { amount } => {
+        LendingInstruction::FlashLoan {
+            amount,
+            receiver_instruction_prefix,
+            apply_fee_exemptions,
+        } => {
             msg!("Instruction: Flash Loan");
-            process_flash_loan(program_id, amount, accounts)
+            process_flash_loan(
+                program_id,
+                amount,
+                &receiver_instruction_prefix,
+                apply_fee_exemptions,
+                accounts,
+            )
         }
         LendingInstruction::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
             msg!("Instruction: Deposit Reserve Liquidity and Obligation Collateral");
@@ -113,11 +168,13 @@ pub fn process_instruction(
         }
         LendingInstruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
             collateral_amount,
+            min_liquidity_amount,
         } => {
             msg!("Instruction: Withdraw Obligation Collateral and Redeem Reserve Collateral");
             process_withdraw_obligation_collateral_and_redeem_reserve_liquidity(
                 program_id,
                 collateral_amount,
+                min_liquidity_amount,
                 accounts,
             )
         }
@@ -125,6 +182,273 @@ pub fn process_instruction(
             msg!("Instruction: UpdateReserveConfig");
             process_update_reserve_config(program_id, config, accounts)
         }
+        LendingInstruction::SetReserveConfigDelay { delay_slots } => {
+            msg!("Instruction: Set Reserve Config Delay");
+            process_set_reserve_config_delay(program_id, delay_slots, accounts)
+        }
+        LendingInstruction::QueueReserveConfig { config } => {
+            msg!("Instruction: Queue Reserve Config");
+            process_queue_reserve_config(program_id, config, accounts)
+        }
+        LendingInstruction::ApplyReserveConfig => {
+            msg!("Instruction: Apply Reserve Config");
+            process_apply_reserve_config(program_id, accounts)
+        }
+        LendingInstruction::CancelReserveConfig => {
+            msg!("Instruction: Cancel Reserve Config");
+            process_cancel_reserve_config(program_id, accounts)
+        }
+        LendingInstruction::SetReserveConfigBounds { bounds } => {
+            msg!("Instruction: Set Reserve Config Bounds");
+            process_set_reserve_config_bounds(program_id, bounds, accounts)
+        }
+        LendingInstruction::SetLendingMarketPermissioned { permissioned } => {
+            msg!("Instruction: Set Lending Market Permissioned");
+            process_set_lending_market_permissioned(program_id, permissioned, accounts)
+        }
+        LendingInstruction::AddLendingMarketMember { member } => {
+            msg!("Instruction: Add Lending Market Member");
+            process_add_lending_market_member(program_id, member, accounts)
+        }
+        LendingInstruction::RemoveLendingMarketMember => {
+            msg!("Instruction: Remove Lending Market Member");
+            process_remove_lending_market_member(program_id, accounts)
+        }
+        LendingInstruction::SetReserveStatus {
+            status,
+            loan_to_value_ratio_target,
+            ramp_slots,
+        } => {
+            msg!("Instruction: Set Reserve Status");
+            process_set_reserve_status(
+                program_id,
+                status,
+                loan_to_value_ratio_target,
+                ramp_slots,
+                accounts,
+            )
+        }
+        LendingInstruction::CloseReserve => {
+            msg!("Instruction: Close Reserve");
+            process_close_reserve(program_id, accounts)
+        }
+        LendingInstruction::SetLiquidationConfig { config } => {
+            msg!("Instruction: Set Liquidation Config");
+            process_set_liquidation_config(program_id, config, accounts)
+        }
+        LendingInstruction::LiquidateObligationAndRedeemReserveCollateral {
+            liquidity_amount,
+            min_liquidity_amount,
+        } => {
+            msg!("Instruction: Liquidate Obligation and Redeem Reserve Collateral");
+            process_liquidate_obligation_and_redeem_reserve_collateral(
+                program_id,
+                liquidity_amount,
+                min_liquidity_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::FlashLoanMultiple {
+            amounts,
+            receiver_instruction_prefix,
+            apply_fee_exemptions,
+        } => {
+            msg!("Instruction: Flash Loan Multiple");
+            process_flash_loan_multiple(
+                program_id,
+                &amounts,
+                &receiver_instruction_prefix,
+                apply_fee_exemptions,
+                accounts,
+            )
+        }
+        LendingInstruction::SetFlashLoanFeeExemption {
+            exempt,
+            fee_discount,
+        } => {
+            msg!("Instruction: Set Flash Loan Fee Exemption");
+            process_set_flash_loan_fee_exemption(program_id, exempt, fee_discount, accounts)
+        }
+        LendingInstruction::RepayWithCollateral { collateral_amount } => {
+            msg!("Instruction: Repay With Collateral");
+            process_repay_with_collateral(program_id, collateral_amount, accounts)
+        }
+        LendingInstruction::SwapCollateral {
+            collateral_amount,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Swap Collateral");
+            process_swap_collateral(program_id, collateral_amount, minimum_amount_out, accounts)
+        }
+        LendingInstruction::AddSwapProgram { swap_program_id } => {
+            msg!("Instruction: Add Swap Program");
+            process_add_swap_program(program_id, swap_program_id, accounts)
+        }
+        LendingInstruction::RemoveSwapProgram => {
+            msg!("Instruction: Remove Swap Program");
+            process_remove_swap_program(program_id, accounts)
+        }
+        LendingInstruction::RefinanceBorrow {
+            liquidity_amount,
+            max_borrow_amount_including_fees,
+        } => {
+            msg!("Instruction: Refinance Borrow");
+            process_refinance_borrow(
+                program_id,
+                liquidity_amount,
+                max_borrow_amount_including_fees,
+                accounts,
+            )
+        }
+        LendingInstruction::OpenLeveragedPosition {
+            liquidity_amount,
+            minimum_amount_out,
+            max_leverage_percent,
+        } => {
+            msg!("Instruction: Open Leveraged Position");
+            process_open_leveraged_position(
+                program_id,
+                liquidity_amount,
+                minimum_amount_out,
+                max_leverage_percent,
+                accounts,
+            )
+        }
+        LendingInstruction::CloseLeveragedPosition {
+            collateral_amount,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Close Leveraged Position");
+            process_close_leveraged_position(
+                program_id,
+                collateral_amount,
+                minimum_amount_out,
+                accounts,
+            )
+        }
+        LendingInstruction::DepositReserveLiquidityAndBorrowObligationLiquidity {
+            deposit_liquidity_amount,
+            borrow_liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        } => {
+            msg!("Instruction: Deposit Reserve Liquidity and Borrow Obligation Liquidity");
+            process_deposit_reserve_liquidity_and_borrow_obligation_liquidity(
+                program_id,
+                deposit_liquidity_amount,
+                borrow_liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+                accounts,
+            )
+        }
+        LendingInstruction::RepayObligationLiquidityAndWithdrawObligationCollateral {
+            repay_liquidity_amount,
+            withdraw_collateral_amount,
+            min_liquidity_amount,
+        } => {
+            msg!("Instruction: Repay Obligation Liquidity and Withdraw Obligation Collateral");
+            process_repay_obligation_liquidity_and_withdraw_obligation_collateral(
+                program_id,
+                repay_liquidity_amount,
+                withdraw_collateral_amount,
+                min_liquidity_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::FlashBorrowReserveLiquidity { liquidity_amount } => {
+            msg!("Instruction: Flash Borrow Reserve Liquidity");
+            process_flash_borrow_reserve_liquidity(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::FlashRepayReserveLiquidity {
+            liquidity_amount,
+            borrow_instruction_index,
+        } => {
+            msg!("Instruction: Flash Repay Reserve Liquidity");
+            process_flash_repay_reserve_liquidity(
+                program_id,
+                liquidity_amount,
+                borrow_instruction_index,
+                accounts,
+            )
+        }
+        LendingInstruction::Batch { ops } => {
+            msg!("Instruction: Batch");
+            process_batch(program_id, &ops, accounts)
+        }
+        LendingInstruction::SetObligationDelegation {
+            delegate,
+            permissions,
+            value_limit,
+        } => {
+            msg!("Instruction: Set Obligation Delegation");
+            process_set_obligation_delegation(
+                program_id,
+                delegate,
+                permissions,
+                value_limit,
+                accounts,
+            )
+        }
+        LendingInstruction::RevokeObligationDelegation => {
+            msg!("Instruction: Revoke Obligation Delegation");
+            process_revoke_obligation_delegation(program_id, accounts)
+        }
+        LendingInstruction::TransferObligationOwnership {
+            new_owner,
+            require_acceptance,
+        } => {
+            msg!("Instruction: Transfer Obligation Ownership");
+            process_transfer_obligation_ownership(
+                program_id,
+                new_owner,
+                require_acceptance,
+                accounts,
+            )
+        }
+        LendingInstruction::AcceptObligationOwnership => {
+            msg!("Instruction: Accept Obligation Ownership");
+            process_accept_obligation_ownership(program_id, accounts)
+        }
+        LendingInstruction::ApproveBorrowAllowance {
+            delegate,
+            allowance,
+        } => {
+            msg!("Instruction: Approve Borrow Allowance");
+            process_approve_borrow_allowance(program_id, delegate, allowance, accounts)
+        }
+        LendingInstruction::RevokeBorrowAllowance => {
+            msg!("Instruction: Revoke Borrow Allowance");
+            process_revoke_borrow_allowance(program_id, accounts)
+        }
+        LendingInstruction::BorrowObligationLiquidityWithAllowance {
+            liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        } => {
+            msg!("Instruction: Borrow Obligation Liquidity With Allowance");
+            process_borrow_obligation_liquidity_with_allowance(
+                program_id,
+                liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+                accounts,
+            )
+        }
+        LendingInstruction::BorrowObligationLiquidityWithDelegation {
+            liquidity_amount,
+            min_receive_amount,
+            max_borrow_amount_including_fees,
+        } => {
+            msg!("Instruction: Borrow Obligation Liquidity With Delegation");
+            process_borrow_obligation_liquidity_with_delegation(
+                program_id,
+                liquidity_amount,
+                min_receive_amount,
+                max_borrow_amount_including_fees,
+                accounts,
+            )
+        }
     }
 }
 
@@ -211,6 +535,7 @@ fn process_init_reserve(
     let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
     let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_fee_receiver_info = next_account_info(account_info_iter)?;
     let pyth_product_info = next_account_info(account_info_iter)?;
     let pyth_price_info = next_account_info(account_info_iter)?;
     let switchboard_feed_info = next_account_info(account_info_iter)?;
@@ -238,6 +563,10 @@ fn process_init_reserve(
         msg!("Reserve liquidity supply cannot be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if &config.collateral_fee_receiver != reserve_collateral_fee_receiver_info.key {
+        msg!("Reserve collateral fee receiver does not match the reserve collateral fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -344,6 +673,14 @@ fn process_init_reserve(
         token_program: token_program_id.clone(),
     })?;
 
+    spl_token_init_account(TokenInitializeAccountParams {
+        account: reserve_collateral_fee_receiver_info.clone(),
+        mint: reserve_collateral_mint_info.clone(),
+        owner: lending_market_owner_info.clone(),
+        rent: rent_info.clone(),
+        token_program: token_program_id.clone(),
+    })?;
+
     spl_token_init_account(TokenInitializeAccountParams {
         account: destination_collateral_info.clone(),
         mint: reserve_collateral_mint_info.clone(),
@@ -430,6 +767,7 @@ fn _refresh_reserve_interest<'a>(
     }
 
     reserve.accrue_interest(clock.slot)?;
+    reserve.finish_ramps(clock.slot);
     reserve.last_update.update_slot(clock.slot);
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -457,6 +795,7 @@ fn process_deposit_reserve_liquidity(
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter).ok();
 
     _refresh_reserve_interest(program_id, reserve_info, clock)?;
     _deposit_reserve_liquidity(
@@ -470,6 +809,7 @@ fn process_deposit_reserve_liquidity(
         lending_market_info,
         lending_market_authority_info,
         user_transfer_authority_info,
+        lending_market_membership_info,
         clock,
         token_program_id,
     )?;
@@ -489,6 +829,7 @@ fn _deposit_reserve_liquidity<'a>(
     lending_market_info: &AccountInfo<'a>,
     lending_market_authority_info: &AccountInfo<'a>,
     user_transfer_authority_info: &AccountInfo<'a>,
+    lending_market_membership_info: Option<&AccountInfo<'a>>,
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
 ) -> Result<u64, ProgramError> {
@@ -501,6 +842,13 @@ fn _deposit_reserve_liquidity<'a>(
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
     }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        user_transfer_authority_info.key,
+        lending_market_membership_info,
+    )?;
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
     if reserve_info.owner != program_id {
         msg!("Reserve provided is not owned by the lending program");
@@ -530,6 +878,10 @@ fn _deposit_reserve_liquidity<'a>(
         msg!("Reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if reserve.status != ReserveStatus::Active {
+        msg!("Reserve is not active and does not accept deposits");
+        return Err(LendingError::ReserveNotActive.into());
+    }
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
         &[lending_market.bump_seed],
@@ -582,6 +934,7 @@ fn _deposit_reserve_liquidity<'a>(
 fn process_redeem_reserve_collateral(
     program_id: &Pubkey,
     collateral_amount: u64,
+    min_liquidity_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     if collateral_amount == 0 {
@@ -605,6 +958,7 @@ fn process_redeem_reserve_collateral(
     _redeem_reserve_collateral(
         program_id,
         collateral_amount,
+        min_liquidity_amount,
         source_collateral_info,
         destination_liquidity_info,
         reserve_info,
@@ -627,6 +981,7 @@ fn process_redeem_reserve_collateral(
 fn _redeem_reserve_collateral<'a>(
     program_id: &Pubkey,
     collateral_amount: u64,
+    min_liquidity_amount: u64,
     source_collateral_info: &AccountInfo<'a>,
     destination_liquidity_info: &AccountInfo<'a>,
     reserve_info: &AccountInfo<'a>,
@@ -673,6 +1028,10 @@ fn _redeem_reserve_collateral<'a>(
         msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if reserve.status == ReserveStatus::Frozen {
+        msg!("Reserve is frozen");
+        return Err(LendingError::ReserveFrozen.into());
+    }
     if reserve.last_update.is_stale(clock.slot)? {
         msg!("Reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
@@ -692,6 +1051,14 @@ fn _redeem_reserve_collateral<'a>(
     }
 
     let liquidity_amount = reserve.redeem_collateral(collateral_amount)?;
+    if liquidity_amount < min_liquidity_amount {
+        msg!(
+            "Liquidity amount received {} is less than the minimum {}",
+            liquidity_amount,
+            min_liquidity_amount
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -717,7 +1084,11 @@ fn _redeem_reserve_collateral<'a>(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+fn process_init_obligation(
+    program_id: &Pubkey,
+    seed: Option<u64>,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
@@ -725,6 +1096,40 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter).ok();
+
+    if let Some(seed) = seed {
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (obligation_pubkey, bump_seed) = find_obligation_address(
+            program_id,
+            lending_market_info.key,
+            obligation_owner_info.key,
+            seed,
+        );
+        if &obligation_pubkey != obligation_info.key {
+            msg!("Obligation provided does not match the derived obligation address");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if obligation_info.data_is_empty() {
+            create_program_derived_account(CreateProgramDerivedAccountParams {
+                payer: payer_info.clone(),
+                account: obligation_info.clone(),
+                space: Obligation::LEN,
+                owner: program_id,
+                rent,
+                signer_seeds: &[
+                    lending_market_info.key.as_ref(),
+                    obligation_owner_info.key.as_ref(),
+                    &seed.to_le_bytes(),
+                    OBLIGATION_SEED,
+                    &[bump_seed],
+                ],
+                system_program: system_program_info.clone(),
+            })?;
+        }
+    }
 
     assert_rent_exempt(rent, obligation_info)?;
     let mut obligation = assert_uninitialized::<Obligation>(obligation_info)?;
@@ -747,6 +1152,13 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         msg!("Obligation owner provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        obligation_owner_info.key,
+        lending_market_membership_info,
+    )?;
 
     obligation.init(InitObligationParams {
         current_slot: clock.slot,
@@ -754,6 +1166,7 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         owner: *obligation_owner_info.key,
         deposits: vec![],
         borrows: vec![],
+        seeded: seed.is_some(),
     });
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
@@ -761,10 +1174,25 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
 }
 
 fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter().peekable();
+    let account_info_iter = &mut accounts.iter();
     let obligation_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    _refresh_obligation(
+        program_id,
+        obligation_info,
+        account_info_iter.as_slice(),
+        clock,
+    )
+}
 
+/// Refresh the obligation from its deposit reserves followed by its borrow reserves
+fn _refresh_obligation<'a>(
+    program_id: &Pubkey,
+    obligation_info: &AccountInfo<'a>,
+    reserve_infos: &[AccountInfo<'a>],
+    clock: &Clock,
+) -> ProgramResult {
+    let account_info_iter = &mut reserve_infos.iter().peekable();
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         msg!("Obligation provided is not owned by the lending program");
@@ -814,9 +1242,10 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
             .try_div(decimals)?;
         collateral.market_value = market_value;
 
-        let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
+        let loan_to_value_rate =
+            Rate::from_percent(deposit_reserve.loan_to_value_ratio(clock.slot));
         let liquidation_threshold_rate =
-            Rate::from_percent(deposit_reserve.config.liquidation_threshold);
+            Rate::from_percent(deposit_reserve.liquidation_threshold(clock.slot));
 
         deposited_value = deposited_value.try_add(market_value)?;
         allowed_borrow_value =
@@ -971,7 +1400,11 @@ fn _deposit_obligation_collateral<'a>(
         msg!("Deposit reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
-    if deposit_reserve.config.loan_to_value_ratio == 0 {
+    if deposit_reserve.status != ReserveStatus::Active {
+        msg!("Deposit reserve is not active and does not accept deposits");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+    if deposit_reserve.loan_to_value_ratio(clock.slot) == 0 {
         msg!("Deposit reserve has collateral disabled for borrowing");
         return Err(LendingError::ReserveCollateralDisabled.into());
     }
@@ -1037,6 +1470,7 @@ fn process_deposit_reserve_liquidity_and_obligation_collateral(
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter).ok();
 
     _refresh_reserve_interest(program_id, reserve_info, clock)?;
     let collateral_amount = _deposit_reserve_liquidity(
@@ -1050,6 +1484,7 @@ fn process_deposit_reserve_liquidity_and_obligation_collateral(
         lending_market_info,
         lending_market_authority_info,
         user_transfer_authority_info,
+        lending_market_membership_info,
         clock,
         token_program_id,
     )?;
@@ -1096,6 +1531,7 @@ fn process_withdraw_obligation_collateral(
     let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let obligation_delegation_info = next_account_info(account_info_iter).ok();
     _withdraw_obligation_collateral(
         program_id,
         collateral_amount,
@@ -1106,6 +1542,7 @@ fn process_withdraw_obligation_collateral(
         lending_market_info,
         lending_market_authority_info,
         obligation_owner_info,
+        obligation_delegation_info,
         clock,
         token_program_id,
     )?;
@@ -1123,6 +1560,7 @@ fn _withdraw_obligation_collateral<'a>(
     lending_market_info: &AccountInfo<'a>,
     lending_market_authority_info: &AccountInfo<'a>,
     obligation_owner_info: &AccountInfo<'a>,
+    obligation_delegation_info: Option<&AccountInfo<'a>>,
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
 ) -> Result<u64, ProgramError> {
@@ -1157,6 +1595,10 @@ fn _withdraw_obligation_collateral<'a>(
         msg!("Withdraw reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if withdraw_reserve.status == ReserveStatus::Frozen {
+        msg!("Withdraw reserve is frozen");
+        return Err(LendingError::ReserveFrozen.into());
+    }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
@@ -1167,14 +1609,14 @@ fn _withdraw_obligation_collateral<'a>(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
-        return Err(LendingError::InvalidObligationOwner.into());
-    }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
-        return Err(LendingError::InvalidSigner.into());
-    }
+    let obligation_delegation = assert_obligation_authority(
+        program_id,
+        &obligation,
+        obligation_info,
+        obligation_owner_info,
+        obligation_delegation_info,
+        DELEGATE_WITHDRAW,
+    )?;
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
@@ -1241,6 +1683,17 @@ fn _withdraw_obligation_collateral<'a>(
         withdraw_amount
     };
 
+    if let (Some(mut obligation_delegation), Some(obligation_delegation_info)) =
+        (obligation_delegation, obligation_delegation_info)
+    {
+        let withdraw_pct = Decimal::from(withdraw_amount).try_div(collateral.deposited_amount)?;
+        obligation_delegation.use_value(collateral.market_value.try_mul(withdraw_pct)?)?;
+        ObligationDelegation::pack(
+            obligation_delegation,
+            &mut obligation_delegation_info.data.borrow_mut(),
+        )?;
+    }
+
     obligation.withdraw(withdraw_amount, collateral_index)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
@@ -1261,6 +1714,8 @@ fn _withdraw_obligation_collateral<'a>(
 fn process_borrow_obligation_liquidity(
     program_id: &Pubkey,
     liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     if liquidity_amount == 0 {
@@ -1280,70 +1735,243 @@ fn process_borrow_obligation_liquidity(
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
-    if lending_market_info.owner != program_id {
-        msg!("Lending market provided is not owned by the lending program");
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &lending_market.token_program_id != token_program_id.key {
-        msg!("Lending market token program does not match the token program provided");
-        return Err(LendingError::InvalidTokenProgram.into());
-    }
+    let host_fee_receiver_info = next_account_info(account_info_iter).ok();
+    _borrow_obligation_liquidity(
+        program_id,
+        liquidity_amount,
+        min_receive_amount,
+        max_borrow_amount_including_fees,
+        source_liquidity_info,
+        destination_liquidity_info,
+        borrow_reserve_info,
+        borrow_reserve_liquidity_fee_receiver_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        None,
+        None,
+        clock,
+        token_program_id,
+        host_fee_receiver_info,
+    )
+}
 
-    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-    if borrow_reserve_info.owner != program_id {
-        msg!("Borrow reserve provided is not owned by the lending program");
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &borrow_reserve.lending_market != lending_market_info.key {
-        msg!("Borrow reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
-        msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
-        msg!(
-            "Borrow reserve liquidity supply cannot be used as the destination liquidity provided"
-        );
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &borrow_reserve.config.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
-        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if borrow_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Borrow reserve is stale and must be refreshed in the current slot");
-        return Err(LendingError::ReserveStale.into());
-    }
-    if liquidity_amount != u64::MAX
-        && Decimal::from(liquidity_amount)
-            .try_add(borrow_reserve.liquidity.borrowed_amount_wads)?
-            .try_floor_u64()?
-            > borrow_reserve.config.borrow_limit
-    {
-        msg!("Cannot borrow above the borrow limit");
+fn process_borrow_obligation_liquidity_with_delegation(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
-    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
-    if obligation_info.owner != program_id {
-        msg!("Obligation provided is not owned by the lending program");
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &obligation.lending_market != lending_market_info.key {
-        msg!("Obligation lending market does not match the lending market provided");
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let obligation_delegation_info = next_account_info(account_info_iter)?;
+
+    let host_fee_receiver_info = next_account_info(account_info_iter).ok();
+    _borrow_obligation_liquidity(
+        program_id,
+        liquidity_amount,
+        min_receive_amount,
+        max_borrow_amount_including_fees,
+        source_liquidity_info,
+        destination_liquidity_info,
+        borrow_reserve_info,
+        borrow_reserve_liquidity_fee_receiver_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        delegate_info,
+        Some(obligation_delegation_info),
+        None,
+        clock,
+        token_program_id,
+        host_fee_receiver_info,
+    )
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_borrow_obligation_liquidity_with_allowance(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 || liquidity_amount == u64::MAX {
+        msg!(
+            "Liquidity amount provided cannot be zero or u64::MAX when borrowing with an allowance"
+        );
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let borrow_allowance_info = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        delegate_info.key,
+        Some(lending_market_membership_info),
+    )?;
+
+    let host_fee_receiver_info = next_account_info(account_info_iter).ok();
+    _borrow_obligation_liquidity(
+        program_id,
+        liquidity_amount,
+        min_receive_amount,
+        max_borrow_amount_including_fees,
+        source_liquidity_info,
+        destination_liquidity_info,
+        borrow_reserve_info,
+        borrow_reserve_liquidity_fee_receiver_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        delegate_info,
+        None,
+        Some(borrow_allowance_info),
+        clock,
+        token_program_id,
+        host_fee_receiver_info,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn _borrow_obligation_liquidity<'a>(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    source_liquidity_info: &AccountInfo<'a>,
+    destination_liquidity_info: &AccountInfo<'a>,
+    borrow_reserve_info: &AccountInfo<'a>,
+    borrow_reserve_liquidity_fee_receiver_info: &AccountInfo<'a>,
+    obligation_info: &AccountInfo<'a>,
+    lending_market_info: &AccountInfo<'a>,
+    lending_market_authority_info: &AccountInfo<'a>,
+    obligation_owner_info: &AccountInfo<'a>,
+    obligation_delegation_info: Option<&AccountInfo<'a>>,
+    borrow_allowance_info: Option<&AccountInfo<'a>>,
+    clock: &Clock,
+    token_program_id: &AccountInfo<'a>,
+    host_fee_receiver_info: Option<&AccountInfo<'a>>,
+) -> ProgramResult {
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
-        return Err(LendingError::InvalidObligationOwner.into());
+    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
-        return Err(LendingError::InvalidSigner.into());
+    if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!(
+            "Borrow reserve liquidity supply cannot be used as the destination liquidity provided"
+        );
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.config.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
+        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if borrow_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Borrow reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+    if borrow_reserve.status != ReserveStatus::Active {
+        msg!("Borrow reserve is not active and does not accept borrows");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+    if liquidity_amount != u64::MAX
+        && Decimal::from(liquidity_amount)
+            .try_add(borrow_reserve.liquidity.borrowed_amount_wads)?
+            .try_floor_u64()?
+            > borrow_reserve.config.borrow_limit
+    {
+        msg!("Cannot borrow above the borrow limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
     }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    // a borrow allowance delegate signs in place of the obligation owner
+    let (obligation_delegation, borrow_allowance) = match borrow_allowance_info {
+        Some(borrow_allowance_info) => (
+            None,
+            Some(assert_borrow_allowance(
+                program_id,
+                obligation_info,
+                borrow_reserve_info,
+                obligation_owner_info,
+                borrow_allowance_info,
+            )?),
+        ),
+        None => (
+            assert_obligation_authority(
+                program_id,
+                &obligation,
+                obligation_info,
+                obligation_owner_info,
+                obligation_delegation_info,
+                DELEGATE_BORROW,
+            )?,
+            None,
+        ),
+    };
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
@@ -1395,6 +2023,49 @@ fn process_borrow_obligation_liquidity(
         msg!("Borrow amount is too small to receive liquidity after fees");
         return Err(LendingError::BorrowTooSmall.into());
     }
+    if receive_amount < min_receive_amount {
+        msg!(
+            "Liquidity amount received {} is less than the minimum {}",
+            receive_amount,
+            min_receive_amount
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
+    if borrow_amount > Decimal::from(max_borrow_amount_including_fees) {
+        msg!(
+            "Borrow amount including fees {} is greater than the maximum {}",
+            borrow_amount,
+            max_borrow_amount_including_fees
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
+
+    if let (Some(mut obligation_delegation), Some(obligation_delegation_info)) =
+        (obligation_delegation, obligation_delegation_info)
+    {
+        // @TODO: add lookup table https://git.io/JOCYq
+        let decimals = 10u64
+            .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        obligation_delegation.use_value(
+            borrow_amount
+                .try_mul(borrow_reserve.liquidity.market_price)?
+                .try_div(decimals)?,
+        )?;
+        ObligationDelegation::pack(
+            obligation_delegation,
+            &mut obligation_delegation_info.data.borrow_mut(),
+        )?;
+    }
+    if let (Some(mut borrow_allowance), Some(borrow_allowance_info)) =
+        (borrow_allowance, borrow_allowance_info)
+    {
+        borrow_allowance.borrow(borrow_amount)?;
+        BorrowAllowance::pack(
+            borrow_allowance,
+            &mut borrow_allowance_info.data.borrow_mut(),
+        )?;
+    }
 
     let cumulative_borrow_rate_wads = borrow_reserve.liquidity.cumulative_borrow_rate_wads;
 
@@ -1410,7 +2081,7 @@ fn process_borrow_obligation_liquidity(
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let mut owner_fee = borrow_fee;
-    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
+    if let Some(host_fee_receiver_info) = host_fee_receiver_info {
         if host_fee > 0 {
             owner_fee = owner_fee
                 .checked_sub(host_fee)
@@ -1469,6 +2140,33 @@ fn process_repay_obligation_liquidity(
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    _repay_obligation_liquidity(
+        program_id,
+        liquidity_amount,
+        source_liquidity_info,
+        destination_liquidity_info,
+        repay_reserve_info,
+        obligation_info,
+        lending_market_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn _repay_obligation_liquidity<'a>(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_info: &AccountInfo<'a>,
+    destination_liquidity_info: &AccountInfo<'a>,
+    repay_reserve_info: &AccountInfo<'a>,
+    obligation_info: &AccountInfo<'a>,
+    lending_market_info: &AccountInfo<'a>,
+    user_transfer_authority_info: &AccountInfo<'a>,
+    clock: &Clock,
+    token_program_id: &AccountInfo<'a>,
+) -> ProgramResult {
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1553,29 +2251,27 @@ fn process_repay_obligation_liquidity(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_liquidate_obligation(
+fn process_repay_with_collateral(
     program_id: &Pubkey,
-    liquidity_amount: u64,
+    collateral_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Liquidity amount provided cannot be zero");
+    if collateral_amount == 0 {
+        msg!("Collateral amount provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
     let account_info_iter = &mut accounts.iter();
-    let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_collateral_info = next_account_info(account_info_iter)?;
-    let repay_reserve_info = next_account_info(account_info_iter)?;
-    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let obligation_delegation_info = next_account_info(account_info_iter).ok();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -1587,57 +2283,25 @@ fn process_liquidate_obligation(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
-    if repay_reserve_info.owner != program_id {
-        msg!("Repay reserve provided is not owned by the lending program");
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &repay_reserve.lending_market != lending_market_info.key {
-        msg!("Repay reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
-        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
-        msg!(
-            "Repay reserve collateral supply cannot be used as the destination collateral provided"
-        );
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if repay_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Repay reserve is stale and must be refreshed in the current slot");
-        return Err(LendingError::ReserveStale.into());
-    }
-
-    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
-    if withdraw_reserve_info.owner != program_id {
-        msg!("Withdraw reserve provided is not owned by the lending program");
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &withdraw_reserve.lending_market != lending_market_info.key {
-        msg!("Withdraw reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
-        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Reserve collateral supply does not match the reserve collateral supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
-        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Reserve collateral mint does not match the reserve collateral mint provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if withdraw_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
 
@@ -1650,36 +2314,30 @@ fn process_liquidate_obligation(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    assert_obligation_authority(
+        program_id,
+        &obligation,
+        obligation_info,
+        obligation_owner_info,
+        obligation_delegation_info,
+        DELEGATE_REPAY_WITH_COLLATERAL,
+    )?;
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
     }
-    if obligation.deposited_value == Decimal::zero() {
-        msg!("Obligation deposited value is zero");
-        return Err(LendingError::ObligationDepositsZero.into());
-    }
-    if obligation.borrowed_value == Decimal::zero() {
-        msg!("Obligation borrowed value is zero");
-        return Err(LendingError::ObligationBorrowsZero.into());
-    }
-    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
-        msg!("Obligation is healthy and cannot be liquidated");
-        return Err(LendingError::ObligationHealthy.into());
-    }
-
-    let (liquidity, liquidity_index) =
-        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
-    if liquidity.market_value == Decimal::zero() {
-        msg!("Obligation borrow value is zero");
-        return Err(LendingError::ObligationLiquidityEmpty.into());
-    }
 
     let (collateral, collateral_index) =
-        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
-    if collateral.market_value == Decimal::zero() {
-        msg!("Obligation deposit value is zero");
+        obligation.find_collateral_in_deposits(*reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        msg!("Collateral deposited amount is zero");
         return Err(LendingError::ObligationCollateralEmpty.into());
     }
+    let (liquidity, liquidity_index) = obligation.find_liquidity_in_borrows(*reserve_info.key)?;
+    if liquidity.borrowed_amount_wads == Decimal::zero() {
+        msg!("Liquidity borrowed amount is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -1694,48 +2352,60 @@ fn process_liquidate_obligation(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let CalculateLiquidationResult {
+    let CalculateRepayWithCollateralResult {
+        collateral_amount,
         settle_amount,
-        repay_amount,
-        withdraw_amount,
-    } = withdraw_reserve.calculate_liquidation(
-        liquidity_amount,
-        &obligation,
-        liquidity,
-        collateral,
+    } = reserve.calculate_repay_with_collateral(
+        collateral_amount,
+        collateral.deposited_amount,
+        liquidity.borrowed_amount_wads,
     )?;
-
-    if repay_amount == 0 {
-        msg!("Liquidation is too small to transfer liquidity");
-        return Err(LendingError::LiquidationTooSmall.into());
+    if collateral_amount == 0 || settle_amount == Decimal::zero() {
+        msg!("Repay amount is too small to settle any liquidity");
+        return Err(LendingError::RepayTooSmall.into());
     }
-    if withdraw_amount == 0 {
-        msg!("Liquidation is too small to receive collateral");
-        return Err(LendingError::LiquidationTooSmall.into());
+
+    // the obligation must stay within its allowed borrow value once the collateral is burned
+    let fully_repaid = settle_amount == liquidity.borrowed_amount_wads;
+    if !fully_repaid || obligation.borrows.len() > 1 {
+        let burn_value = collateral
+            .market_value
+            .try_mul(Decimal::from(collateral_amount))?
+            .try_div(Decimal::from(collateral.deposited_amount))?;
+        let settle_value = liquidity
+            .market_value
+            .try_mul(settle_amount)?
+            .try_div(liquidity.borrowed_amount_wads)?;
+        let loan_to_value_rate = Rate::from_percent(reserve.loan_to_value_ratio(clock.slot));
+
+        let borrowed_value = obligation
+            .borrowed_value
+            .try_sub(settle_value.min(obligation.borrowed_value))?;
+        let allowed_borrow_reduction = burn_value.try_mul(loan_to_value_rate)?;
+        let allowed_borrow_value = obligation
+            .allowed_borrow_value
+            .try_sub(allowed_borrow_reduction.min(obligation.allowed_borrow_value))?;
+        if borrowed_value > allowed_borrow_value {
+            msg!(
+                "Repay with collateral cannot leave the obligation above its allowed borrow value"
+            );
+            return Err(LendingError::WithdrawTooLarge.into());
+        }
     }
 
-    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
-    repay_reserve.last_update.mark_stale();
-    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+    reserve.repay_with_collateral(collateral_amount, settle_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     obligation.repay(settle_amount, liquidity_index)?;
-    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.withdraw(collateral_amount, collateral_index)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    spl_token_transfer(TokenTransferParams {
-        source: source_liquidity_info.clone(),
-        destination: repay_reserve_liquidity_supply_info.clone(),
-        amount: repay_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
-
-    spl_token_transfer(TokenTransferParams {
-        source: withdraw_reserve_collateral_supply_info.clone(),
-        destination: destination_collateral_info.clone(),
-        amount: withdraw_amount,
+    spl_token_burn(TokenBurnParams {
+        mint: reserve_collateral_mint_info.clone(),
+        source: reserve_collateral_supply_info.clone(),
+        amount: collateral_amount,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
@@ -1745,40 +2415,113 @@ fn process_liquidate_obligation(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_flash_loan(
+fn process_swap_collateral(
     program_id: &Pubkey,
-    liquidity_amount: u64,
+    collateral_amount: u64,
+    minimum_amount_out: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Liquidity amount provided cannot be zero");
+    if collateral_amount == 0 {
+        msg!("Collateral amount provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
     let account_info_iter = &mut accounts.iter();
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
     let source_liquidity_info = next_account_info(account_info_iter)?;
     let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let reserve_info = next_account_info(account_info_iter)?;
-    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
-    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
-    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
-
-    if program_id == flash_loan_receiver_program_id.key {
-        msg!("Lending program cannot be used as the flash loan receiver program provided");
-        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
-    }
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+    let swap_program_approval_info = next_account_info(account_info_iter)?;
+    let swap_program_info = next_account_info(account_info_iter)?;
+    let swap_account_infos = account_info_iter.as_slice();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
     if &lending_market.token_program_id != token_program_id.key {
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
     }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        obligation_owner_info.key,
+        Some(lending_market_membership_info),
+    )?;
+
+    assert_swap_program_approved(
+        program_id,
+        lending_market_info,
+        swap_program_approval_info,
+        swap_program_info,
+    )?;
+
+    if withdraw_reserve_info.key == deposit_reserve_info.key {
+        msg!("Withdraw reserve and deposit reserve cannot be the same");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let withdraw_reserve = assert_withdraw_reserve(
+        program_id,
+        lending_market_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        source_liquidity_info,
+        clock,
+    )?;
+
+    let deposit_reserve = assert_deposit_reserve(
+        program_id,
+        lending_market_info,
+        deposit_reserve_info,
+        deposit_reserve_liquidity_supply_info,
+        deposit_reserve_collateral_mint_info,
+        deposit_reserve_collateral_supply_info,
+        destination_liquidity_info,
+        clock,
+    )?;
+    let deposit_loan_to_value_rate =
+        Rate::from_percent(deposit_reserve.loan_to_value_ratio(clock.slot));
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -1793,292 +2536,4348 @@ fn process_flash_loan(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
-    if reserve_info.owner != program_id {
-        msg!("Reserve provided is not owned by the lending program");
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &reserve.lending_market != lending_market_info.key {
-        msg!("Invalid reserve lending market account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
-        msg!("Reserve liquidity supply must be used as the source liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
-        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-
-    // @FIXME: if u64::MAX is flash loaned, fees should be inclusive as with ordinary borrows
-    let flash_loan_amount = if liquidity_amount == u64::MAX {
-        reserve.liquidity.available_amount
-    } else {
-        liquidity_amount
-    };
-
-    let flash_loan_amount_decimal = Decimal::from(flash_loan_amount);
-    let (origination_fee, host_fee) = reserve
-        .config
-        .fees
-        .calculate_flash_loan_fees(flash_loan_amount_decimal)?;
-
-    let balance_before_flash_loan = Account::unpack(&source_liquidity_info.data.borrow())?.amount;
-    let expected_balance_after_flash_loan = balance_before_flash_loan
-        .checked_add(origination_fee)
-        .ok_or(LendingError::MathOverflow)?;
-    let returned_amount_required = flash_loan_amount
-        .checked_add(origination_fee)
-        .ok_or(LendingError::MathOverflow)?;
-
-    let mut flash_loan_instruction_accounts = vec![
-        AccountMeta::new(*destination_liquidity_info.key, false),
-        AccountMeta::new(*source_liquidity_info.key, false),
-        AccountMeta::new_readonly(*token_program_id.key, false),
-    ];
-    let mut flash_loan_instruction_account_infos = vec![
-        destination_liquidity_info.clone(),
-        flash_loan_receiver_program_id.clone(),
-        source_liquidity_info.clone(),
-        token_program_id.clone(),
-    ];
-    for account_info in account_info_iter {
-        flash_loan_instruction_accounts.push(AccountMeta {
-            pubkey: *account_info.key,
-            is_signer: account_info.is_signer,
-            is_writable: account_info.is_writable,
-        });
-        flash_loan_instruction_account_infos.push(account_info.clone());
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        msg!("Collateral deposited amount is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
     }
+    let withdraw_amount = collateral.deposited_amount.min(collateral_amount);
+    let withdraw_value = collateral
+        .market_value
+        .try_mul(Decimal::from(withdraw_amount))?
+        .try_div(Decimal::from(collateral.deposited_amount))?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    reserve.liquidity.borrow(flash_loan_amount_decimal)?;
-    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
-
-    spl_token_transfer(TokenTransferParams {
-        source: source_liquidity_info.clone(),
-        destination: destination_liquidity_info.clone(),
-        amount: flash_loan_amount,
-        authority: lending_market_authority_info.clone(),
+    let (withdraw_loan_to_value_rate, amount_out) = _redeem_and_swap_collateral(
+        withdraw_reserve,
+        withdraw_amount,
+        minimum_amount_out,
+        clock,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        source_liquidity_info,
+        destination_liquidity_info,
+        lending_market_authority_info,
         authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
-
-    const RECEIVE_FLASH_LOAN_INSTRUCTION_DATA_SIZE: usize = 9;
-    // @FIXME: don't use 0 to indicate a flash loan receiver instruction https://git.io/JGzz9
-    const RECEIVE_FLASH_LOAN_INSTRUCTION_TAG: u8 = 0u8;
-
-    let mut data = Vec::with_capacity(RECEIVE_FLASH_LOAN_INSTRUCTION_DATA_SIZE);
-    data.push(RECEIVE_FLASH_LOAN_INSTRUCTION_TAG);
-    data.extend_from_slice(&returned_amount_required.to_le_bytes());
-
-    invoke(
-        &Instruction {
-            program_id: *flash_loan_receiver_program_id.key,
-            accounts: flash_loan_instruction_accounts,
-            data,
-        },
-        &flash_loan_instruction_account_infos[..],
+        token_program_id,
+        swap_program_info,
+        swap_account_infos,
     )?;
 
-    reserve = Reserve::unpack(&reserve_info.data.borrow())?;
-    reserve
-        .liquidity
-        .repay(flash_loan_amount, flash_loan_amount_decimal)?;
-    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    let (deposit_value, deposit_collateral_amount) = _deposit_swapped_liquidity(
+        amount_out,
+        deposit_reserve_info,
+        deposit_reserve_liquidity_supply_info,
+        deposit_reserve_collateral_mint_info,
+        deposit_reserve_collateral_supply_info,
+        destination_liquidity_info,
+        lending_market_authority_info,
+        authority_signer_seeds,
+        obligation_owner_info,
+        token_program_id,
+    )?;
 
-    let actual_balance_after_flash_loan =
-        Account::unpack(&source_liquidity_info.data.borrow())?.amount;
-    if actual_balance_after_flash_loan < expected_balance_after_flash_loan {
-        msg!("Insufficient reserve liquidity after flash loan");
-        return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
-    }
+    // the swap program can invoke other programs, so the obligation is reloaded once it returns
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
 
-    let mut owner_fee = origination_fee;
-    if host_fee > 0 {
-        owner_fee = owner_fee
-            .checked_sub(host_fee)
-            .ok_or(LendingError::MathOverflow)?;
-        spl_token_transfer(TokenTransferParams {
-            source: source_liquidity_info.clone(),
-            destination: host_fee_receiver_info.clone(),
-            amount: host_fee,
-            authority: lending_market_authority_info.clone(),
-            authority_signer_seeds,
-            token_program: token_program_id.clone(),
-        })?;
+    // health is only checked once the swapped collateral is deposited
+    if !obligation.borrows.is_empty() {
+        let withdraw_allowed_borrow_value = withdraw_value.try_mul(withdraw_loan_to_value_rate)?;
+        let allowed_borrow_value = obligation
+            .allowed_borrow_value
+            .try_sub(withdraw_allowed_borrow_value.min(obligation.allowed_borrow_value))?
+            .try_add(deposit_value.try_mul(deposit_loan_to_value_rate)?)?;
+        if obligation.borrowed_value > allowed_borrow_value {
+            msg!("Swap collateral cannot leave the obligation above its allowed borrow value");
+            return Err(LendingError::WithdrawTooLarge.into());
+        }
     }
 
-    if owner_fee > 0 {
-        spl_token_transfer(TokenTransferParams {
-            source: source_liquidity_info.clone(),
-            destination: reserve_liquidity_fee_receiver_info.clone(),
-            amount: owner_fee,
-            authority: lending_market_authority_info.clone(),
-            authority_signer_seeds,
-            token_program: token_program_id.clone(),
-        })?;
-    }
+    obligation
+        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .deposit(deposit_collateral_amount)?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     Ok(())
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_withdraw_obligation_collateral_and_redeem_reserve_liquidity(
+fn process_refinance_borrow(
     program_id: &Pubkey,
-    collateral_amount: u64,
+    liquidity_amount: u64,
+    max_borrow_amount_including_fees: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let reserve_collateral_info = next_account_info(account_info_iter)?;
-    let user_collateral_info = next_account_info(account_info_iter)?;
-    let reserve_info = next_account_info(account_info_iter)?;
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let user_liquidity_info = next_account_info(account_info_iter)?;
-    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
-    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
     let obligation_owner_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    let liquidity_amount = _withdraw_obligation_collateral(
-        program_id,
-        collateral_amount,
-        reserve_collateral_info,
-        user_collateral_info,
-        reserve_info,
-        obligation_info,
-        lending_market_info,
-        lending_market_authority_info,
-        obligation_owner_info,
-        clock,
-        token_program_id,
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    if repay_reserve_info.key == borrow_reserve_info.key {
+        msg!("Repay reserve and borrow reserve cannot be the same");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve_info.owner != program_id {
+        msg!("Repay reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &repay_reserve.lending_market != lending_market_info.key {
+        msg!("Repay reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Repay reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.liquidity.supply_pubkey != borrow_reserve_liquidity_supply_info.key {
+        msg!("Borrow reserve liquidity supply does not match the borrow reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.config.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
+        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if borrow_reserve.liquidity.mint_pubkey != repay_reserve.liquidity.mint_pubkey {
+        msg!("Borrow reserve liquidity mint does not match the repay reserve liquidity mint");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if borrow_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Borrow reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+    if borrow_reserve.status != ReserveStatus::Active {
+        msg!("Borrow reserve is not active and does not accept borrows");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    if liquidity.borrowed_amount_wads == Decimal::zero() {
+        msg!("Liquidity borrowed amount is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+
+    let CalculateRepayResult {
+        settle_amount,
+        repay_amount,
+    } = repay_reserve.calculate_repay(liquidity_amount, liquidity.borrowed_amount_wads)?;
+    if repay_amount == 0 {
+        msg!("Repay amount is too small to transfer liquidity");
+        return Err(LendingError::RepayTooSmall.into());
+    }
+
+    // the repaid debt frees up borrowing power for the new borrow, so health is only checked
+    // once both legs are accounted for
+    let settle_value = liquidity
+        .market_value
+        .try_mul(settle_amount)?
+        .try_div(liquidity.borrowed_amount_wads)?;
+    let borrowed_value = obligation
+        .borrowed_value
+        .try_sub(settle_value.min(obligation.borrowed_value))?;
+    let remaining_borrow_value = obligation
+        .allowed_borrow_value
+        .try_sub(borrowed_value.min(obligation.allowed_borrow_value))?;
+    let remaining_reserve_capacity = Decimal::from(borrow_reserve.config.borrow_limit)
+        .try_sub(borrow_reserve.liquidity.borrowed_amount_wads)
+        .unwrap_or_else(|_| Decimal::zero());
+
+    let CalculateBorrowResult {
+        borrow_amount,
+        receive_amount,
+        borrow_fee,
+        host_fee,
+    } = borrow_reserve.calculate_borrow(
+        repay_amount,
+        remaining_borrow_value,
+        remaining_reserve_capacity,
     )?;
+    if borrow_amount > remaining_reserve_capacity {
+        msg!("Cannot borrow above the borrow limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if borrow_amount > Decimal::from(max_borrow_amount_including_fees) {
+        msg!(
+            "Borrow amount including fees {} is greater than the maximum {}",
+            borrow_amount,
+            max_borrow_amount_including_fees
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
 
-    _redeem_reserve_collateral(
-        program_id,
-        liquidity_amount,
-        user_collateral_info,
-        user_liquidity_info,
-        reserve_info,
-        reserve_collateral_mint_info,
-        reserve_liquidity_supply_info,
-        lending_market_info,
-        lending_market_authority_info,
-        user_transfer_authority_info,
-        clock,
-        token_program_id,
-    )
+    let cumulative_borrow_rate_wads = borrow_reserve.liquidity.cumulative_borrow_rate_wads;
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    borrow_reserve.liquidity.borrow(borrow_amount)?;
+    borrow_reserve.last_update.mark_stale();
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation
+        .find_or_add_liquidity_to_borrows(*borrow_reserve_info.key, cumulative_borrow_rate_wads)?
+        .borrow(borrow_amount)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    let mut owner_fee = borrow_fee;
+    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
+        if host_fee > 0 {
+            owner_fee = owner_fee
+                .checked_sub(host_fee)
+                .ok_or(LendingError::MathOverflow)?;
+
+            spl_token_transfer(TokenTransferParams {
+                source: borrow_reserve_liquidity_supply_info.clone(),
+                destination: host_fee_receiver_info.clone(),
+                amount: host_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: borrow_reserve_liquidity_supply_info.clone(),
+            destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: borrow_reserve_liquidity_supply_info.clone(),
+        destination: repay_reserve_liquidity_supply_info.clone(),
+        amount: receive_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_update_reserve_config(
+fn process_open_leveraged_position(
     program_id: &Pubkey,
-    config: ReserveConfig,
+    liquidity_amount: u64,
+    minimum_amount_out: u64,
+    max_leverage_percent: u16,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    validate_reserve_config(config)?;
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    // the swap output is only known once the liquidity is borrowed, so the most the obligation
+    // can borrow cannot be sized up front
+    if liquidity_amount == u64::MAX {
+        msg!("Liquidity amount provided cannot be u64::MAX");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if max_leverage_percent < 100 {
+        msg!("Max leverage percent provided cannot be less than 100");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
     let account_info_iter = &mut accounts.iter();
-    let reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let lending_market_owner_info = next_account_info(account_info_iter)?;
-    let pyth_product_info = next_account_info(account_info_iter)?;
-    let pyth_price_info = next_account_info(account_info_iter)?;
-    let switchboard_feed_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+    let swap_program_approval_info = next_account_info(account_info_iter)?;
+    let swap_program_info = next_account_info(account_info_iter)?;
+    let swap_account_infos = account_info_iter.as_slice();
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        obligation_owner_info.key,
+        Some(lending_market_membership_info),
+    )?;
+    assert_swap_program_approved(
+        program_id,
+        lending_market_info,
+        swap_program_approval_info,
+        swap_program_info,
+    )?;
+
+    if borrow_reserve_info.key == deposit_reserve_info.key {
+        msg!("Borrow reserve and deposit reserve cannot be the same");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.liquidity.supply_pubkey != borrow_reserve_liquidity_supply_info.key {
+        msg!("Borrow reserve liquidity supply does not match the borrow reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.config.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
+        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Borrow reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if borrow_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Borrow reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+    if borrow_reserve.status != ReserveStatus::Active {
+        msg!("Borrow reserve is not active and does not accept borrows");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+
+    let deposit_reserve = assert_deposit_reserve(
+        program_id,
+        lending_market_info,
+        deposit_reserve_info,
+        deposit_reserve_liquidity_supply_info,
+        deposit_reserve_collateral_mint_info,
+        deposit_reserve_collateral_supply_info,
+        destination_liquidity_info,
+        clock,
+    )?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    // the borrowed liquidity is deposited back as collateral once swapped, so the obligation can
+    // borrow up to its remaining borrow value over one less the deposit loan to value ratio
+    let deposit_loan_to_value_rate =
+        Rate::from_percent(deposit_reserve.loan_to_value_ratio(clock.slot));
+    let remaining_borrow_value = obligation
+        .remaining_borrow_value()?
+        .try_div(Rate::one().try_sub(deposit_loan_to_value_rate)?)?;
+    if remaining_borrow_value == Decimal::zero() {
+        msg!("Remaining borrow value is zero");
+        return Err(LendingError::BorrowTooLarge.into());
+    }
+
+    let remaining_reserve_capacity = Decimal::from(borrow_reserve.config.borrow_limit)
+        .try_sub(borrow_reserve.liquidity.borrowed_amount_wads)
+        .unwrap_or_else(|_| Decimal::zero());
+
+    let CalculateBorrowResult {
+        borrow_amount,
+        receive_amount,
+        borrow_fee,
+        host_fee,
+    } = borrow_reserve.calculate_borrow(
+        liquidity_amount,
+        remaining_borrow_value,
+        remaining_reserve_capacity,
+    )?;
+    if receive_amount == 0 {
+        msg!("Borrow amount is too small to receive liquidity after fees");
+        return Err(LendingError::BorrowTooSmall.into());
+    }
+    if borrow_amount > remaining_reserve_capacity {
+        msg!("Cannot borrow above the borrow limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    // @TODO: add lookup table https://git.io/JOCYq
+    let borrow_decimals = 10u64
+        .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
+        .ok_or(LendingError::MathOverflow)?;
+    let borrow_value = borrow_amount
+        .try_mul(borrow_reserve.liquidity.market_price)?
+        .try_div(borrow_decimals)?;
+    let cumulative_borrow_rate_wads = borrow_reserve.liquidity.cumulative_borrow_rate_wads;
+
+    borrow_reserve.liquidity.borrow(borrow_amount)?;
+    borrow_reserve.last_update.mark_stale();
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
+    let mut owner_fee = borrow_fee;
+    if host_fee > 0 {
+        owner_fee = owner_fee
+            .checked_sub(host_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        spl_token_transfer(TokenTransferParams {
+            source: borrow_reserve_liquidity_supply_info.clone(),
+            destination: host_fee_receiver_info.clone(),
+            amount: host_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: borrow_reserve_liquidity_supply_info.clone(),
+            destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: borrow_reserve_liquidity_supply_info.clone(),
+        destination: source_liquidity_info.clone(),
+        amount: receive_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    let amount_out = _swap_liquidity(
+        swap_program_info,
+        swap_account_infos,
+        destination_liquidity_info,
+        receive_amount,
+        minimum_amount_out,
+    )?;
+
+    let (deposit_value, deposit_collateral_amount) = _deposit_swapped_liquidity(
+        amount_out,
+        deposit_reserve_info,
+        deposit_reserve_liquidity_supply_info,
+        deposit_reserve_collateral_mint_info,
+        deposit_reserve_collateral_supply_info,
+        destination_liquidity_info,
+        lending_market_authority_info,
+        authority_signer_seeds,
+        obligation_owner_info,
+        token_program_id,
+    )?;
+
+    // health and leverage are only checked once the swapped collateral is deposited
+    let borrowed_value = obligation.borrowed_value.try_add(borrow_value)?;
+    let allowed_borrow_value = obligation
+        .allowed_borrow_value
+        .try_add(deposit_value.try_mul(deposit_loan_to_value_rate)?)?;
+    if borrowed_value > allowed_borrow_value {
+        msg!("Leveraged position cannot leave the obligation above its allowed borrow value");
+        return Err(LendingError::BorrowTooLarge.into());
+    }
+
+    let deposited_value = obligation.deposited_value.try_add(deposit_value)?;
+    if deposited_value <= borrowed_value
+        || deposited_value.try_mul(100)?
+            > deposited_value
+                .try_sub(borrowed_value)?
+                .try_mul(max_leverage_percent as u64)?
+    {
+        msg!(
+            "Leveraged position cannot leave the obligation above the max leverage of {}%",
+            max_leverage_percent
+        );
+        return Err(LendingError::LeverageTooHigh.into());
+    }
+
+    obligation
+        .find_or_add_liquidity_to_borrows(*borrow_reserve_info.key, cumulative_borrow_rate_wads)?
+        .borrow(borrow_amount)?;
+    obligation
+        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .deposit(deposit_collateral_amount)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_close_leveraged_position(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    minimum_amount_out: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if collateral_amount == 0 {
+        msg!("Collateral amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let swap_program_approval_info = next_account_info(account_info_iter)?;
+    let swap_program_info = next_account_info(account_info_iter)?;
+    let swap_account_infos = account_info_iter.as_slice();
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    assert_swap_program_approved(
+        program_id,
+        lending_market_info,
+        swap_program_approval_info,
+        swap_program_info,
+    )?;
+
+    if withdraw_reserve_info.key == repay_reserve_info.key {
+        msg!("Withdraw reserve and repay reserve cannot be the same");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let withdraw_reserve = assert_withdraw_reserve(
+        program_id,
+        lending_market_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        source_liquidity_info,
+        clock,
+    )?;
+
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve_info.owner != program_id {
+        msg!("Repay reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &repay_reserve.lending_market != lending_market_info.key {
+        msg!("Repay reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Repay reserve liquidity supply cannot be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Repay reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    if liquidity.borrowed_amount_wads == Decimal::zero() {
+        msg!("Liquidity borrowed amount is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        msg!("Collateral deposited amount is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+
+    let withdraw_amount = collateral.deposited_amount.min(collateral_amount);
+    let withdraw_value = collateral
+        .market_value
+        .try_mul(Decimal::from(withdraw_amount))?
+        .try_div(Decimal::from(collateral.deposited_amount))?;
+
+    let (withdraw_loan_to_value_rate, amount_out) = _redeem_and_swap_collateral(
+        withdraw_reserve,
+        withdraw_amount,
+        minimum_amount_out,
+        clock,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        source_liquidity_info,
+        destination_liquidity_info,
+        lending_market_authority_info,
+        authority_signer_seeds,
+        token_program_id,
+        swap_program_info,
+        swap_account_infos,
+    )?;
+
+    let CalculateRepayResult {
+        settle_amount,
+        repay_amount,
+    } = repay_reserve.calculate_repay(amount_out, liquidity.borrowed_amount_wads)?;
+    if repay_amount == 0 {
+        msg!("Repay amount is too small to transfer liquidity");
+        return Err(LendingError::RepayTooSmall.into());
+    }
+
+    // health is only checked once the swapped liquidity repays the borrow
+    let fully_repaid = settle_amount == liquidity.borrowed_amount_wads;
+    if !fully_repaid || obligation.borrows.len() > 1 {
+        let settle_value = liquidity
+            .market_value
+            .try_mul(settle_amount)?
+            .try_div(liquidity.borrowed_amount_wads)?;
+        let borrowed_value = obligation
+            .borrowed_value
+            .try_sub(settle_value.min(obligation.borrowed_value))?;
+        let withdraw_allowed_borrow_value = withdraw_value.try_mul(withdraw_loan_to_value_rate)?;
+        let allowed_borrow_value = obligation
+            .allowed_borrow_value
+            .try_sub(withdraw_allowed_borrow_value.min(obligation.allowed_borrow_value))?;
+        if borrowed_value > allowed_borrow_value {
+            msg!("Leveraged position cannot leave the obligation above its allowed borrow value");
+            return Err(LendingError::WithdrawTooLarge.into());
+        }
+    }
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: destination_liquidity_info.clone(),
+        destination: repay_reserve_liquidity_supply_info.clone(),
+        amount: repay_amount,
+        authority: obligation_owner_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_liquidate_obligation(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    _liquidate_obligation(
+        program_id,
+        liquidity_amount,
+        min_collateral_amount,
+        source_liquidity_info,
+        destination_collateral_info,
+        repay_reserve_info,
+        repay_reserve_liquidity_supply_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        withdraw_reserve_collateral_fee_receiver_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+#[allow(clippy::too_many_arguments)]
+fn _liquidate_obligation<'a>(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_collateral_amount: u64,
+    source_liquidity_info: &AccountInfo<'a>,
+    destination_collateral_info: &AccountInfo<'a>,
+    repay_reserve_info: &AccountInfo<'a>,
+    repay_reserve_liquidity_supply_info: &AccountInfo<'a>,
+    withdraw_reserve_info: &AccountInfo<'a>,
+    withdraw_reserve_collateral_supply_info: &AccountInfo<'a>,
+    withdraw_reserve_collateral_fee_receiver_info: &AccountInfo<'a>,
+    obligation_info: &AccountInfo<'a>,
+    lending_market_info: &AccountInfo<'a>,
+    lending_market_authority_info: &AccountInfo<'a>,
+    user_transfer_authority_info: &AccountInfo<'a>,
+    clock: &Clock,
+    token_program_id: &AccountInfo<'a>,
+) -> Result<u64, ProgramError> {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve_info.owner != program_id {
+        msg!("Repay reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &repay_reserve.lending_market != lending_market_info.key {
+        msg!("Repay reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+        msg!(
+            "Repay reserve collateral supply cannot be used as the destination collateral provided"
+        );
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Repay reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if withdraw_reserve_info.owner != program_id {
+        msg!("Withdraw reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &withdraw_reserve.lending_market != lending_market_info.key {
+        msg!("Withdraw reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
+        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.config.collateral_fee_receiver
+        != withdraw_reserve_collateral_fee_receiver_info.key
+    {
+        msg!("Withdraw reserve collateral fee receiver does not match the withdraw reserve collateral fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if withdraw_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+    if obligation.deposited_value == Decimal::zero() {
+        msg!("Obligation deposited value is zero");
+        return Err(LendingError::ObligationDepositsZero.into());
+    }
+    if obligation.borrowed_value == Decimal::zero() {
+        msg!("Obligation borrowed value is zero");
+        return Err(LendingError::ObligationBorrowsZero.into());
+    }
+    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
+        msg!("Obligation is healthy and cannot be liquidated");
+        return Err(LendingError::ObligationHealthy.into());
+    }
+
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    if liquidity.market_value == Decimal::zero() {
+        msg!("Obligation borrow value is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+    if collateral.market_value == Decimal::zero() {
+        msg!("Obligation deposit value is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let CalculateLiquidationResult {
+        settle_amount,
+        repay_amount,
+        withdraw_amount,
+        protocol_liquidation_fee,
+    } = withdraw_reserve.calculate_liquidation(
+        liquidity_amount,
+        &obligation,
+        liquidity,
+        collateral,
+        &lending_market.liquidation_config(clock.slot),
+    )?;
+
+    if repay_amount == 0 {
+        msg!("Liquidation is too small to transfer liquidity");
+        return Err(LendingError::LiquidationTooSmall.into());
+    }
+    if withdraw_amount == 0 {
+        msg!("Liquidation is too small to receive collateral");
+        return Err(LendingError::LiquidationTooSmall.into());
+    }
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: repay_reserve_liquidity_supply_info.clone(),
+        amount: repay_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    if protocol_liquidation_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: withdraw_reserve_collateral_supply_info.clone(),
+            destination: withdraw_reserve_collateral_fee_receiver_info.clone(),
+            amount: protocol_liquidation_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    let collateral_amount = withdraw_amount
+        .checked_sub(protocol_liquidation_fee)
+        .ok_or(LendingError::MathOverflow)?;
+    if collateral_amount < min_collateral_amount {
+        msg!(
+            "Collateral amount received {} is less than the minimum {}",
+            collateral_amount,
+            min_collateral_amount
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
+    spl_token_transfer(TokenTransferParams {
+        source: withdraw_reserve_collateral_supply_info.clone(),
+        destination: destination_collateral_info.clone(),
+        amount: collateral_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(collateral_amount)
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_liquidate_obligation_and_redeem_reserve_collateral(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let collateral_amount = _liquidate_obligation(
+        program_id,
+        liquidity_amount,
+        0,
+        source_liquidity_info,
+        destination_collateral_info,
+        repay_reserve_info,
+        repay_reserve_liquidity_supply_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        withdraw_reserve_collateral_fee_receiver_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+
+    // the withdraw reserve is stale if it was also the repay reserve
+    _refresh_reserve_interest(program_id, withdraw_reserve_info, clock)?;
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    let redeem_amount = withdraw_reserve
+        .collateral_exchange_rate()?
+        .collateral_to_liquidity(collateral_amount)?;
+    if redeem_amount > withdraw_reserve.liquidity.available_amount {
+        msg!("Withdraw reserve does not have enough available liquidity to redeem the collateral");
+        return Err(LendingError::InsufficientLiquidity.into());
+    }
+
+    _redeem_reserve_collateral(
+        program_id,
+        collateral_amount,
+        min_liquidity_amount,
+        destination_collateral_info,
+        destination_liquidity_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_loan(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    receiver_instruction_prefix: &[u8],
+    apply_fee_exemptions: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
+
+    _flash_loan(
+        program_id,
+        &[liquidity_amount],
+        &[FlashLoanReserveAccounts {
+            source_liquidity_info,
+            destination_liquidity_info,
+            reserve_info,
+            reserve_liquidity_fee_receiver_info,
+            host_fee_receiver_info,
+        }],
+        lending_market_info,
+        lending_market_authority_info,
+        token_program_id,
+        flash_loan_receiver_program_id,
+        account_info_iter.as_slice(),
+        receiver_instruction_prefix,
+        apply_fee_exemptions,
+    )
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_loan_multiple(
+    program_id: &Pubkey,
+    liquidity_amounts: &[u64],
+    receiver_instruction_prefix: &[u8],
+    apply_fee_exemptions: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
+
+    let mut flash_loan_reserves = Vec::with_capacity(liquidity_amounts.len());
+    for _ in liquidity_amounts {
+        flash_loan_reserves.push(FlashLoanReserveAccounts {
+            source_liquidity_info: next_account_info(account_info_iter)?,
+            destination_liquidity_info: next_account_info(account_info_iter)?,
+            reserve_info: next_account_info(account_info_iter)?,
+            reserve_liquidity_fee_receiver_info: next_account_info(account_info_iter)?,
+            host_fee_receiver_info: next_account_info(account_info_iter)?,
+        });
+    }
+
+    _flash_loan(
+        program_id,
+        liquidity_amounts,
+        &flash_loan_reserves,
+        lending_market_info,
+        lending_market_authority_info,
+        token_program_id,
+        flash_loan_receiver_program_id,
+        account_info_iter.as_slice(),
+        receiver_instruction_prefix,
+        apply_fee_exemptions,
+    )
+}
+
+/// Lend each amount from its reserve, make a single call to the receiver program and check that
+/// every reserve was repaid with fees
+#[allow(clippy::too_many_arguments)]
+fn _flash_loan<'a>(
+    program_id: &Pubkey,
+    liquidity_amounts: &[u64],
+    flash_loan_reserves: &[FlashLoanReserveAccounts<'a, '_>],
+    lending_market_info: &AccountInfo<'a>,
+    lending_market_authority_info: &AccountInfo<'a>,
+    token_program_id: &AccountInfo<'a>,
+    flash_loan_receiver_program_id: &AccountInfo<'a>,
+    additional_account_infos: &[AccountInfo<'a>],
+    receiver_instruction_prefix: &[u8],
+    apply_fee_exemptions: bool,
+) -> ProgramResult {
+    if liquidity_amounts.is_empty() || liquidity_amounts.len() != flash_loan_reserves.len() {
+        msg!("A flash loan amount must be provided for each reserve");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if liquidity_amounts.contains(&0) {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    if program_id == flash_loan_receiver_program_id.key {
+        msg!("Lending program cannot be used as the flash loan receiver program provided");
+        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let (fee_discount, additional_account_infos) = flash_loan_fee_discount(
+        program_id,
+        lending_market_info,
+        flash_loan_receiver_program_id,
+        additional_account_infos,
+        apply_fee_exemptions,
+    )?;
+
+    let mut flash_loan_amounts = Vec::with_capacity(flash_loan_reserves.len());
+    let mut data =
+        Vec::with_capacity(receiver_instruction_prefix.len() + 8 * liquidity_amounts.len());
+    data.extend_from_slice(receiver_instruction_prefix);
+    for (index, (liquidity_amount, flash_loan_reserve)) in liquidity_amounts
+        .iter()
+        .zip(flash_loan_reserves)
+        .enumerate()
+    {
+        let FlashLoanReserveAccounts {
+            source_liquidity_info,
+            reserve_info,
+            reserve_liquidity_fee_receiver_info,
+            ..
+        } = flash_loan_reserve;
+
+        if flash_loan_reserves[..index]
+            .iter()
+            .any(|previous| previous.reserve_info.key == reserve_info.key)
+        {
+            msg!("Flash loan reserves must be unique");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+
+        let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        if reserve_info.owner != program_id {
+            msg!("Reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if &reserve.lending_market != lending_market_info.key {
+            msg!("Invalid reserve lending market account");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+            msg!("Reserve liquidity supply must be used as the source liquidity provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+            msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if reserve.status == ReserveStatus::Frozen {
+            msg!("Reserve is frozen");
+            return Err(LendingError::ReserveFrozen.into());
+        }
+
+        // if u64::MAX is flash loaned, fees are inclusive as with ordinary borrows so the receiver
+        // never has to return more than the reserve's available liquidity
+        let (flash_loan_amount, origination_fee, host_fee) = if *liquidity_amount == u64::MAX {
+            let available_amount = reserve.liquidity.available_amount;
+            let (origination_fee, host_fee) =
+                reserve.config.fees.calculate_discounted_flash_loan_fees(
+                    available_amount.into(),
+                    fee_discount,
+                    FeeCalculation::Inclusive,
+                )?;
+            let flash_loan_amount = available_amount
+                .checked_sub(origination_fee)
+                .ok_or(LendingError::MathOverflow)?;
+            (flash_loan_amount, origination_fee, host_fee)
+        } else {
+            let (origination_fee, host_fee) =
+                reserve.config.fees.calculate_discounted_flash_loan_fees(
+                    (*liquidity_amount).into(),
+                    fee_discount,
+                    FeeCalculation::Exclusive,
+                )?;
+            (*liquidity_amount, origination_fee, host_fee)
+        };
+
+        let returned_amount_required = flash_loan_amount
+            .checked_add(origination_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        data.extend_from_slice(&returned_amount_required.to_le_bytes());
+
+        let balance_before_flash_loan =
+            Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+        let expected_balance_after_flash_loan = balance_before_flash_loan
+            .checked_add(origination_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        flash_loan_amounts.push((
+            flash_loan_amount,
+            origination_fee,
+            host_fee,
+            expected_balance_after_flash_loan,
+        ));
+    }
+
+    let mut flash_loan_instruction_accounts = vec![];
+    let mut flash_loan_instruction_account_infos = vec![flash_loan_receiver_program_id.clone()];
+    for flash_loan_reserve in flash_loan_reserves {
+        flash_loan_instruction_accounts.push(AccountMeta::new(
+            *flash_loan_reserve.destination_liquidity_info.key,
+            false,
+        ));
+        flash_loan_instruction_accounts.push(AccountMeta::new(
+            *flash_loan_reserve.source_liquidity_info.key,
+            false,
+        ));
+        flash_loan_instruction_account_infos
+            .push(flash_loan_reserve.destination_liquidity_info.clone());
+        flash_loan_instruction_account_infos.push(flash_loan_reserve.source_liquidity_info.clone());
+    }
+    flash_loan_instruction_accounts.push(AccountMeta::new_readonly(*token_program_id.key, false));
+    flash_loan_instruction_account_infos.push(token_program_id.clone());
+    for account_info in additional_account_infos {
+        flash_loan_instruction_accounts.push(AccountMeta {
+            pubkey: *account_info.key,
+            is_signer: account_info.is_signer,
+            is_writable: account_info.is_writable,
+        });
+        flash_loan_instruction_account_infos.push(account_info.clone());
+    }
+
+    for (flash_loan_reserve, (flash_loan_amount, ..)) in
+        flash_loan_reserves.iter().zip(&flash_loan_amounts)
+    {
+        let mut reserve = Reserve::unpack(&flash_loan_reserve.reserve_info.data.borrow())?;
+        reserve
+            .liquidity
+            .borrow(Decimal::from(*flash_loan_amount))?;
+        Reserve::pack(
+            reserve,
+            &mut flash_loan_reserve.reserve_info.data.borrow_mut(),
+        )?;
+
+        spl_token_transfer(TokenTransferParams {
+            source: flash_loan_reserve.source_liquidity_info.clone(),
+            destination: flash_loan_reserve.destination_liquidity_info.clone(),
+            amount: *flash_loan_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    invoke(
+        &Instruction {
+            program_id: *flash_loan_receiver_program_id.key,
+            accounts: flash_loan_instruction_accounts,
+            data,
+        },
+        &flash_loan_instruction_account_infos[..],
+    )?;
+
+    for (
+        flash_loan_reserve,
+        (flash_loan_amount, origination_fee, host_fee, expected_balance_after_flash_loan),
+    ) in flash_loan_reserves.iter().zip(flash_loan_amounts)
+    {
+        let FlashLoanReserveAccounts {
+            source_liquidity_info,
+            reserve_info,
+            reserve_liquidity_fee_receiver_info,
+            host_fee_receiver_info,
+            ..
+        } = flash_loan_reserve;
+
+        let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        reserve
+            .liquidity
+            .repay(flash_loan_amount, Decimal::from(flash_loan_amount))?;
+        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+        let actual_balance_after_flash_loan =
+            Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+        if actual_balance_after_flash_loan < expected_balance_after_flash_loan {
+            msg!("Insufficient reserve liquidity after flash loan");
+            return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
+        }
+
+        let mut owner_fee = origination_fee;
+        if host_fee > 0 {
+            owner_fee = owner_fee
+                .checked_sub(host_fee)
+                .ok_or(LendingError::MathOverflow)?;
+            spl_token_transfer(TokenTransferParams {
+                source: (*source_liquidity_info).clone(),
+                destination: (*host_fee_receiver_info).clone(),
+                amount: host_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+
+        if owner_fee > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: (*source_liquidity_info).clone(),
+                destination: (*reserve_liquidity_fee_receiver_info).clone(),
+                amount: owner_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fee discount for the flash loan receiver program or any signer, if fee exemptions are applied
+/// and the lending market's flash loan fee exemptions are the first additional account. The
+/// remaining accounts are returned for the receiver program.
+fn flash_loan_fee_discount<'a, 'b>(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo<'a>,
+    flash_loan_receiver_program_id: &AccountInfo<'a>,
+    additional_account_infos: &'b [AccountInfo<'a>],
+    apply_fee_exemptions: bool,
+) -> Result<(u8, &'b [AccountInfo<'a>]), ProgramError> {
+    let (flash_loan_fee_exemptions_info, receiver_account_infos) = if apply_fee_exemptions {
+        additional_account_infos
+            .split_first()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?
+    } else {
+        assert_no_flash_loan_fee_exemptions(program_id, additional_account_infos)?;
+        return Ok((0, additional_account_infos));
+    };
+    assert_no_flash_loan_fee_exemptions(program_id, receiver_account_infos)?;
+
+    if flash_loan_fee_exemptions_info.owner != program_id {
+        msg!("Flash loan fee exemptions provided are not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let flash_loan_fee_exemptions =
+        FlashLoanFeeExemptions::unpack(&flash_loan_fee_exemptions_info.data.borrow())?;
+    if &flash_loan_fee_exemptions.lending_market != lending_market_info.key {
+        msg!("Flash loan fee exemptions lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let flash_loan_fee_exemptions_pubkey = Pubkey::create_program_address(
+        &[
+            lending_market_info.key.as_ref(),
+            FLASH_LOAN_FEE_EXEMPTIONS_SEED,
+            &[flash_loan_fee_exemptions.bump_seed],
+        ],
+        program_id,
+    )?;
+    if &flash_loan_fee_exemptions_pubkey != flash_loan_fee_exemptions_info.key {
+        msg!("Flash loan fee exemptions provided do not match the derived flash loan fee exemptions address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let fee_discount = flash_loan_fee_exemptions.fee_discount(
+        iter::once(flash_loan_receiver_program_id.key).chain(
+            receiver_account_infos
+                .iter()
+                .filter(|account_info| account_info.is_signer)
+                .map(|account_info| account_info.key),
+        ),
+    );
+    Ok((fee_discount, receiver_account_infos))
+}
+
+/// Reject flash loan fee exemptions passed outside of their account slot, which would otherwise
+/// be forwarded to the receiver program without applying a discount
+fn assert_no_flash_loan_fee_exemptions(
+    program_id: &Pubkey,
+    account_infos: &[AccountInfo],
+) -> ProgramResult {
+    if account_infos.iter().any(|account_info| {
+        account_info.owner == program_id && account_info.data_len() == FlashLoanFeeExemptions::LEN
+    }) {
+        msg!("Flash loan fee exemptions must be provided in the fee exemptions account slot with fee exemptions applied");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_borrow_reserve_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let instructions_sysvar_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.status == ReserveStatus::Frozen {
+        msg!("Reserve is frozen");
+        return Err(LendingError::ReserveFrozen.into());
+    }
+
+    if instructions_sysvar_info.key != &sysvar::instructions::id() {
+        msg!("Instructions sysvar provided does not match the instructions sysvar id");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let instructions_data = instructions_sysvar_info.data.borrow();
+    let current_index = load_current_index(&instructions_data) as usize;
+    let current_instruction = load_instruction_at(current_index, &instructions_data)
+        .map_err(|_| LendingError::InvalidAccountInput)?;
+    if &current_instruction.program_id != program_id {
+        msg!("Flash borrow cannot be called via CPI");
+        return Err(LendingError::FlashLoanCpi.into());
+    }
+
+    // the first flash repay referencing this instruction must match it, must not be preceded by
+    // another flash borrow or flash repay, and must be the only one
+    let mut found_repay = false;
+    let mut index = current_index + 1;
+    while let Ok(instruction) = load_instruction_at(index, &instructions_data) {
+        index += 1;
+        if &instruction.program_id != program_id {
+            continue;
+        }
+        match LendingInstruction::unpack(&instruction.data)? {
+            LendingInstruction::FlashBorrowReserveLiquidity { .. } if !found_repay => {
+                msg!("Flash borrow cannot be nested inside another flash borrow");
+                return Err(LendingError::NestedFlashBorrow.into());
+            }
+            LendingInstruction::FlashRepayReserveLiquidity {
+                liquidity_amount: repay_liquidity_amount,
+                borrow_instruction_index,
+            } => {
+                if borrow_instruction_index as usize != current_index {
+                    if !found_repay {
+                        msg!("Flash repay for another flash borrow found before the matching flash repay");
+                        return Err(LendingError::InvalidFlashRepay.into());
+                    }
+                    continue;
+                }
+                if found_repay {
+                    msg!("Flash borrow can only be repaid once");
+                    return Err(LendingError::InvalidFlashRepay.into());
+                }
+                if repay_liquidity_amount != liquidity_amount {
+                    msg!("Flash repay amount does not match the flash borrow amount");
+                    return Err(LendingError::InvalidFlashRepay.into());
+                }
+                // reserve is the fifth account of a flash repay
+                if instruction.accounts.get(4).map(|meta| &meta.pubkey) != Some(reserve_info.key) {
+                    msg!("Flash repay reserve does not match the flash borrow reserve");
+                    return Err(LendingError::InvalidFlashRepay.into());
+                }
+                found_repay = true;
+            }
+            _ => {}
+        }
+    }
+    if !found_repay {
+        msg!("Flash borrow must be followed by a matching flash repay");
+        return Err(LendingError::InvalidFlashBorrow.into());
+    }
+
+    reserve.liquidity.borrow(Decimal::from(liquidity_amount))?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: liquidity_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_repay_reserve_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    borrow_instruction_index: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let instructions_sysvar_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != destination_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if instructions_sysvar_info.key != &sysvar::instructions::id() {
+        msg!("Instructions sysvar provided does not match the instructions sysvar id");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let instructions_data = instructions_sysvar_info.data.borrow();
+    let current_index = load_current_index(&instructions_data) as usize;
+    let current_instruction = load_instruction_at(current_index, &instructions_data)
+        .map_err(|_| LendingError::InvalidAccountInput)?;
+    if &current_instruction.program_id != program_id {
+        msg!("Flash repay cannot be called via CPI");
+        return Err(LendingError::FlashLoanCpi.into());
+    }
+    if borrow_instruction_index as usize >= current_index {
+        msg!("Flash borrow instruction must come before the flash repay");
+        return Err(LendingError::InvalidFlashRepay.into());
+    }
+    let borrow_instruction =
+        load_instruction_at(borrow_instruction_index as usize, &instructions_data)
+            .map_err(|_| LendingError::InvalidFlashRepay)?;
+    if &borrow_instruction.program_id != program_id {
+        msg!("Flash borrow instruction is not a lending program instruction");
+        return Err(LendingError::InvalidFlashRepay.into());
+    }
+    match LendingInstruction::unpack(&borrow_instruction.data)? {
+        LendingInstruction::FlashBorrowReserveLiquidity {
+            liquidity_amount: borrow_liquidity_amount,
+        } if borrow_liquidity_amount == liquidity_amount => {}
+        _ => {
+            msg!("Flash repay does not match the flash borrow instruction");
+            return Err(LendingError::InvalidFlashRepay.into());
+        }
+    }
+    // reserve is the third account of a flash borrow
+    if borrow_instruction.accounts.get(2).map(|meta| &meta.pubkey) != Some(reserve_info.key) {
+        msg!("Flash repay reserve does not match the flash borrow reserve");
+        return Err(LendingError::InvalidFlashRepay.into());
+    }
+
+    let flash_loan_amount_decimal = Decimal::from(liquidity_amount);
+    let (origination_fee, host_fee) = reserve
+        .config
+        .fees
+        .calculate_flash_loan_fees(flash_loan_amount_decimal, FeeCalculation::Exclusive)?;
+
+    reserve
+        .liquidity
+        .repay(liquidity_amount, flash_loan_amount_decimal)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: liquidity_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    let mut owner_fee = origination_fee;
+    if host_fee > 0 {
+        owner_fee = owner_fee
+            .checked_sub(host_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: host_fee_receiver_info.clone(),
+            amount: host_fee,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_obligation_collateral_and_redeem_reserve_liquidity(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    min_liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_collateral_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let obligation_delegation_info = next_account_info(account_info_iter).ok();
+
+    let liquidity_amount = _withdraw_obligation_collateral(
+        program_id,
+        collateral_amount,
+        reserve_collateral_info,
+        user_collateral_info,
+        reserve_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        obligation_delegation_info,
+        clock,
+        token_program_id,
+    )?;
+
+    _redeem_reserve_collateral(
+        program_id,
+        liquidity_amount,
+        min_liquidity_amount,
+        user_collateral_info,
+        user_liquidity_info,
+        reserve_info,
+        reserve_collateral_mint_info,
+        reserve_liquidity_supply_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_deposit_reserve_liquidity_and_borrow_obligation_liquidity(
+    program_id: &Pubkey,
+    deposit_liquidity_amount: u64,
+    borrow_liquidity_amount: u64,
+    min_receive_amount: u64,
+    max_borrow_amount_including_fees: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if deposit_liquidity_amount == 0 {
+        msg!("Deposit liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if borrow_liquidity_amount == 0 {
+        msg!("Borrow liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+    let reserve_infos = account_info_iter.as_slice();
+
+    // the deposit reserve must already be refreshed with its oracle price, so only its interest
+    // is refreshed once the deposit marks it stale
+    let collateral_amount = _deposit_reserve_liquidity(
+        program_id,
+        deposit_liquidity_amount,
+        source_liquidity_info,
+        user_collateral_info,
+        deposit_reserve_info,
+        deposit_reserve_liquidity_supply_info,
+        deposit_reserve_collateral_mint_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        Some(lending_market_membership_info),
+        clock,
+        token_program_id,
+    )?;
+    _refresh_reserve_interest(program_id, deposit_reserve_info, clock)?;
+    _deposit_obligation_collateral(
+        program_id,
+        collateral_amount,
+        user_collateral_info,
+        deposit_reserve_collateral_supply_info,
+        deposit_reserve_info,
+        obligation_info,
+        lending_market_info,
+        obligation_owner_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+
+    _refresh_obligation(program_id, obligation_info, reserve_infos, clock)?;
+    _borrow_obligation_liquidity(
+        program_id,
+        borrow_liquidity_amount,
+        min_receive_amount,
+        max_borrow_amount_including_fees,
+        borrow_reserve_liquidity_supply_info,
+        destination_liquidity_info,
+        borrow_reserve_info,
+        borrow_reserve_liquidity_fee_receiver_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        None,
+        None,
+        clock,
+        token_program_id,
+        None,
+    )?;
+
+    // mark the deposit reserve as stale to make sure no weird bugs happen
+    let mut deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    deposit_reserve.last_update.mark_stale();
+    Reserve::pack(deposit_reserve, &mut deposit_reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_repay_obligation_liquidity_and_withdraw_obligation_collateral(
+    program_id: &Pubkey,
+    repay_liquidity_amount: u64,
+    withdraw_collateral_amount: u64,
+    min_liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if repay_liquidity_amount == 0 {
+        msg!("Repay liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if withdraw_collateral_amount == 0 {
+        msg!("Withdraw collateral amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let reserve_infos = account_info_iter.as_slice();
+
+    // repaying only refreshes the repay reserve interest, so its oracle price must already be
+    // refreshed before the obligation is valued with it
+    let repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Repay reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    _repay_obligation_liquidity(
+        program_id,
+        repay_liquidity_amount,
+        source_liquidity_info,
+        repay_reserve_liquidity_supply_info,
+        repay_reserve_info,
+        obligation_info,
+        lending_market_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+    _refresh_reserve_interest(program_id, repay_reserve_info, clock)?;
+
+    _refresh_obligation(program_id, obligation_info, reserve_infos, clock)?;
+    let collateral_amount = _withdraw_obligation_collateral(
+        program_id,
+        withdraw_collateral_amount,
+        withdraw_reserve_collateral_supply_info,
+        user_collateral_info,
+        withdraw_reserve_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        None,
+        clock,
+        token_program_id,
+    )?;
+    _redeem_reserve_collateral(
+        program_id,
+        collateral_amount,
+        min_liquidity_amount,
+        user_collateral_info,
+        destination_liquidity_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+
+    // mark the repay reserve as stale to make sure no weird bugs happen
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_batch(program_id: &Pubkey, ops: &[BatchOp], accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+
+    let reserve_account_chunks = account_info_iter.as_slice().chunks_exact(8);
+    if !reserve_account_chunks.remainder().is_empty() {
+        msg!("Batch reserve accounts must be provided in groups of 8");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let mut batch_reserves: Vec<BatchReserveAccounts> = Vec::new();
+    for reserve_accounts in reserve_account_chunks {
+        if batch_reserves
+            .iter()
+            .any(|batch_reserve| batch_reserve.reserve_info.key == reserve_accounts[0].key)
+        {
+            msg!("Batch reserve accounts cannot be provided more than once");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        batch_reserves.push(BatchReserveAccounts {
+            reserve_info: &reserve_accounts[0],
+            pyth_price_info: &reserve_accounts[1],
+            switchboard_feed_info: &reserve_accounts[2],
+            reserve_liquidity_supply_info: &reserve_accounts[3],
+            reserve_liquidity_fee_receiver_info: &reserve_accounts[4],
+            reserve_collateral_mint_info: &reserve_accounts[5],
+            reserve_collateral_supply_info: &reserve_accounts[6],
+            user_liquidity_info: &reserve_accounts[7],
+        });
+    }
+    if ops
+        .iter()
+        .any(|op| op.reserve_index() as usize >= batch_reserves.len())
+    {
+        msg!("Batch operation reserve index is out of range of the reserves provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if ops.iter().any(|op| matches!(op, BatchOp::Deposit { .. })) {
+        assert_lending_market_member(
+            program_id,
+            &lending_market,
+            lending_market_info,
+            user_transfer_authority_info.key,
+            Some(lending_market_membership_info),
+        )?;
+    }
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let mut reserves = Vec::with_capacity(batch_reserves.len());
+    for batch_reserve in &batch_reserves {
+        _refresh_reserve(
+            program_id,
+            batch_reserve.reserve_info,
+            batch_reserve.pyth_price_info,
+            batch_reserve.switchboard_feed_info,
+            clock,
+        )?;
+        let reserve = Reserve::unpack(&batch_reserve.reserve_info.data.borrow())?;
+        if &reserve.lending_market != lending_market_info.key {
+            msg!("Reserve lending market does not match the lending market provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.liquidity.supply_pubkey != batch_reserve.reserve_liquidity_supply_info.key {
+            msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.config.fee_receiver != batch_reserve.reserve_liquidity_fee_receiver_info.key {
+            msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.collateral.mint_pubkey != batch_reserve.reserve_collateral_mint_info.key {
+            msg!("Reserve collateral mint does not match the reserve collateral mint provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.collateral.supply_pubkey != batch_reserve.reserve_collateral_supply_info.key {
+            msg!("Reserve collateral supply does not match the reserve collateral supply provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.liquidity.supply_pubkey == batch_reserve.user_liquidity_info.key {
+            msg!("Reserve liquidity supply cannot be used as the user liquidity provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        reserves.push(reserve);
+    }
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    _refresh_obligation(
+        program_id,
+        obligation_info,
+        &batch_obligation_reserve_infos(&obligation, &batch_reserves)?,
+        clock,
+    )?;
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut check_health = false;
+    for op in ops {
+        let batch_reserve = &batch_reserves[op.reserve_index() as usize];
+        let reserve = &mut reserves[op.reserve_index() as usize];
+        match *op {
+            BatchOp::Deposit {
+                liquidity_amount, ..
+            } => {
+                if liquidity_amount == 0 {
+                    msg!("Liquidity amount provided cannot be zero");
+                    return Err(LendingError::InvalidAmount.into());
+                }
+                if reserve.status != ReserveStatus::Active {
+                    msg!("Reserve is not active and does not accept deposits");
+                    return Err(LendingError::ReserveNotActive.into());
+                }
+                if reserve.loan_to_value_ratio(clock.slot) == 0 {
+                    msg!("Reserve has collateral disabled for borrowing");
+                    return Err(LendingError::ReserveCollateralDisabled.into());
+                }
+                if Decimal::from(liquidity_amount)
+                    .try_add(reserve.liquidity.total_supply()?)?
+                    .try_floor_u64()?
+                    > reserve.config.deposit_limit
+                {
+                    msg!("Cannot deposit liquidity above the reserve deposit limit");
+                    return Err(LendingError::InvalidAmount.into());
+                }
+
+                let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
+                obligation
+                    .find_or_add_collateral_to_deposits(*batch_reserve.reserve_info.key)?
+                    .deposit(collateral_amount)?;
+
+                spl_token_transfer(TokenTransferParams {
+                    source: batch_reserve.user_liquidity_info.clone(),
+                    destination: batch_reserve.reserve_liquidity_supply_info.clone(),
+                    amount: liquidity_amount,
+                    authority: user_transfer_authority_info.clone(),
+                    authority_signer_seeds: &[],
+                    token_program: token_program_id.clone(),
+                })?;
+                spl_token_mint_to(TokenMintToParams {
+                    mint: batch_reserve.reserve_collateral_mint_info.clone(),
+                    destination: batch_reserve.reserve_collateral_supply_info.clone(),
+                    amount: collateral_amount,
+                    authority: lending_market_authority_info.clone(),
+                    authority_signer_seeds,
+                    token_program: token_program_id.clone(),
+                })?;
+            }
+            BatchOp::Withdraw {
+                collateral_amount, ..
+            } => {
+                if collateral_amount == 0 {
+                    msg!("Collateral amount provided cannot be zero");
+                    return Err(LendingError::InvalidAmount.into());
+                }
+                if reserve.status == ReserveStatus::Frozen {
+                    msg!("Reserve is frozen");
+                    return Err(LendingError::ReserveFrozen.into());
+                }
+
+                let (collateral, collateral_index) =
+                    obligation.find_collateral_in_deposits(*batch_reserve.reserve_info.key)?;
+                if collateral.deposited_amount == 0 {
+                    msg!("Collateral deposited amount is zero");
+                    return Err(LendingError::ObligationCollateralEmpty.into());
+                }
+                let withdraw_amount = collateral_amount.min(collateral.deposited_amount);
+                obligation.withdraw(withdraw_amount, collateral_index)?;
+                let liquidity_amount = reserve.redeem_collateral(withdraw_amount)?;
+                check_health = true;
+
+                spl_token_burn(TokenBurnParams {
+                    mint: batch_reserve.reserve_collateral_mint_info.clone(),
+                    source: batch_reserve.reserve_collateral_supply_info.clone(),
+                    amount: withdraw_amount,
+                    authority: lending_market_authority_info.clone(),
+                    authority_signer_seeds,
+                    token_program: token_program_id.clone(),
+                })?;
+                spl_token_transfer(TokenTransferParams {
+                    source: batch_reserve.reserve_liquidity_supply_info.clone(),
+                    destination: batch_reserve.user_liquidity_info.clone(),
+                    amount: liquidity_amount,
+                    authority: lending_market_authority_info.clone(),
+                    authority_signer_seeds,
+                    token_program: token_program_id.clone(),
+                })?;
+            }
+            BatchOp::Borrow {
+                liquidity_amount, ..
+            } => {
+                if liquidity_amount == 0 || liquidity_amount == u64::MAX {
+                    msg!("Liquidity amount provided must be an exact non-zero amount");
+                    return Err(LendingError::InvalidAmount.into());
+                }
+                if reserve.status != ReserveStatus::Active {
+                    msg!("Reserve is not active and does not accept borrows");
+                    return Err(LendingError::ReserveNotActive.into());
+                }
+
+                let (borrow_fee, _host_fee) = reserve
+                    .config
+                    .fees
+                    .calculate_borrow_fees(liquidity_amount.into(), FeeCalculation::Exclusive)?;
+                let borrow_amount = Decimal::from(liquidity_amount).try_add(borrow_fee.into())?;
+                if borrow_amount
+                    .try_add(reserve.liquidity.borrowed_amount_wads)?
+                    .try_floor_u64()?
+                    > reserve.config.borrow_limit
+                {
+                    msg!("Cannot borrow above the borrow limit");
+                    return Err(LendingError::InvalidAmount.into());
+                }
+
+                let cumulative_borrow_rate_wads = reserve.liquidity.cumulative_borrow_rate_wads;
+                reserve.liquidity.borrow(borrow_amount)?;
+                obligation
+                    .find_or_add_liquidity_to_borrows(
+                        *batch_reserve.reserve_info.key,
+                        cumulative_borrow_rate_wads,
+                    )?
+                    .borrow(borrow_amount)?;
+                check_health = true;
+
+                if borrow_fee > 0 {
+                    spl_token_transfer(TokenTransferParams {
+                        source: batch_reserve.reserve_liquidity_supply_info.clone(),
+                        destination: batch_reserve.reserve_liquidity_fee_receiver_info.clone(),
+                        amount: borrow_fee,
+                        authority: lending_market_authority_info.clone(),
+                        authority_signer_seeds,
+                        token_program: token_program_id.clone(),
+                    })?;
+                }
+                spl_token_transfer(TokenTransferParams {
+                    source: batch_reserve.reserve_liquidity_supply_info.clone(),
+                    destination: batch_reserve.user_liquidity_info.clone(),
+                    amount: liquidity_amount,
+                    authority: lending_market_authority_info.clone(),
+                    authority_signer_seeds,
+                    token_program: token_program_id.clone(),
+                })?;
+            }
+            BatchOp::Repay {
+                liquidity_amount, ..
+            } => {
+                if liquidity_amount == 0 {
+                    msg!("Liquidity amount provided cannot be zero");
+                    return Err(LendingError::InvalidAmount.into());
+                }
+
+                let (liquidity, liquidity_index) =
+                    obligation.find_liquidity_in_borrows(*batch_reserve.reserve_info.key)?;
+                if liquidity.borrowed_amount_wads == Decimal::zero() {
+                    msg!("Liquidity borrowed amount is zero");
+                    return Err(LendingError::ObligationLiquidityEmpty.into());
+                }
+                let CalculateRepayResult {
+                    settle_amount,
+                    repay_amount,
+                } = reserve.calculate_repay(liquidity_amount, liquidity.borrowed_amount_wads)?;
+                if repay_amount == 0 {
+                    msg!("Repay amount is too small to transfer liquidity");
+                    return Err(LendingError::RepayTooSmall.into());
+                }
+                reserve.liquidity.repay(repay_amount, settle_amount)?;
+                obligation.repay(settle_amount, liquidity_index)?;
+
+                spl_token_transfer(TokenTransferParams {
+                    source: batch_reserve.user_liquidity_info.clone(),
+                    destination: batch_reserve.reserve_liquidity_supply_info.clone(),
+                    amount: repay_amount,
+                    authority: user_transfer_authority_info.clone(),
+                    authority_signer_seeds: &[],
+                    token_program: token_program_id.clone(),
+                })?;
+            }
+        }
+    }
+
+    for (batch_reserve, reserve) in batch_reserves.iter().zip(reserves) {
+        Reserve::pack(reserve, &mut batch_reserve.reserve_info.data.borrow_mut())?;
+    }
+    let obligation_reserve_infos = batch_obligation_reserve_infos(&obligation, &batch_reserves)?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+    _refresh_obligation(
+        program_id,
+        obligation_info,
+        &obligation_reserve_infos,
+        clock,
+    )?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if check_health && obligation.borrowed_value > obligation.allowed_borrow_value {
+        msg!(
+            "Obligation borrowed value {} exceeds its allowed borrow value {} once all operations have run",
+            obligation.borrowed_value,
+            obligation.allowed_borrow_value
+        );
+        return Err(LendingError::ObligationUnhealthy.into());
+    }
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    // mark the reserves that were operated on as stale to make sure no weird bugs happen
+    for (index, batch_reserve) in batch_reserves.iter().enumerate() {
+        if ops.iter().any(|op| op.reserve_index() as usize == index) {
+            let mut reserve = Reserve::unpack(&batch_reserve.reserve_info.data.borrow())?;
+            reserve.last_update.mark_stale();
+            Reserve::pack(reserve, &mut batch_reserve.reserve_info.data.borrow_mut())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reserve accounts of the obligation deposits followed by its borrows, as for RefreshObligation
+fn batch_obligation_reserve_infos<'a>(
+    obligation: &Obligation,
+    batch_reserves: &[BatchReserveAccounts<'a, '_>],
+) -> Result<Vec<AccountInfo<'a>>, ProgramError> {
+    obligation
+        .deposits
+        .iter()
+        .map(|collateral| collateral.deposit_reserve)
+        .chain(
+            obligation
+                .borrows
+                .iter()
+                .map(|liquidity| liquidity.borrow_reserve),
+        )
+        .map(|reserve_pubkey| {
+            batch_reserves
+                .iter()
+                .find(|batch_reserve| batch_reserve.reserve_info.key == &reserve_pubkey)
+                .map(|batch_reserve| batch_reserve.reserve_info.clone())
+                .ok_or_else(|| {
+                    msg!(
+                        "Obligation reserve {} must be provided to the batch",
+                        reserve_pubkey
+                    );
+                    LendingError::InvalidAccountInput.into()
+                })
+        })
+        .collect()
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_update_reserve_config(
+    program_id: &Pubkey,
+    config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    validate_reserve_config(config)?;
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let pyth_product_info = next_account_info(account_info_iter)?;
+    let pyth_price_info = next_account_info(account_info_iter)?;
+    let switchboard_feed_info = next_account_info(account_info_iter)?;
+    let collateral_fee_receiver_info = next_account_info(account_info_iter).ok();
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!(
+            "Reserve provided is not owned by the lending program {} != {}",
+            &reserve_info.owner.to_string(),
+            &program_id.to_string(),
+        );
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!(
+            "Lending market provided is not owned by the lending program  {} != {}",
+            &lending_market_info.owner.to_string(),
+            &program_id.to_string(),
+        );
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let clock = Clock::get()?;
+    if lending_market.reserve_config_delay(clock.slot) > 0
+        && (*pyth_price_info.key != reserve.liquidity.pyth_oracle_pubkey
+            || *switchboard_feed_info.key != reserve.liquidity.switchboard_oracle_pubkey
+            || !reserve.config.is_risk_reducing(&config))
+    {
+        msg!("Reserve config changes which increase risk must be queued");
+        return Err(LendingError::ReserveConfigTimelocked.into());
+    }
+
+    if *pyth_price_info.key != reserve.liquidity.pyth_oracle_pubkey {
+        validate_pyth_keys(&lending_market, pyth_product_info, pyth_price_info)?;
+        reserve.liquidity.pyth_oracle_pubkey = *pyth_price_info.key;
+    }
+
+    if *switchboard_feed_info.key != reserve.liquidity.switchboard_oracle_pubkey {
+        validate_switchboard_keys(&lending_market, switchboard_feed_info)?;
+        reserve.liquidity.switchboard_oracle_pubkey = *switchboard_feed_info.key;
+    }
+    if reserve.liquidity.switchboard_oracle_pubkey == spl_token_lending::NULL_PUBKEY
+        && (*pyth_price_info.key == spl_token_lending::NULL_PUBKEY
+            || *pyth_product_info.key == spl_token_lending::NULL_PUBKEY)
+    {
+        msg!("At least one price oracle must have a non-null pubkey");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    validate_collateral_fee_receiver(
+        &lending_market,
+        &reserve,
+        &config,
+        collateral_fee_receiver_info,
+    )?;
+
+    reserve.update_config(
+        config,
+        &lending_market.reserve_config_bounds(clock.slot),
+        clock.slot,
+    )?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_reserve_config_delay(
+    program_id: &Pubkey,
+    delay_slots: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.set_reserve_config_delay(delay_slots, clock.slot)?;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_reserve_config_bounds(
+    program_id: &Pubkey,
+    bounds: ReserveConfigBounds,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let clock = Clock::get()?;
+    lending_market.set_reserve_config_bounds(bounds, clock.slot)?;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_queue_reserve_config(
+    program_id: &Pubkey,
+    config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    validate_reserve_config(config)?;
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let pyth_product_info = next_account_info(account_info_iter)?;
+    let pyth_price_info = next_account_info(account_info_iter)?;
+    let switchboard_feed_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let collateral_fee_receiver_info = next_account_info(account_info_iter).ok();
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if *pyth_price_info.key != reserve.liquidity.pyth_oracle_pubkey {
+        validate_pyth_keys(&lending_market, pyth_product_info, pyth_price_info)?;
+    }
+    if *switchboard_feed_info.key != reserve.liquidity.switchboard_oracle_pubkey {
+        validate_switchboard_keys(&lending_market, switchboard_feed_info)?;
+    }
+    if *switchboard_feed_info.key == spl_token_lending::NULL_PUBKEY
+        && (*pyth_price_info.key == spl_token_lending::NULL_PUBKEY
+            || *pyth_product_info.key == spl_token_lending::NULL_PUBKEY)
+    {
+        msg!("At least one price oracle must have a non-null pubkey");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    validate_collateral_fee_receiver(
+        &lending_market,
+        &reserve,
+        &config,
+        collateral_fee_receiver_info,
+    )?;
+
+    let (pending_reserve_config_pubkey, bump_seed) =
+        find_pending_reserve_config_address(program_id, reserve_info.key);
+    if &pending_reserve_config_pubkey != pending_reserve_config_info.key {
+        msg!("Pending reserve config provided does not match the derived pending reserve config address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if pending_reserve_config_info.data_is_empty() {
+        create_program_derived_account(CreateProgramDerivedAccountParams {
+            payer: lending_market_owner_info.clone(),
+            account: pending_reserve_config_info.clone(),
+            space: PendingReserveConfig::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: &[
+                reserve_info.key.as_ref(),
+                PENDING_RESERVE_CONFIG_SEED,
+                &[bump_seed],
+            ],
+            system_program: system_program_info.clone(),
+        })?;
+    } else if pending_reserve_config_info.owner != program_id {
+        msg!("Pending reserve config provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let earliest_apply_slot = clock
+        .slot
+        .checked_add(lending_market.reserve_config_delay(clock.slot))
+        .ok_or(LendingError::MathOverflow)?;
+    // the config is checked against the bounds again when it is applied
+    lending_market
+        .reserve_config_bounds(earliest_apply_slot)
+        .validate_change(
+            &reserve.config,
+            &config,
+            reserve.last_config_update_slot,
+            earliest_apply_slot,
+        )?;
+    let pending_reserve_config = PendingReserveConfig::new(InitPendingReserveConfigParams {
+        reserve: *reserve_info.key,
+        earliest_apply_slot,
+        pyth_oracle_pubkey: *pyth_price_info.key,
+        switchboard_oracle_pubkey: *switchboard_feed_info.key,
+        config,
+    });
+    PendingReserveConfig::pack(
+        pending_reserve_config,
+        &mut pending_reserve_config_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_apply_reserve_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+
+    let pending_reserve_config =
+        assert_pending_reserve_config(program_id, reserve_info, pending_reserve_config_info)?;
+
+    // risk reducing changes are checked against the current config, which may have been
+    // updated since the change was queued
+    let is_risk_reducing = pending_reserve_config.pyth_oracle_pubkey
+        == reserve.liquidity.pyth_oracle_pubkey
+        && pending_reserve_config.switchboard_oracle_pubkey
+            == reserve.liquidity.switchboard_oracle_pubkey
+        && reserve
+            .config
+            .is_risk_reducing(&pending_reserve_config.config);
+    if !pending_reserve_config.can_apply(clock.slot) && !is_risk_reducing {
+        msg!(
+            "Pending reserve config cannot be applied until slot {}",
+            pending_reserve_config.earliest_apply_slot
+        );
+        return Err(LendingError::ReserveConfigDelayNotElapsed.into());
+    }
+
+    reserve.liquidity.pyth_oracle_pubkey = pending_reserve_config.pyth_oracle_pubkey;
+    reserve.liquidity.switchboard_oracle_pubkey = pending_reserve_config.switchboard_oracle_pubkey;
+    reserve.update_config(
+        pending_reserve_config.config,
+        &lending_market.reserve_config_bounds(clock.slot),
+        clock.slot,
+    )?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    PendingReserveConfig::pack(
+        PendingReserveConfig::default(),
+        &mut pending_reserve_config_info.data.borrow_mut(),
+    )?;
+    let lamports = pending_reserve_config_info.lamports();
+    **pending_reserve_config_info.lamports.borrow_mut() = 0;
+    **lending_market_owner_info.lamports.borrow_mut() = lending_market_owner_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_cancel_reserve_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    assert_pending_reserve_config(program_id, reserve_info, pending_reserve_config_info)?;
+
+    PendingReserveConfig::pack(
+        PendingReserveConfig::default(),
+        &mut pending_reserve_config_info.data.borrow_mut(),
+    )?;
+    let lamports = pending_reserve_config_info.lamports();
+    **pending_reserve_config_info.lamports.borrow_mut() = 0;
+    **lending_market_owner_info.lamports.borrow_mut() = lending_market_owner_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_lending_market_permissioned(
+    program_id: &Pubkey,
+    permissioned: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.permissioned = permissioned;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_add_lending_market_member(
+    program_id: &Pubkey,
+    member: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let (lending_market_membership_pubkey, bump_seed) =
+        find_lending_market_membership_address(program_id, lending_market_info.key, &member);
+    if &lending_market_membership_pubkey != lending_market_membership_info.key {
+        msg!("Lending market membership provided does not match the derived lending market membership address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if lending_market_membership_info.data_is_empty() {
+        create_program_derived_account(CreateProgramDerivedAccountParams {
+            payer: lending_market_owner_info.clone(),
+            account: lending_market_membership_info.clone(),
+            space: LendingMarketMembership::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: &[
+                lending_market_info.key.as_ref(),
+                member.as_ref(),
+                LENDING_MARKET_MEMBERSHIP_SEED,
+                &[bump_seed],
+            ],
+            system_program: system_program_info.clone(),
+        })?;
+    } else if lending_market_membership_info.owner != program_id {
+        msg!("Lending market membership provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_rent_exempt(rent, lending_market_membership_info)?;
+    let mut lending_market_membership =
+        assert_uninitialized::<LendingMarketMembership>(lending_market_membership_info)?;
+
+    lending_market_membership.init(InitLendingMarketMembershipParams {
+        lending_market: *lending_market_info.key,
+        member,
+    });
+    LendingMarketMembership::pack(
+        lending_market_membership,
+        &mut lending_market_membership_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_remove_lending_market_member(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_membership_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if lending_market_membership_info.owner != program_id {
+        msg!("Lending market membership provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let lending_market_membership =
+        LendingMarketMembership::unpack(&lending_market_membership_info.data.borrow())?;
+    if &lending_market_membership.lending_market != lending_market_info.key {
+        msg!("Lending market membership lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    LendingMarketMembership::pack(
+        LendingMarketMembership::default(),
+        &mut lending_market_membership_info.data.borrow_mut(),
+    )?;
+    let lamports = lending_market_membership_info.lamports();
+    **lending_market_membership_info.lamports.borrow_mut() = 0;
+    **lending_market_owner_info.lamports.borrow_mut() = lending_market_owner_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_add_swap_program(
+    program_id: &Pubkey,
+    swap_program_id: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let swap_program_approval_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &swap_program_id == program_id {
+        msg!("Lending program cannot be approved as a swap program");
+        return Err(LendingError::InvalidSwapProgram.into());
+    }
+
+    let (swap_program_approval_pubkey, bump_seed) =
+        find_swap_program_approval_address(program_id, lending_market_info.key, &swap_program_id);
+    if &swap_program_approval_pubkey != swap_program_approval_info.key {
+        msg!("Swap program approval provided does not match the derived swap program approval address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if swap_program_approval_info.data_is_empty() {
+        create_program_derived_account(CreateProgramDerivedAccountParams {
+            payer: lending_market_owner_info.clone(),
+            account: swap_program_approval_info.clone(),
+            space: SwapProgramApproval::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: &[
+                lending_market_info.key.as_ref(),
+                swap_program_id.as_ref(),
+                SWAP_PROGRAM_APPROVAL_SEED,
+                &[bump_seed],
+            ],
+            system_program: system_program_info.clone(),
+        })?;
+    } else if swap_program_approval_info.owner != program_id {
+        msg!("Swap program approval provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_rent_exempt(rent, swap_program_approval_info)?;
+    let mut swap_program_approval =
+        assert_uninitialized::<SwapProgramApproval>(swap_program_approval_info)?;
+
+    swap_program_approval.init(InitSwapProgramApprovalParams {
+        lending_market: *lending_market_info.key,
+        swap_program_id,
+    });
+    SwapProgramApproval::pack(
+        swap_program_approval,
+        &mut swap_program_approval_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_remove_swap_program(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let swap_program_approval_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if swap_program_approval_info.owner != program_id {
+        msg!("Swap program approval provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let swap_program_approval =
+        SwapProgramApproval::unpack(&swap_program_approval_info.data.borrow())?;
+    if &swap_program_approval.lending_market != lending_market_info.key {
+        msg!("Swap program approval lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    SwapProgramApproval::pack(
+        SwapProgramApproval::default(),
+        &mut swap_program_approval_info.data.borrow_mut(),
+    )?;
+    let lamports = swap_program_approval_info.lamports();
+    **swap_program_approval_info.lamports.borrow_mut() = 0;
+    **lending_market_owner_info.lamports.borrow_mut() = lending_market_owner_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_obligation_delegation(
+    program_id: &Pubkey,
+    delegate: Pubkey,
+    permissions: u8,
+    value_limit: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    ObligationDelegation::validate_permissions(permissions)?;
+
+    let account_info_iter = &mut accounts.iter();
+    let obligation_delegation_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if delegate == obligation.owner {
+        msg!("Obligation owner cannot be its own delegate");
+        return Err(LendingError::InvalidObligationDelegation.into());
+    }
+
+    let (obligation_delegation_pubkey, bump_seed) =
+        find_obligation_delegation_address(program_id, obligation_info.key, &delegate);
+    if &obligation_delegation_pubkey != obligation_delegation_info.key {
+        msg!("Obligation delegation provided does not match the derived obligation delegation address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation_delegation_info.data_is_empty() {
+        create_program_derived_account(CreateProgramDerivedAccountParams {
+            payer: obligation_owner_info.clone(),
+            account: obligation_delegation_info.clone(),
+            space: ObligationDelegation::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: &[
+                obligation_info.key.as_ref(),
+                delegate.as_ref(),
+                OBLIGATION_DELEGATION_SEED,
+                &[bump_seed],
+            ],
+            system_program: system_program_info.clone(),
+        })?;
+    } else if obligation_delegation_info.owner != program_id {
+        msg!("Obligation delegation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_rent_exempt(rent, obligation_delegation_info)?;
+
+    // setting a delegation replaces any previous grant, including the value already used
+    let mut obligation_delegation =
+        ObligationDelegation::unpack_unchecked(&obligation_delegation_info.data.borrow())?;
+    if !obligation_delegation.is_initialized() {
+        obligation.delegations_count = obligation
+            .delegations_count
+            .checked_add(1)
+            .ok_or(LendingError::MathOverflow)?;
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+    }
+    obligation_delegation.init(InitObligationDelegationParams {
+        obligation: *obligation_info.key,
+        delegate,
+        permissions,
+        value_limit: Decimal::from(value_limit),
+    });
+    ObligationDelegation::pack(
+        obligation_delegation,
+        &mut obligation_delegation_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_revoke_obligation_delegation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_delegation_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if obligation_delegation_info.owner != program_id {
+        msg!("Obligation delegation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let obligation_delegation =
+        ObligationDelegation::unpack(&obligation_delegation_info.data.borrow())?;
+    if &obligation_delegation.obligation != obligation_info.key {
+        msg!("Obligation delegation obligation does not match the obligation provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    obligation.delegations_count = obligation
+        .delegations_count
+        .checked_sub(1)
+        .ok_or(LendingError::MathOverflow)?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    ObligationDelegation::pack(
+        ObligationDelegation::default(),
+        &mut obligation_delegation_info.data.borrow_mut(),
+    )?;
+    let lamports = obligation_delegation_info.lamports();
+    **obligation_delegation_info.lamports.borrow_mut() = 0;
+    **obligation_owner_info.lamports.borrow_mut() = obligation_owner_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_transfer_obligation_ownership(
+    program_id: &Pubkey,
+    new_owner: Pubkey,
+    require_acceptance: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter).ok();
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation.delegations_count > 0 {
+        msg!("Obligation delegations and borrow allowances must be revoked before its ownership can be transferred");
+        return Err(LendingError::ObligationDelegated.into());
+    }
+    if obligation.seeded {
+        msg!(
+            "Obligation address is derived from its owner and its ownership cannot be transferred"
+        );
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    // a pending owner is only checked on acceptance
+    if !require_acceptance && new_owner != obligation.owner {
+        assert_lending_market_member(
+            program_id,
+            &lending_market,
+            lending_market_info,
+            &new_owner,
+            lending_market_membership_info,
+        )?;
+    }
+
+    if require_acceptance && new_owner != obligation.owner {
+        obligation.pending_owner = new_owner;
+    } else {
+        // proposing the current owner cancels a pending transfer
+        obligation.owner = new_owner;
+        obligation.pending_owner = Pubkey::default();
+    }
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_accept_obligation_ownership(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let pending_owner_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter).ok();
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.pending_owner == Pubkey::default()
+        || &obligation.pending_owner != pending_owner_info.key
+    {
+        msg!("Obligation pending owner does not match the pending owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !pending_owner_info.is_signer {
+        msg!("Obligation pending owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation.delegations_count > 0 {
+        msg!("Obligation delegations and borrow allowances must be revoked before its ownership can be transferred");
+        return Err(LendingError::ObligationDelegated.into());
+    }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        pending_owner_info.key,
+        lending_market_membership_info,
+    )?;
+
+    obligation.owner = obligation.pending_owner;
+    obligation.pending_owner = Pubkey::default();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_approve_borrow_allowance(
+    program_id: &Pubkey,
+    delegate: Pubkey,
+    allowance: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let borrow_allowance_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_membership_info = next_account_info(account_info_iter).ok();
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if delegate == obligation.owner {
+        msg!("Obligation owner cannot be its own borrow allowance delegate");
+        return Err(LendingError::InvalidBorrowAllowance.into());
+    }
+    assert_lending_market_member(
+        program_id,
+        &lending_market,
+        lending_market_info,
+        &delegate,
+        lending_market_membership_info,
+    )?;
+
+    let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if borrow_reserve.lending_market != obligation.lending_market {
+        msg!("Borrow reserve lending market does not match the obligation lending market");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let (borrow_allowance_pubkey, bump_seed) = find_borrow_allowance_address(
+        program_id,
+        obligation_info.key,
+        &delegate,
+        borrow_reserve_info.key,
+    );
+    if &borrow_allowance_pubkey != borrow_allowance_info.key {
+        msg!("Borrow allowance provided does not match the derived borrow allowance address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if borrow_allowance_info.data_is_empty() {
+        create_program_derived_account(CreateProgramDerivedAccountParams {
+            payer: obligation_owner_info.clone(),
+            account: borrow_allowance_info.clone(),
+            space: BorrowAllowance::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: &[
+                obligation_info.key.as_ref(),
+                delegate.as_ref(),
+                borrow_reserve_info.key.as_ref(),
+                BORROW_ALLOWANCE_SEED,
+                &[bump_seed],
+            ],
+            system_program: system_program_info.clone(),
+        })?;
+    } else if borrow_allowance_info.owner != program_id {
+        msg!("Borrow allowance provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_rent_exempt(rent, borrow_allowance_info)?;
+
+    // approving an existing allowance keeps the cumulative amount already borrowed
+    let mut borrow_allowance =
+        BorrowAllowance::unpack_unchecked(&borrow_allowance_info.data.borrow())?;
+    if borrow_allowance.is_initialized() {
+        borrow_allowance.allowance = allowance;
+    } else {
+        obligation.delegations_count = obligation
+            .delegations_count
+            .checked_add(1)
+            .ok_or(LendingError::MathOverflow)?;
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+        borrow_allowance.init(InitBorrowAllowanceParams {
+            obligation: *obligation_info.key,
+            delegate,
+            borrow_reserve: *borrow_reserve_info.key,
+            allowance,
+        });
+    }
+    BorrowAllowance::pack(
+        borrow_allowance,
+        &mut borrow_allowance_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_revoke_borrow_allowance(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let borrow_allowance_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if borrow_allowance_info.owner != program_id {
+        msg!("Borrow allowance provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let borrow_allowance = BorrowAllowance::unpack(&borrow_allowance_info.data.borrow())?;
+    if &borrow_allowance.obligation != obligation_info.key {
+        msg!("Borrow allowance obligation does not match the obligation provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    obligation.delegations_count = obligation
+        .delegations_count
+        .checked_sub(1)
+        .ok_or(LendingError::MathOverflow)?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    BorrowAllowance::pack(
+        BorrowAllowance::default(),
+        &mut borrow_allowance_info.data.borrow_mut(),
+    )?;
+    let lamports = borrow_allowance_info.lamports();
+    **borrow_allowance_info.lamports.borrow_mut() = 0;
+    **obligation_owner_info.lamports.borrow_mut() = obligation_owner_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_flash_loan_fee_exemption(
+    program_id: &Pubkey,
+    exempt: Pubkey,
+    fee_discount: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let flash_loan_fee_exemptions_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let (flash_loan_fee_exemptions_pubkey, bump_seed) =
+        find_flash_loan_fee_exemptions_address(program_id, lending_market_info.key);
+    if &flash_loan_fee_exemptions_pubkey != flash_loan_fee_exemptions_info.key {
+        msg!("Flash loan fee exemptions provided do not match the derived flash loan fee exemptions address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if flash_loan_fee_exemptions_info.data_is_empty() {
+        create_program_derived_account(CreateProgramDerivedAccountParams {
+            payer: lending_market_owner_info.clone(),
+            account: flash_loan_fee_exemptions_info.clone(),
+            space: FlashLoanFeeExemptions::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: &[
+                lending_market_info.key.as_ref(),
+                FLASH_LOAN_FEE_EXEMPTIONS_SEED,
+                &[bump_seed],
+            ],
+            system_program: system_program_info.clone(),
+        })?;
+    } else if flash_loan_fee_exemptions_info.owner != program_id {
+        msg!("Flash loan fee exemptions provided are not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_rent_exempt(rent, flash_loan_fee_exemptions_info)?;
+
+    let mut flash_loan_fee_exemptions =
+        FlashLoanFeeExemptions::unpack_unchecked(&flash_loan_fee_exemptions_info.data.borrow())?;
+    if !flash_loan_fee_exemptions.is_initialized() {
+        flash_loan_fee_exemptions.init(InitFlashLoanFeeExemptionsParams {
+            bump_seed,
+            lending_market: *lending_market_info.key,
+        });
+    }
+    flash_loan_fee_exemptions.set_exemption(exempt, fee_discount)?;
+    FlashLoanFeeExemptions::pack(
+        flash_loan_fee_exemptions,
+        &mut flash_loan_fee_exemptions_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_reserve_status(
+    program_id: &Pubkey,
+    status: ReserveStatus,
+    loan_to_value_ratio_target: u8,
+    ramp_slots: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    reserve.set_status(
+        status,
+        loan_to_value_ratio_target,
+        ramp_slots,
+        &lending_market.reserve_config_bounds(clock.slot),
+        clock.slot,
+    )?;
+    validate_reserve_config(reserve.config)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_close_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Reserve collateral supply does not match the reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.status == ReserveStatus::Active {
+        msg!("Reserve must be reduce-only or frozen before it can be closed");
+        return Err(LendingError::ReserveActive.into());
+    }
+    // fractional borrow dust left by rounding repayments can never be repaid, so it is dropped
+    // with the reserve while the liquidity supply remainder is swept to the fee receiver
+    if reserve.collateral.mint_total_supply != 0
+        || reserve.liquidity.borrowed_amount_wads.try_floor_u64()? != 0
+    {
+        msg!("Reserve cannot be closed while it has deposits or borrows");
+        return Err(LendingError::ReserveNotEmpty.into());
+    }
+    let reserve_collateral_supply = Account::unpack(&reserve_collateral_supply_info.data.borrow())?;
+    if reserve_collateral_supply.amount != 0 {
+        msg!("Reserve cannot be closed while it has collateral deposited in obligations");
+        return Err(LendingError::ReserveNotEmpty.into());
+    }
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(program_id, reserve_info.key);
+    if &pending_reserve_config_pubkey != pending_reserve_config_info.key {
+        msg!("Pending reserve config provided does not match the derived pending reserve config address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let reserve_liquidity_supply = Account::unpack(&reserve_liquidity_supply_info.data.borrow())?;
+    if reserve_liquidity_supply.amount > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: reserve_liquidity_supply_info.clone(),
+            destination: reserve_liquidity_fee_receiver_info.clone(),
+            amount: reserve_liquidity_supply.amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    spl_token_close_account(TokenCloseAccountParams {
+        account: reserve_liquidity_supply_info.clone(),
+        destination: destination_info.clone(),
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    spl_token_close_account(TokenCloseAccountParams {
+        account: reserve_collateral_supply_info.clone(),
+        destination: destination_info.clone(),
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Reserve::pack(Reserve::default(), &mut reserve_info.data.borrow_mut())?;
+    let lamports = reserve_info.lamports();
+    **reserve_info.lamports.borrow_mut() = 0;
+    **destination_info.lamports.borrow_mut() = destination_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+
+    // a config change still queued for the reserve is closed with it
+    if pending_reserve_config_info.owner == program_id {
+        PendingReserveConfig::pack(
+            PendingReserveConfig::default(),
+            &mut pending_reserve_config_info.data.borrow_mut(),
+        )?;
+        let lamports = pending_reserve_config_info.lamports();
+        **pending_reserve_config_info.lamports.borrow_mut() = 0;
+        **destination_info.lamports.borrow_mut() = destination_info
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(LendingError::MathOverflow)?;
+    }
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_liquidation_config(
+    program_id: &Pubkey,
+    config: LiquidationConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    config.validate()?;
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.set_liquidation_config(config, clock.slot)?;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
+    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+        msg!(
+            "Rent exempt balance insufficient got {} expected {}",
+            &account_info.lamports().to_string(),
+            &rent.minimum_balance(account_info.data_len()).to_string(),
+        );
+        Err(LendingError::NotRentExempt.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn assert_uninitialized<T: Pack + IsInitialized>(
+    account_info: &AccountInfo,
+) -> Result<T, ProgramError> {
+    let account: T = T::unpack_unchecked(&account_info.data.borrow())?;
+    if account.is_initialized() {
+        Err(LendingError::AlreadyInitialized.into())
+    } else {
+        Ok(account)
+    }
+}
+
+fn assert_pending_reserve_config(
+    program_id: &Pubkey,
+    reserve_info: &AccountInfo,
+    pending_reserve_config_info: &AccountInfo,
+) -> Result<PendingReserveConfig, ProgramError> {
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(program_id, reserve_info.key);
+    if &pending_reserve_config_pubkey != pending_reserve_config_info.key {
+        msg!("Pending reserve config provided does not match the derived pending reserve config address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if pending_reserve_config_info.data_is_empty() {
+        msg!("Reserve has no pending config change");
+        return Err(LendingError::ReserveConfigNotPending.into());
+    }
+    if pending_reserve_config_info.owner != program_id {
+        msg!("Pending reserve config provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let pending_reserve_config =
+        PendingReserveConfig::unpack_unchecked(&pending_reserve_config_info.data.borrow())?;
+    if !pending_reserve_config.is_initialized() {
+        msg!("Reserve has no pending config change");
+        return Err(LendingError::ReserveConfigNotPending.into());
+    }
+    if &pending_reserve_config.reserve != reserve_info.key {
+        msg!("Pending reserve config reserve does not match the reserve provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    Ok(pending_reserve_config)
+}
+
+/// Checks that the member is allowed to use the lending market. Markets which aren't
+/// permissioned don't require a membership account.
+fn assert_lending_market_member(
+    program_id: &Pubkey,
+    lending_market: &LendingMarket,
+    lending_market_info: &AccountInfo,
+    member: &Pubkey,
+    lending_market_membership_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    if !lending_market.permissioned {
+        return Ok(());
+    }
+    let lending_market_membership_info = match lending_market_membership_info {
+        Some(lending_market_membership_info) => lending_market_membership_info,
+        None => {
+            msg!("Lending market is permissioned and requires a lending market membership");
+            return Err(LendingError::InvalidLendingMarketMembership.into());
+        }
+    };
+    let (lending_market_membership_pubkey, _bump_seed) =
+        find_lending_market_membership_address(program_id, lending_market_info.key, member);
+    if &lending_market_membership_pubkey != lending_market_membership_info.key {
+        msg!("Lending market membership provided does not match the derived lending market membership address");
+        return Err(LendingError::InvalidLendingMarketMembership.into());
+    }
+    if lending_market_membership_info.owner != program_id {
+        msg!("Lending market membership provided is not owned by the lending program");
+        return Err(LendingError::InvalidLendingMarketMembership.into());
+    }
+    let lending_market_membership =
+        LendingMarketMembership::unpack_unchecked(&lending_market_membership_info.data.borrow())?;
+    if !lending_market_membership.is_initialized()
+        || &lending_market_membership.lending_market != lending_market_info.key
+        || &lending_market_membership.member != member
+    {
+        msg!("Lending market membership provided does not belong to the member");
+        return Err(LendingError::InvalidLendingMarketMembership.into());
+    }
+    Ok(())
+}
+
+/// Check that the obligation owner signed, or a delegate with the permission signed and provided
+/// its obligation delegation, which is returned
+fn assert_obligation_authority(
+    program_id: &Pubkey,
+    obligation: &Obligation,
+    obligation_info: &AccountInfo,
+    obligation_authority_info: &AccountInfo,
+    obligation_delegation_info: Option<&AccountInfo>,
+    permission: u8,
+) -> Result<Option<ObligationDelegation>, ProgramError> {
+    if &obligation.owner == obligation_authority_info.key {
+        if !obligation_authority_info.is_signer {
+            msg!("Obligation owner provided must be a signer");
+            return Err(LendingError::InvalidSigner.into());
+        }
+        return Ok(None);
+    }
+    let obligation_delegation_info = match obligation_delegation_info {
+        Some(obligation_delegation_info) => obligation_delegation_info,
+        None => {
+            msg!("Obligation owner does not match the obligation owner provided");
+            return Err(LendingError::InvalidObligationOwner.into());
+        }
+    };
+    let (obligation_delegation_pubkey, _bump_seed) = find_obligation_delegation_address(
+        program_id,
+        obligation_info.key,
+        obligation_authority_info.key,
+    );
+    if &obligation_delegation_pubkey != obligation_delegation_info.key {
+        msg!("Obligation delegation provided does not match the derived obligation delegation address");
+        return Err(LendingError::InvalidObligationDelegation.into());
+    }
+    if obligation_delegation_info.owner != program_id {
+        msg!("Obligation delegation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let obligation_delegation =
+        ObligationDelegation::unpack(&obligation_delegation_info.data.borrow())?;
+    if &obligation_delegation.obligation != obligation_info.key
+        || &obligation_delegation.delegate != obligation_authority_info.key
+    {
+        msg!("Obligation delegation provided does not belong to the obligation and delegate");
+        return Err(LendingError::InvalidObligationDelegation.into());
+    }
+    if !obligation_delegation.has_permission(permission) {
+        msg!("Obligation delegation does not grant the permission required");
+        return Err(LendingError::InvalidObligationDelegation.into());
+    }
+    if !obligation_authority_info.is_signer {
+        msg!("Obligation delegate provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    Ok(Some(obligation_delegation))
+}
+
+/// Check that the delegate signed and provided its borrow allowance for the obligation and borrow
+/// reserve, which is returned
+fn assert_borrow_allowance(
+    program_id: &Pubkey,
+    obligation_info: &AccountInfo,
+    borrow_reserve_info: &AccountInfo,
+    delegate_info: &AccountInfo,
+    borrow_allowance_info: &AccountInfo,
+) -> Result<BorrowAllowance, ProgramError> {
+    let (borrow_allowance_pubkey, _bump_seed) = find_borrow_allowance_address(
+        program_id,
+        obligation_info.key,
+        delegate_info.key,
+        borrow_reserve_info.key,
+    );
+    if &borrow_allowance_pubkey != borrow_allowance_info.key {
+        msg!("Borrow allowance provided does not match the derived borrow allowance address");
+        return Err(LendingError::InvalidBorrowAllowance.into());
+    }
+    if borrow_allowance_info.owner != program_id {
+        msg!("Borrow allowance provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let borrow_allowance = BorrowAllowance::unpack(&borrow_allowance_info.data.borrow())?;
+    if &borrow_allowance.obligation != obligation_info.key
+        || &borrow_allowance.delegate != delegate_info.key
+        || &borrow_allowance.borrow_reserve != borrow_reserve_info.key
+    {
+        msg!("Borrow allowance provided does not belong to the obligation, delegate and borrow reserve");
+        return Err(LendingError::InvalidBorrowAllowance.into());
+    }
+    if !delegate_info.is_signer {
+        msg!("Borrow allowance delegate provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    Ok(borrow_allowance)
+}
+
+/// Checks that the swap program is approved by the lending market
+fn assert_swap_program_approved(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo,
+    swap_program_approval_info: &AccountInfo,
+    swap_program_info: &AccountInfo,
+) -> ProgramResult {
+    let (swap_program_approval_pubkey, _bump_seed) = find_swap_program_approval_address(
+        program_id,
+        lending_market_info.key,
+        swap_program_info.key,
+    );
+    if &swap_program_approval_pubkey != swap_program_approval_info.key {
+        msg!("Swap program approval provided does not match the derived swap program approval address");
+        return Err(LendingError::InvalidSwapProgram.into());
+    }
+    if swap_program_approval_info.owner != program_id {
+        msg!("Swap program approval provided is not owned by the lending program");
+        return Err(LendingError::InvalidSwapProgram.into());
+    }
+    let swap_program_approval =
+        SwapProgramApproval::unpack_unchecked(&swap_program_approval_info.data.borrow())?;
+    if !swap_program_approval.is_initialized()
+        || &swap_program_approval.lending_market != lending_market_info.key
+        || &swap_program_approval.swap_program_id != swap_program_info.key
+    {
+        msg!("Swap program provided is not approved by the lending market");
+        return Err(LendingError::InvalidSwapProgram.into());
+    }
+    Ok(())
+}
 
-    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
-    if reserve_info.owner != program_id {
+/// Swap liquidity through an approved swap program, returning the amount received
+fn _swap_liquidity<'a>(
+    swap_program_info: &AccountInfo<'a>,
+    swap_account_infos: &[AccountInfo<'a>],
+    destination_liquidity_info: &AccountInfo<'a>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<u64, ProgramError> {
+    let destination_liquidity_balance_before =
+        Account::unpack(&destination_liquidity_info.data.borrow())?.amount;
+    token_swap(TokenSwapParams {
+        swap_program: swap_program_info.clone(),
+        swap_accounts: swap_account_infos,
+        amount_in,
+        minimum_amount_out,
+    })?;
+    let amount_out = Account::unpack(&destination_liquidity_info.data.borrow())?
+        .amount
+        .checked_sub(destination_liquidity_balance_before)
+        .ok_or(LendingError::InvalidAccountInput)?;
+    if amount_out < minimum_amount_out {
         msg!(
-            "Reserve provided is not owned by the lending program {} != {}",
-            &reserve_info.owner.to_string(),
-            &program_id.to_string(),
+            "Swap amount received {} is less than the minimum {}",
+            amount_out,
+            minimum_amount_out
         );
+        return Err(LendingError::ExceededSlippage.into());
+    }
+    if amount_out == 0 {
+        msg!("Swap amount received is too small to deposit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    Ok(amount_out)
+}
+
+/// Check the reserve whose collateral is withdrawn and swapped, which is returned
+#[allow(clippy::too_many_arguments)]
+fn assert_withdraw_reserve(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo,
+    withdraw_reserve_info: &AccountInfo,
+    withdraw_reserve_collateral_supply_info: &AccountInfo,
+    withdraw_reserve_collateral_mint_info: &AccountInfo,
+    withdraw_reserve_liquidity_supply_info: &AccountInfo,
+    source_liquidity_info: &AccountInfo,
+    clock: &Clock,
+) -> Result<Reserve, ProgramError> {
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if withdraw_reserve_info.owner != program_id {
+        msg!("Withdraw reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &reserve.lending_market != lending_market_info.key {
-        msg!("Reserve lending market does not match the lending market provided");
+    if &withdraw_reserve.lending_market != lending_market_info.key {
+        msg!("Withdraw reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
+        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.mint_pubkey != withdraw_reserve_collateral_mint_info.key {
+        msg!("Withdraw reserve collateral mint does not match the withdraw reserve collateral mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.liquidity.supply_pubkey != withdraw_reserve_liquidity_supply_info.key {
+        msg!("Withdraw reserve liquidity supply does not match the withdraw reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if withdraw_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+    if withdraw_reserve.status == ReserveStatus::Frozen {
+        msg!("Withdraw reserve is frozen");
+        return Err(LendingError::ReserveFrozen.into());
+    }
+    Ok(withdraw_reserve)
+}
 
-    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
-    if lending_market_info.owner != program_id {
-        msg!(
-            "Lending market provided is not owned by the lending program  {} != {}",
-            &lending_market_info.owner.to_string(),
-            &program_id.to_string(),
-        );
+/// Check the reserve the swapped liquidity is deposited into, which is returned
+#[allow(clippy::too_many_arguments)]
+fn assert_deposit_reserve(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo,
+    deposit_reserve_info: &AccountInfo,
+    deposit_reserve_liquidity_supply_info: &AccountInfo,
+    deposit_reserve_collateral_mint_info: &AccountInfo,
+    deposit_reserve_collateral_supply_info: &AccountInfo,
+    destination_liquidity_info: &AccountInfo,
+    clock: &Clock,
+) -> Result<Reserve, ProgramError> {
+    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    if deposit_reserve_info.owner != program_id {
+        msg!("Deposit reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &lending_market.owner != lending_market_owner_info.key {
-        msg!("Lending market owner does not match the lending market owner provided");
-        return Err(LendingError::InvalidMarketOwner.into());
+    if &deposit_reserve.lending_market != lending_market_info.key {
+        msg!("Deposit reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-    if !lending_market_owner_info.is_signer {
-        msg!("Lending market owner provided must be a signer");
-        return Err(LendingError::InvalidSigner.into());
+    if &deposit_reserve.liquidity.supply_pubkey != deposit_reserve_liquidity_supply_info.key {
+        msg!("Deposit reserve liquidity supply does not match the deposit reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-
-    let authority_signer_seeds = &[
-        lending_market_info.key.as_ref(),
-        &[lending_market.bump_seed],
-    ];
-    let lending_market_authority_pubkey =
-        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
-    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+    if &deposit_reserve.collateral.mint_pubkey != deposit_reserve_collateral_mint_info.key {
+        msg!("Deposit reserve collateral mint does not match the deposit reserve collateral mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &deposit_reserve.collateral.supply_pubkey != deposit_reserve_collateral_supply_info.key {
+        msg!("Deposit reserve collateral supply does not match the deposit reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &deposit_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
         msg!(
-            "Derived lending market authority does not match the lending market authority provided"
+            "Deposit reserve liquidity supply cannot be used as the destination liquidity provided"
         );
-        return Err(LendingError::InvalidMarketAuthority.into());
+        return Err(LendingError::InvalidAccountInput.into());
     }
-
-    if *pyth_price_info.key != reserve.liquidity.pyth_oracle_pubkey {
-        validate_pyth_keys(&lending_market, pyth_product_info, pyth_price_info)?;
-        reserve.liquidity.pyth_oracle_pubkey = *pyth_price_info.key;
+    if deposit_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Deposit reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
     }
-
-    if *switchboard_feed_info.key != reserve.liquidity.switchboard_oracle_pubkey {
-        validate_switchboard_keys(&lending_market, switchboard_feed_info)?;
-        reserve.liquidity.switchboard_oracle_pubkey = *switchboard_feed_info.key;
+    if deposit_reserve.status != ReserveStatus::Active {
+        msg!("Deposit reserve is not active and does not accept deposits");
+        return Err(LendingError::ReserveNotActive.into());
     }
-    if reserve.liquidity.switchboard_oracle_pubkey == spl_token_lending::NULL_PUBKEY
-        && (*pyth_price_info.key == spl_token_lending::NULL_PUBKEY
-            || *pyth_product_info.key == spl_token_lending::NULL_PUBKEY)
-    {
-        msg!("At least one price oracle must have a non-null pubkey");
-        return Err(LendingError::InvalidOracleConfig.into());
+    if deposit_reserve.loan_to_value_ratio(clock.slot) == 0 {
+        msg!("Deposit reserve has collateral disabled for borrowing");
+        return Err(LendingError::ReserveCollateralDisabled.into());
     }
-
-    reserve.config = config;
-    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
-    Ok(())
+    Ok(deposit_reserve)
 }
 
-fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
-        msg!(
-            "Rent exempt balance insufficient got {} expected {}",
-            &account_info.lamports().to_string(),
-            &rent.minimum_balance(account_info.data_len()).to_string(),
-        );
-        Err(LendingError::NotRentExempt.into())
-    } else {
-        Ok(())
-    }
+/// Redeem collateral withdrawn from the obligation and swap the liquidity, returning the withdraw
+/// reserve loan to value rate and the amount received
+#[allow(clippy::too_many_arguments)]
+fn _redeem_and_swap_collateral<'a>(
+    mut withdraw_reserve: Reserve,
+    withdraw_amount: u64,
+    minimum_amount_out: u64,
+    clock: &Clock,
+    withdraw_reserve_info: &AccountInfo<'a>,
+    withdraw_reserve_collateral_supply_info: &AccountInfo<'a>,
+    withdraw_reserve_collateral_mint_info: &AccountInfo<'a>,
+    withdraw_reserve_liquidity_supply_info: &AccountInfo<'a>,
+    source_liquidity_info: &AccountInfo<'a>,
+    destination_liquidity_info: &AccountInfo<'a>,
+    lending_market_authority_info: &AccountInfo<'a>,
+    authority_signer_seeds: &[&[u8]],
+    token_program_id: &AccountInfo<'a>,
+    swap_program_info: &AccountInfo<'a>,
+    swap_account_infos: &[AccountInfo<'a>],
+) -> Result<(Rate, u64), ProgramError> {
+    let liquidity_amount = withdraw_reserve.redeem_collateral(withdraw_amount)?;
+    if liquidity_amount == 0 {
+        msg!("Withdraw amount is too small to redeem liquidity");
+        return Err(LendingError::WithdrawTooSmall.into());
+    }
+    let withdraw_loan_to_value_rate =
+        Rate::from_percent(withdraw_reserve.loan_to_value_ratio(clock.slot));
+    withdraw_reserve.last_update.mark_stale();
+    Reserve::pack(
+        withdraw_reserve,
+        &mut withdraw_reserve_info.data.borrow_mut(),
+    )?;
+
+    spl_token_burn(TokenBurnParams {
+        mint: withdraw_reserve_collateral_mint_info.clone(),
+        source: withdraw_reserve_collateral_supply_info.clone(),
+        amount: withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: withdraw_reserve_liquidity_supply_info.clone(),
+        destination: source_liquidity_info.clone(),
+        amount: liquidity_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    let amount_out = _swap_liquidity(
+        swap_program_info,
+        swap_account_infos,
+        destination_liquidity_info,
+        liquidity_amount,
+        minimum_amount_out,
+    )?;
+
+    Ok((withdraw_loan_to_value_rate, amount_out))
 }
 
-fn assert_uninitialized<T: Pack + IsInitialized>(
-    account_info: &AccountInfo,
-) -> Result<T, ProgramError> {
-    let account: T = T::unpack_unchecked(&account_info.data.borrow())?;
-    if account.is_initialized() {
-        Err(LendingError::AlreadyInitialized.into())
-    } else {
-        Ok(account)
+/// Deposit swapped liquidity into the deposit reserve, returning its market value and the amount
+/// of collateral minted
+#[allow(clippy::too_many_arguments)]
+fn _deposit_swapped_liquidity<'a>(
+    amount_out: u64,
+    deposit_reserve_info: &AccountInfo<'a>,
+    deposit_reserve_liquidity_supply_info: &AccountInfo<'a>,
+    deposit_reserve_collateral_mint_info: &AccountInfo<'a>,
+    deposit_reserve_collateral_supply_info: &AccountInfo<'a>,
+    destination_liquidity_info: &AccountInfo<'a>,
+    lending_market_authority_info: &AccountInfo<'a>,
+    authority_signer_seeds: &[&[u8]],
+    obligation_owner_info: &AccountInfo<'a>,
+    token_program_id: &AccountInfo<'a>,
+) -> Result<(Decimal, u64), ProgramError> {
+    // the swap program can invoke other programs, so the deposit reserve is loaded once it returns
+    let mut deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    if Decimal::from(amount_out)
+        .try_add(deposit_reserve.liquidity.total_supply()?)?
+        .try_floor_u64()?
+        > deposit_reserve.config.deposit_limit
+    {
+        msg!("Cannot deposit liquidity above the reserve deposit limit");
+        return Err(LendingError::InvalidAmount.into());
     }
+
+    // @TODO: add lookup table https://git.io/JOCYq
+    let decimals = 10u64
+        .checked_pow(deposit_reserve.liquidity.mint_decimals as u32)
+        .ok_or(LendingError::MathOverflow)?;
+    let deposit_value = Decimal::from(amount_out)
+        .try_mul(deposit_reserve.liquidity.market_price)?
+        .try_div(decimals)?;
+
+    let deposit_collateral_amount = deposit_reserve.deposit_liquidity(amount_out)?;
+    deposit_reserve.last_update.mark_stale();
+    Reserve::pack(deposit_reserve, &mut deposit_reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: destination_liquidity_info.clone(),
+        destination: deposit_reserve_liquidity_supply_info.clone(),
+        amount: amount_out,
+        authority: obligation_owner_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    spl_token_mint_to(TokenMintToParams {
+        mint: deposit_reserve_collateral_mint_info.clone(),
+        destination: deposit_reserve_collateral_supply_info.clone(),
+        amount: deposit_collateral_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok((deposit_value, deposit_collateral_amount))
 }
 
 /// Unpacks a spl_token `Mint`.
@@ -2367,6 +7166,30 @@ fn spl_token_mint_to(params: TokenMintToParams<'_, '_>) -> ProgramResult {
     result.map_err(|_| LendingError::TokenMintToFailed.into())
 }
 
+/// Issue a spl_token `CloseAccount` instruction.
+#[inline(always)]
+fn spl_token_close_account(params: TokenCloseAccountParams<'_, '_>) -> ProgramResult {
+    let TokenCloseAccountParams {
+        account,
+        destination,
+        authority,
+        authority_signer_seeds,
+        token_program,
+    } = params;
+    let result = invoke_optionally_signed(
+        &spl_token::instruction::close_account(
+            token_program.key,
+            account.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?,
+        &[account, destination, authority, token_program],
+        authority_signer_seeds,
+    );
+    result.map_err(|_| LendingError::TokenCloseAccountFailed.into())
+}
+
 /// Issue a spl_token `Burn` instruction.
 #[inline(always)]
 fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
@@ -2393,6 +7216,43 @@ fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
     result.map_err(|_| LendingError::TokenBurnFailed.into())
 }
 
+/// Issue a `Swap` instruction using the SPL token-swap instruction layout, forwarding the swap
+/// accounts in the order they were provided.
+#[inline(always)]
+fn token_swap(params: TokenSwapParams<'_, '_>) -> ProgramResult {
+    let TokenSwapParams {
+        swap_program,
+        swap_accounts,
+        amount_in,
+        minimum_amount_out,
+    } = params;
+    let mut data = Vec::with_capacity(17);
+    data.push(1);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    let accounts = swap_accounts
+        .iter()
+        .map(|account_info| AccountMeta {
+            pubkey: *account_info.key,
+            is_signer: account_info.is_signer,
+            is_writable: account_info.is_writable,
+        })
+        .collect();
+    let mut account_infos = Vec::with_capacity(swap_accounts.len() + 1);
+    account_infos.push(swap_program.clone());
+    account_infos.extend_from_slice(swap_accounts);
+
+    invoke(
+        &Instruction {
+            program_id: *swap_program.key,
+            accounts,
+            data,
+        },
+        &account_infos,
+    )
+}
+
 /// validates reserve configs
 #[inline(always)]
 fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
@@ -2408,12 +7268,50 @@ fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
         msg!("Liquidation bonus must be in range [0, 100]");
         return Err(LendingError::InvalidConfig.into());
     }
+    if config.max_liquidation_bonus > 100 {
+        msg!("Max liquidation bonus must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.protocol_liquidation_fee > 100 {
+        msg!("Protocol liquidation fee must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    // reserves created before the protocol liquidation fee have no collateral fee receiver
+    if config.protocol_liquidation_fee > 0 && config.collateral_fee_receiver == Pubkey::default() {
+        msg!("Collateral fee receiver must be set to charge a protocol liquidation fee");
+        return Err(LendingError::InvalidConfig.into());
+    }
     if config.liquidation_threshold <= config.loan_to_value_ratio
         || config.liquidation_threshold > 100
     {
         msg!("Liquidation threshold must be in range (LTV, 100]");
         return Err(LendingError::InvalidConfig.into());
     }
+    let ramps = [
+        config.loan_to_value_ratio_ramp,
+        config.liquidation_threshold_ramp,
+    ];
+    for ramp in &ramps {
+        if ramp.is_active() && ramp.start_slot > ramp.end_slot {
+            msg!("Ramp start slot must not be after the ramp end slot");
+            return Err(LendingError::InvalidConfig.into());
+        }
+    }
+    // both values move linearly between the ramp slots, so checking those slots is enough
+    for ramp in &ramps {
+        for slot in &[ramp.start_slot, ramp.end_slot] {
+            let loan_to_value_ratio = config
+                .loan_to_value_ratio_ramp
+                .value(config.loan_to_value_ratio, *slot);
+            let liquidation_threshold = config
+                .liquidation_threshold_ramp
+                .value(config.liquidation_threshold, *slot);
+            if liquidation_threshold <= loan_to_value_ratio || liquidation_threshold > 100 {
+                msg!("Liquidation threshold must stay in range (LTV, 100] during ramps");
+                return Err(LendingError::InvalidConfig.into());
+            }
+        }
+    }
     if config.optimal_borrow_rate < config.min_borrow_rate {
         msg!("Optimal borrow rate must be >= min borrow rate");
         return Err(LendingError::InvalidConfig.into());
@@ -2501,6 +7399,107 @@ fn validate_switchboard_keys(
     Ok(())
 }
 
+/// validates a changed reserve collateral fee receiver AccountInfo
+fn validate_collateral_fee_receiver(
+    lending_market: &LendingMarket,
+    reserve: &Reserve,
+    config: &ReserveConfig,
+    collateral_fee_receiver_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    if config.collateral_fee_receiver == reserve.config.collateral_fee_receiver {
+        return Ok(());
+    }
+    let collateral_fee_receiver_info = match collateral_fee_receiver_info {
+        Some(info) if info.key == &config.collateral_fee_receiver => info,
+        _ => {
+            msg!("Reserve collateral fee receiver provided does not match the reserve config collateral fee receiver");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    };
+    if collateral_fee_receiver_info.owner != &lending_market.token_program_id {
+        msg!("Reserve collateral fee receiver provided is not owned by the token program");
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+    let collateral_fee_receiver = Account::unpack(&collateral_fee_receiver_info.data.borrow())?;
+    if collateral_fee_receiver.mint != reserve.collateral.mint_pubkey {
+        msg!("Reserve collateral fee receiver mint does not match the reserve collateral mint");
+        return Err(LendingError::InvalidTokenMint.into());
+    }
+    Ok(())
+}
+
+/// Create an account owned by the program at a program derived address. Any lamports already
+/// held by the address are kept, so the account creation cannot be blocked by a transfer.
+fn create_program_derived_account(
+    params: CreateProgramDerivedAccountParams<'_, '_>,
+) -> ProgramResult {
+    let CreateProgramDerivedAccountParams {
+        payer,
+        account,
+        space,
+        owner,
+        rent,
+        signer_seeds,
+        system_program,
+    } = params;
+    let required_lamports = rent
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required_lamports),
+            &[payer, account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, owner),
+        &[account, system_program],
+        &[signer_seeds],
+    )
+}
+
+struct FlashLoanReserveAccounts<'a: 'b, 'b> {
+    source_liquidity_info: &'b AccountInfo<'a>,
+    destination_liquidity_info: &'b AccountInfo<'a>,
+    reserve_info: &'b AccountInfo<'a>,
+    reserve_liquidity_fee_receiver_info: &'b AccountInfo<'a>,
+    host_fee_receiver_info: &'b AccountInfo<'a>,
+}
+
+struct BatchReserveAccounts<'a: 'b, 'b> {
+    reserve_info: &'b AccountInfo<'a>,
+    pyth_price_info: &'b AccountInfo<'a>,
+    switchboard_feed_info: &'b AccountInfo<'a>,
+    reserve_liquidity_supply_info: &'b AccountInfo<'a>,
+    reserve_liquidity_fee_receiver_info: &'b AccountInfo<'a>,
+    reserve_collateral_mint_info: &'b AccountInfo<'a>,
+    reserve_collateral_supply_info: &'b AccountInfo<'a>,
+    user_liquidity_info: &'b AccountInfo<'a>,
+}
+
+struct CreateProgramDerivedAccountParams<'a: 'b, 'b> {
+    payer: AccountInfo<'a>,
+    account: AccountInfo<'a>,
+    space: usize,
+    owner: &'b Pubkey,
+    rent: &'b Rent,
+    signer_seeds: &'b [&'b [u8]],
+    system_program: AccountInfo<'a>,
+}
+
+struct TokenCloseAccountParams<'a: 'b, 'b> {
+    account: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    authority_signer_seeds: &'b [&'b [u8]],
+    token_program: AccountInfo<'a>,
+}
+
 struct TokenInitializeMintParams<'a: 'b, 'b> {
     mint: AccountInfo<'a>,
     rent: AccountInfo<'a>,
@@ -2544,6 +7543,13 @@ struct TokenBurnParams<'a: 'b, 'b> {
     token_program: AccountInfo<'a>,
 }
 
+struct TokenSwapParams<'a: 'b, 'b> {
+    swap_program: AccountInfo<'a>,
+    swap_accounts: &'b [AccountInfo<'a>],
+    amount_in: u64,
+    minimum_amount_out: u64,
+}
+
 impl PrintProgramError for LendingError {
     fn print<E>(&self)
     where
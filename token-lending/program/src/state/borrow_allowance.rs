@@ -0,0 +1,234 @@
+use super::*;
+use crate::{
+    error::LendingError,
+    math::{Decimal, TryAdd},
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+
+/// Seed used with the obligation, delegate and borrow reserve addresses to derive the borrow
+/// allowance address
+pub const BORROW_ALLOWANCE_SEED: &[u8] = b"borrow_allowance";
+
+/// Find the borrow allowance address of a delegate on an obligation for a borrow reserve
+pub fn find_borrow_allowance_address(
+    program_id: &Pubkey,
+    obligation: &Pubkey,
+    delegate: &Pubkey,
+    borrow_reserve: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            obligation.as_ref(),
+            delegate.as_ref(),
+            borrow_reserve.as_ref(),
+            BORROW_ALLOWANCE_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Liquidity an obligation owner allows a delegate to borrow against the obligation collateral
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BorrowAllowance {
+    /// Version of the struct
+    pub version: u8,
+    /// Obligation address, which holds the debt
+    pub obligation: Pubkey,
+    /// Delegate address, which receives the borrowed liquidity
+    pub delegate: Pubkey,
+    /// Borrow reserve address
+    pub borrow_reserve: Pubkey,
+    /// Amount of liquidity the delegate can still borrow, including fees
+    pub allowance: u64,
+    /// Cumulative amount of liquidity borrowed by the delegate, including fees. Repayments are
+    /// settled on the obligation and never decrease this amount.
+    pub cumulative_borrowed_amount_wads: Decimal,
+}
+
+impl BorrowAllowance {
+    /// Create a new borrow allowance
+    pub fn new(params: InitBorrowAllowanceParams) -> Self {
+        let mut borrow_allowance = Self::default();
+        Self::init(&mut borrow_allowance, params);
+        borrow_allowance
+    }
+
+    /// Initialize a borrow allowance
+    pub fn init(&mut self, params: InitBorrowAllowanceParams) {
+        self.version = PROGRAM_VERSION;
+        self.obligation = params.obligation;
+        self.delegate = params.delegate;
+        self.borrow_reserve = params.borrow_reserve;
+        self.allowance = params.allowance;
+        self.cumulative_borrowed_amount_wads = Decimal::zero();
+    }
+
+    /// Record liquidity borrowed by the delegate, up to the remaining allowance
+    pub fn borrow(&mut self, borrow_amount: Decimal) -> ProgramResult {
+        let borrow_amount_u64 = borrow_amount.try_ceil_u64()?;
+        if borrow_amount_u64 > self.allowance {
+            msg!(
+                "Borrow amount {} cannot exceed the remaining borrow allowance {}",
+                borrow_amount_u64,
+                self.allowance
+            );
+            return Err(LendingError::BorrowAllowanceExceeded.into());
+        }
+        self.allowance -= borrow_amount_u64;
+        self.cumulative_borrowed_amount_wads = self
+            .cumulative_borrowed_amount_wads
+            .try_add(borrow_amount)?;
+        Ok(())
+    }
+}
+
+/// Initialize a borrow allowance
+pub struct InitBorrowAllowanceParams {
+    /// Obligation address, which holds the debt
+    pub obligation: Pubkey,
+    /// Delegate address, which receives the borrowed liquidity
+    pub delegate: Pubkey,
+    /// Borrow reserve address
+    pub borrow_reserve: Pubkey,
+    /// Amount of liquidity the delegate can borrow, including fees
+    pub allowance: u64,
+}
+
+impl Sealed for BorrowAllowance {}
+impl IsInitialized for BorrowAllowance {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const BORROW_ALLOWANCE_LEN: usize = 185; // 1 + 32 + 32 + 32 + 8 + 16 + 64
+impl Pack for BorrowAllowance {
+    const LEN: usize = BORROW_ALLOWANCE_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, BORROW_ALLOWANCE_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version,
+            obligation,
+            delegate,
+            borrow_reserve,
+            allowance,
+            cumulative_borrowed_amount_wads,
+            _padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            8,
+            16,
+            64
+        ];
+
+        *version = self.version.to_le_bytes();
+        obligation.copy_from_slice(self.obligation.as_ref());
+        delegate.copy_from_slice(self.delegate.as_ref());
+        borrow_reserve.copy_from_slice(self.borrow_reserve.as_ref());
+        *allowance = self.allowance.to_le_bytes();
+        pack_decimal(
+            self.cumulative_borrowed_amount_wads,
+            cumulative_borrowed_amount_wads,
+        );
+    }
+
+    /// Unpacks a byte buffer into a [BorrowAllowance](struct.BorrowAllowance.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, BORROW_ALLOWANCE_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version,
+            obligation,
+            delegate,
+            borrow_reserve,
+            allowance,
+            cumulative_borrowed_amount_wads,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            8,
+            16,
+            64
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Borrow allowance version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            obligation: Pubkey::new_from_array(*obligation),
+            delegate: Pubkey::new_from_array(*delegate),
+            borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
+            allowance: u64::from_le_bytes(*allowance),
+            cumulative_borrowed_amount_wads: unpack_decimal(cumulative_borrowed_amount_wads),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::TryDiv;
+
+    fn new_borrow_allowance(allowance: u64) -> BorrowAllowance {
+        BorrowAllowance::new(InitBorrowAllowanceParams {
+            obligation: Pubkey::new_unique(),
+            delegate: Pubkey::new_unique(),
+            borrow_reserve: Pubkey::new_unique(),
+            allowance,
+        })
+    }
+
+    #[test]
+    fn borrow() {
+        let mut borrow_allowance = new_borrow_allowance(100);
+        borrow_allowance.borrow(Decimal::from(60u64)).unwrap();
+        assert_eq!(borrow_allowance.allowance, 40);
+
+        // fractional borrow amounts round up against the allowance
+        let borrow_amount = Decimal::from(79u64).try_div(2).unwrap();
+        borrow_allowance.borrow(borrow_amount).unwrap();
+        assert_eq!(borrow_allowance.allowance, 0);
+        assert_eq!(
+            borrow_allowance.cumulative_borrowed_amount_wads,
+            Decimal::from(60u64).try_add(borrow_amount).unwrap()
+        );
+
+        assert_eq!(
+            borrow_allowance.borrow(Decimal::from(1u64)),
+            Err(LendingError::BorrowAllowanceExceeded.into())
+        );
+        assert_eq!(borrow_allowance.allowance, 0);
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let mut borrow_allowance = new_borrow_allowance(1_000);
+        borrow_allowance.borrow(Decimal::from(250u64)).unwrap();
+
+        let mut packed = [0u8; BorrowAllowance::LEN];
+        BorrowAllowance::pack(borrow_allowance.clone(), &mut packed).unwrap();
+        let unpacked = BorrowAllowance::unpack(&packed).unwrap();
+        assert_eq!(unpacked, borrow_allowance);
+    }
+}
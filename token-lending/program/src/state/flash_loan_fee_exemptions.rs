@@ -0,0 +1,297 @@
+use super::*;
+use crate::error::LendingError;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+use std::convert::TryFrom;
+
+/// Seed used with the lending market address to derive the flash loan fee exemptions address
+pub const FLASH_LOAN_FEE_EXEMPTIONS_SEED: &[u8] = b"flash_loan_fee_exemptions";
+
+/// Max number of flash loan fee exemptions in a lending market
+pub const MAX_FLASH_LOAN_FEE_EXEMPTIONS: usize = 16;
+
+/// Find the flash loan fee exemptions address of a lending market
+pub fn find_flash_loan_fee_exemptions_address(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[lending_market.as_ref(), FLASH_LOAN_FEE_EXEMPTIONS_SEED],
+        program_id,
+    )
+}
+
+/// Receiver programs and signers of a lending market that pay reduced flash loan fees
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlashLoanFeeExemptions {
+    /// Version of the struct
+    pub version: u8,
+    /// Bump seed for the derived flash loan fee exemptions address
+    pub bump_seed: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Exempt receiver programs and signers
+    pub exemptions: Vec<FlashLoanFeeExemption>,
+}
+
+impl FlashLoanFeeExemptions {
+    /// Create new flash loan fee exemptions
+    pub fn new(params: InitFlashLoanFeeExemptionsParams) -> Self {
+        let mut flash_loan_fee_exemptions = Self::default();
+        Self::init(&mut flash_loan_fee_exemptions, params);
+        flash_loan_fee_exemptions
+    }
+
+    /// Initialize flash loan fee exemptions
+    pub fn init(&mut self, params: InitFlashLoanFeeExemptionsParams) {
+        self.version = PROGRAM_VERSION;
+        self.bump_seed = params.bump_seed;
+        self.lending_market = params.lending_market;
+        self.exemptions = vec![];
+    }
+
+    /// Add or update the fee discount of an exempt address, removing it if the discount is zero
+    pub fn set_exemption(&mut self, exempt: Pubkey, fee_discount: u8) -> ProgramResult {
+        if fee_discount > 100 {
+            msg!("Flash loan fee discount must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+
+        let index = self
+            .exemptions
+            .iter()
+            .position(|exemption| exemption.exempt == exempt);
+        match index {
+            Some(index) if fee_discount == 0 => {
+                self.exemptions.remove(index);
+            }
+            Some(index) => self.exemptions[index].fee_discount = fee_discount,
+            None if fee_discount == 0 => {}
+            None => {
+                if self.exemptions.len() >= MAX_FLASH_LOAN_FEE_EXEMPTIONS {
+                    msg!(
+                        "Flash loan fee exemptions limit exceeded, the max is {}",
+                        MAX_FLASH_LOAN_FEE_EXEMPTIONS
+                    );
+                    return Err(LendingError::FlashLoanFeeExemptionsLimit.into());
+                }
+                self.exemptions.push(FlashLoanFeeExemption {
+                    exempt,
+                    fee_discount,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Largest fee discount of any of the given addresses, as a percentage
+    pub fn fee_discount<'a>(&self, addresses: impl IntoIterator<Item = &'a Pubkey>) -> u8 {
+        addresses
+            .into_iter()
+            .filter_map(|address| {
+                self.exemptions
+                    .iter()
+                    .find(|exemption| &exemption.exempt == address)
+                    .map(|exemption| exemption.fee_discount)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Initialize flash loan fee exemptions
+pub struct InitFlashLoanFeeExemptionsParams {
+    /// Bump seed for the derived flash loan fee exemptions address
+    pub bump_seed: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+}
+
+/// Flash loan receiver program or signer that pays a reduced fee
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FlashLoanFeeExemption {
+    /// Receiver program id or signer address
+    pub exempt: Pubkey,
+    /// Discount on the reserve flash loan fee, as a percentage - 100 for no fee
+    pub fee_discount: u8,
+}
+
+impl Sealed for FlashLoanFeeExemptions {}
+impl IsInitialized for FlashLoanFeeExemptions {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const FLASH_LOAN_FEE_EXEMPTION_LEN: usize = 33; // 32 + 1
+const FLASH_LOAN_FEE_EXEMPTIONS_LEN: usize = 627; // 1 + 1 + 32 + 1 + (33 * 16) + 64
+impl Pack for FlashLoanFeeExemptions {
+    const LEN: usize = FLASH_LOAN_FEE_EXEMPTIONS_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, FLASH_LOAN_FEE_EXEMPTIONS_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, bump_seed, lending_market, exemptions_len, data_flat, _padding) = mut_array_refs![
+            output,
+            1,
+            1,
+            PUBKEY_BYTES,
+            1,
+            FLASH_LOAN_FEE_EXEMPTION_LEN * MAX_FLASH_LOAN_FEE_EXEMPTIONS,
+            64
+        ];
+
+        *version = self.version.to_le_bytes();
+        *bump_seed = self.bump_seed.to_le_bytes();
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        *exemptions_len = u8::try_from(self.exemptions.len()).unwrap().to_le_bytes();
+
+        let mut offset = 0;
+        for exemption in &self.exemptions {
+            let exemption_flat = array_mut_ref![data_flat, offset, FLASH_LOAN_FEE_EXEMPTION_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (exempt, fee_discount) = mut_array_refs![exemption_flat, PUBKEY_BYTES, 1];
+            exempt.copy_from_slice(exemption.exempt.as_ref());
+            *fee_discount = exemption.fee_discount.to_le_bytes();
+            offset += FLASH_LOAN_FEE_EXEMPTION_LEN;
+        }
+    }
+
+    /// Unpacks a byte buffer into [FlashLoanFeeExemptions](struct.FlashLoanFeeExemptions.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, FLASH_LOAN_FEE_EXEMPTIONS_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, bump_seed, lending_market, exemptions_len, data_flat, _padding) = array_refs![
+            input,
+            1,
+            1,
+            PUBKEY_BYTES,
+            1,
+            FLASH_LOAN_FEE_EXEMPTION_LEN * MAX_FLASH_LOAN_FEE_EXEMPTIONS,
+            64
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Flash loan fee exemptions version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let exemptions_len = u8::from_le_bytes(*exemptions_len) as usize;
+        if exemptions_len > MAX_FLASH_LOAN_FEE_EXEMPTIONS {
+            msg!("Flash loan fee exemptions length is invalid");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut exemptions = Vec::with_capacity(exemptions_len);
+        let mut offset = 0;
+        for _ in 0..exemptions_len {
+            let exemption_flat = array_ref![data_flat, offset, FLASH_LOAN_FEE_EXEMPTION_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (exempt, fee_discount) = array_refs![exemption_flat, PUBKEY_BYTES, 1];
+            exemptions.push(FlashLoanFeeExemption {
+                exempt: Pubkey::new_from_array(*exempt),
+                fee_discount: u8::from_le_bytes(*fee_discount),
+            });
+            offset += FLASH_LOAN_FEE_EXEMPTION_LEN;
+        }
+
+        Ok(Self {
+            version,
+            bump_seed: u8::from_le_bytes(*bump_seed),
+            lending_market: Pubkey::new_from_array(*lending_market),
+            exemptions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_exemptions() -> FlashLoanFeeExemptions {
+        FlashLoanFeeExemptions::new(InitFlashLoanFeeExemptionsParams {
+            bump_seed: 255,
+            lending_market: Pubkey::new_unique(),
+        })
+    }
+
+    #[test]
+    fn set_exemption() {
+        let mut flash_loan_fee_exemptions = new_exemptions();
+        let exempt = Pubkey::new_unique();
+
+        flash_loan_fee_exemptions.set_exemption(exempt, 50).unwrap();
+        assert_eq!(flash_loan_fee_exemptions.fee_discount(&[exempt]), 50);
+
+        flash_loan_fee_exemptions
+            .set_exemption(exempt, 100)
+            .unwrap();
+        assert_eq!(flash_loan_fee_exemptions.exemptions.len(), 1);
+        assert_eq!(flash_loan_fee_exemptions.fee_discount(&[exempt]), 100);
+
+        flash_loan_fee_exemptions.set_exemption(exempt, 0).unwrap();
+        assert!(flash_loan_fee_exemptions.exemptions.is_empty());
+        assert_eq!(flash_loan_fee_exemptions.fee_discount(&[exempt]), 0);
+
+        assert_eq!(
+            flash_loan_fee_exemptions.set_exemption(exempt, 101),
+            Err(LendingError::InvalidConfig.into())
+        );
+    }
+
+    #[test]
+    fn set_exemption_limit() {
+        let mut flash_loan_fee_exemptions = new_exemptions();
+        for _ in 0..MAX_FLASH_LOAN_FEE_EXEMPTIONS {
+            flash_loan_fee_exemptions
+                .set_exemption(Pubkey::new_unique(), 100)
+                .unwrap();
+        }
+        assert_eq!(
+            flash_loan_fee_exemptions.set_exemption(Pubkey::new_unique(), 100),
+            Err(LendingError::FlashLoanFeeExemptionsLimit.into())
+        );
+    }
+
+    #[test]
+    fn fee_discount_uses_largest_match() {
+        let mut flash_loan_fee_exemptions = new_exemptions();
+        let receiver_program = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        flash_loan_fee_exemptions
+            .set_exemption(receiver_program, 25)
+            .unwrap();
+        flash_loan_fee_exemptions.set_exemption(signer, 75).unwrap();
+
+        assert_eq!(
+            flash_loan_fee_exemptions.fee_discount(&[receiver_program, Pubkey::new_unique()]),
+            25
+        );
+        assert_eq!(
+            flash_loan_fee_exemptions.fee_discount(&[receiver_program, signer]),
+            75
+        );
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let mut flash_loan_fee_exemptions = new_exemptions();
+        for fee_discount in 1..=MAX_FLASH_LOAN_FEE_EXEMPTIONS as u8 {
+            flash_loan_fee_exemptions
+                .set_exemption(Pubkey::new_unique(), fee_discount)
+                .unwrap();
+        }
+
+        let mut packed = [0u8; FlashLoanFeeExemptions::LEN];
+        FlashLoanFeeExemptions::pack(flash_loan_fee_exemptions.clone(), &mut packed).unwrap();
+        let unpacked = FlashLoanFeeExemptions::unpack(&packed).unwrap();
+        assert_eq!(unpacked, flash_loan_fee_exemptions);
+    }
+}
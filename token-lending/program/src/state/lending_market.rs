@@ -1,6 +1,12 @@
 use super::*;
+use crate::{
+    error::LendingError,
+    math::{Decimal, Rate, TryMul},
+};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
+    clock::Slot,
+    entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -25,6 +31,29 @@ pub struct LendingMarket {
     pub oracle_program_id: Pubkey,
     /// Oracle (Switchboard) program id
     pub switchboard_oracle_program_id: Pubkey,
+    /// Minimum number of slots between queueing and applying a reserve config change
+    pub reserve_config_delay_slots: u64,
+    /// Reserve config delay which takes effect once `pending_reserve_config_delay_slot` is reached
+    pub pending_reserve_config_delay_slots: u64,
+    /// Slot at which the pending reserve config delay takes effect, 0 if none is pending
+    pub pending_reserve_config_delay_slot: Slot,
+    /// Limits on how far and how often reserve risk parameters can change
+    pub reserve_config_bounds: ReserveConfigBounds,
+    /// Reserve config bounds which take effect once `pending_reserve_config_bounds_slot` is
+    /// reached
+    pub pending_reserve_config_bounds: ReserveConfigBounds,
+    /// Slot at which the pending reserve config bounds take effect, 0 if none are pending
+    pub pending_reserve_config_bounds_slot: Slot,
+    /// Whether opening obligations and depositing requires a lending market membership
+    pub permissioned: bool,
+    /// Liquidation parameters
+    pub liquidation_config: LiquidationConfig,
+    /// Liquidation parameters which take effect once `pending_liquidation_config_slot` is reached
+    pub pending_liquidation_config: LiquidationConfig,
+    /// Slot at which the pending liquidation parameters take effect, 0 if none are pending
+    pub pending_liquidation_config_slot: Slot,
+    /// Slot at which the liquidation parameters were last changed
+    pub last_liquidation_config_update_slot: Slot,
 }
 
 impl LendingMarket {
@@ -44,9 +73,403 @@ impl LendingMarket {
         self.token_program_id = params.token_program_id;
         self.oracle_program_id = params.oracle_program_id;
         self.switchboard_oracle_program_id = params.switchboard_oracle_program_id;
+        self.reserve_config_delay_slots = 0;
+        self.pending_reserve_config_delay_slots = 0;
+        self.pending_reserve_config_delay_slot = 0;
+        self.reserve_config_bounds = ReserveConfigBounds::default();
+        self.pending_reserve_config_bounds = ReserveConfigBounds::default();
+        self.pending_reserve_config_bounds_slot = 0;
+        self.permissioned = false;
+        self.liquidation_config = LiquidationConfig::default();
+        self.pending_liquidation_config = LiquidationConfig::default();
+        self.pending_liquidation_config_slot = 0;
+        self.last_liquidation_config_update_slot = 0;
+    }
+
+    /// Reserve config delay in effect at the given slot
+    pub fn reserve_config_delay(&self, slot: Slot) -> u64 {
+        if self.pending_reserve_config_delay_slot != 0
+            && slot >= self.pending_reserve_config_delay_slot
+        {
+            self.pending_reserve_config_delay_slots
+        } else {
+            self.reserve_config_delay_slots
+        }
+    }
+
+    /// Set the reserve config delay. Increases take effect immediately, while decreases only
+    /// take effect after the current delay has elapsed.
+    pub fn set_reserve_config_delay(&mut self, delay_slots: u64, slot: Slot) -> ProgramResult {
+        let current_delay_slots = self.reserve_config_delay(slot);
+        if delay_slots >= current_delay_slots {
+            self.reserve_config_delay_slots = delay_slots;
+            self.pending_reserve_config_delay_slots = 0;
+            self.pending_reserve_config_delay_slot = 0;
+        } else {
+            self.reserve_config_delay_slots = current_delay_slots;
+            self.pending_reserve_config_delay_slots = delay_slots;
+            self.pending_reserve_config_delay_slot = slot
+                .checked_add(current_delay_slots)
+                .ok_or(LendingError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Reserve config bounds in effect at the given slot
+    pub fn reserve_config_bounds(&self, slot: Slot) -> ReserveConfigBounds {
+        if self.pending_reserve_config_bounds_slot != 0
+            && slot >= self.pending_reserve_config_bounds_slot
+        {
+            self.pending_reserve_config_bounds
+        } else {
+            self.reserve_config_bounds
+        }
+    }
+
+    /// Set the reserve config bounds. Tightened bounds take effect immediately, while loosened
+    /// or removed bounds only take effect after the current minimum update interval has elapsed.
+    pub fn set_reserve_config_bounds(
+        &mut self,
+        bounds: ReserveConfigBounds,
+        slot: Slot,
+    ) -> ProgramResult {
+        let current_bounds = self.reserve_config_bounds(slot);
+        if current_bounds.is_loosened_by(&bounds) {
+            self.reserve_config_bounds = current_bounds;
+            self.pending_reserve_config_bounds = bounds;
+            self.pending_reserve_config_bounds_slot = slot
+                .checked_add(current_bounds.min_update_interval_slots)
+                .ok_or(LendingError::MathOverflow)?;
+        } else {
+            self.reserve_config_bounds = bounds;
+            self.pending_reserve_config_bounds = ReserveConfigBounds::default();
+            self.pending_reserve_config_bounds_slot = 0;
+        }
+        Ok(())
+    }
+
+    /// Liquidation parameters in effect at the given slot
+    pub fn liquidation_config(&self, slot: Slot) -> LiquidationConfig {
+        if self.pending_liquidation_config_slot != 0 && slot >= self.pending_liquidation_config_slot
+        {
+            self.pending_liquidation_config
+        } else {
+            self.liquidation_config
+        }
+    }
+
+    /// Set the liquidation parameters, within the reserve config bounds in effect. Changes take
+    /// effect once the reserve config delay has elapsed.
+    pub fn set_liquidation_config(
+        &mut self,
+        config: LiquidationConfig,
+        slot: Slot,
+    ) -> ProgramResult {
+        let current_config = self.liquidation_config(slot);
+        self.reserve_config_bounds(slot)
+            .validate_liquidation_config_change(
+                &current_config,
+                &config,
+                self.last_liquidation_config_update_slot,
+                slot,
+            )?;
+        let delay_slots = self.reserve_config_delay(slot);
+        if delay_slots == 0 {
+            self.liquidation_config = config;
+            self.pending_liquidation_config = LiquidationConfig::default();
+            self.pending_liquidation_config_slot = 0;
+        } else {
+            self.liquidation_config = current_config;
+            self.pending_liquidation_config = config;
+            self.pending_liquidation_config_slot = slot
+                .checked_add(delay_slots)
+                .ok_or(LendingError::MathOverflow)?;
+        }
+        self.last_liquidation_config_update_slot = slot;
+        Ok(())
     }
 }
 
+/// Limits on reserve risk parameter changes, a step of 0 leaves a parameter unbounded
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReserveConfigBounds {
+    /// Maximum change of the loan to value ratio per update, as a percentage
+    pub max_loan_to_value_ratio_step: u8,
+    /// Maximum change of the liquidation threshold per update, as a percentage. Also bounds the
+    /// lending market close factor and full liquidation threshold.
+    pub max_liquidation_threshold_step: u8,
+    /// Maximum change of the min and max liquidation bonus per update, as a percentage
+    pub max_liquidation_bonus_step: u8,
+    /// Maximum change of the min, optimal and max borrow rates per update, as a percentage
+    pub max_borrow_rate_step: u8,
+    /// Minimum number of slots between updates which change a bounded parameter
+    pub min_update_interval_slots: u64,
+}
+
+impl ReserveConfigBounds {
+    /// Whether any limit of the new bounds is less strict than this one
+    pub fn is_loosened_by(&self, bounds: &ReserveConfigBounds) -> bool {
+        is_step_loosened(
+            self.max_loan_to_value_ratio_step,
+            bounds.max_loan_to_value_ratio_step,
+        ) || is_step_loosened(
+            self.max_liquidation_threshold_step,
+            bounds.max_liquidation_threshold_step,
+        ) || is_step_loosened(
+            self.max_liquidation_bonus_step,
+            bounds.max_liquidation_bonus_step,
+        ) || is_step_loosened(self.max_borrow_rate_step, bounds.max_borrow_rate_step)
+            || bounds.min_update_interval_slots < self.min_update_interval_slots
+    }
+
+    /// Whether the change touches any of the bounded parameters
+    pub fn is_bounded_change(config: &ReserveConfig, new_config: &ReserveConfig) -> bool {
+        config.loan_to_value_ratio != new_config.loan_to_value_ratio
+            || config.liquidation_threshold != new_config.liquidation_threshold
+            || config.liquidation_bonus != new_config.liquidation_bonus
+            || config.max_liquidation_bonus != new_config.max_liquidation_bonus
+            || config.min_borrow_rate != new_config.min_borrow_rate
+            || config.optimal_borrow_rate != new_config.optimal_borrow_rate
+            || config.max_borrow_rate != new_config.max_borrow_rate
+            || config.loan_to_value_ratio_ramp != new_config.loan_to_value_ratio_ramp
+            || config.liquidation_threshold_ramp != new_config.liquidation_threshold_ramp
+    }
+
+    /// Validate a reserve config change made at `slot` against the bounds
+    pub fn validate_change(
+        &self,
+        config: &ReserveConfig,
+        new_config: &ReserveConfig,
+        last_config_update_slot: Slot,
+        slot: Slot,
+    ) -> ProgramResult {
+        if !Self::is_bounded_change(config, new_config) {
+            return Ok(());
+        }
+        if slot.saturating_sub(last_config_update_slot) < self.min_update_interval_slots {
+            msg!(
+                "Reserve risk parameters cannot be changed again until slot {}",
+                last_config_update_slot.saturating_add(self.min_update_interval_slots)
+            );
+            return Err(LendingError::ReserveConfigChangeOutOfBounds.into());
+        }
+        validate_step(
+            "Loan to value ratio",
+            config.loan_to_value_ratio,
+            new_config.loan_to_value_ratio,
+            self.max_loan_to_value_ratio_step,
+        )?;
+        validate_step(
+            "Liquidation threshold",
+            config.liquidation_threshold,
+            new_config.liquidation_threshold,
+            self.max_liquidation_threshold_step,
+        )?;
+        if new_config.loan_to_value_ratio_ramp != config.loan_to_value_ratio_ramp
+            && new_config.loan_to_value_ratio_ramp.is_active()
+        {
+            validate_step(
+                "Loan to value ratio ramp",
+                new_config.loan_to_value_ratio,
+                new_config.loan_to_value_ratio_ramp.target,
+                self.max_loan_to_value_ratio_step,
+            )?;
+            validate_ramp(
+                "Loan to value ratio ramp",
+                &new_config.loan_to_value_ratio_ramp,
+                self.min_update_interval_slots,
+                slot,
+            )?;
+        }
+        if new_config.liquidation_threshold_ramp != config.liquidation_threshold_ramp
+            && new_config.liquidation_threshold_ramp.is_active()
+        {
+            validate_step(
+                "Liquidation threshold ramp",
+                new_config.liquidation_threshold,
+                new_config.liquidation_threshold_ramp.target,
+                self.max_liquidation_threshold_step,
+            )?;
+            validate_ramp(
+                "Liquidation threshold ramp",
+                &new_config.liquidation_threshold_ramp,
+                self.min_update_interval_slots,
+                slot,
+            )?;
+        }
+        validate_step(
+            "Liquidation bonus",
+            config.liquidation_bonus,
+            new_config.liquidation_bonus,
+            self.max_liquidation_bonus_step,
+        )?;
+        validate_step(
+            "Max liquidation bonus",
+            config.max_liquidation_bonus,
+            new_config.max_liquidation_bonus,
+            self.max_liquidation_bonus_step,
+        )?;
+        validate_step(
+            "Min borrow rate",
+            config.min_borrow_rate,
+            new_config.min_borrow_rate,
+            self.max_borrow_rate_step,
+        )?;
+        validate_step(
+            "Optimal borrow rate",
+            config.optimal_borrow_rate,
+            new_config.optimal_borrow_rate,
+            self.max_borrow_rate_step,
+        )?;
+        validate_step(
+            "Max borrow rate",
+            config.max_borrow_rate,
+            new_config.max_borrow_rate,
+            self.max_borrow_rate_step,
+        )
+    }
+
+    /// Validate a liquidation config change made at `slot` against the bounds
+    pub fn validate_liquidation_config_change(
+        &self,
+        config: &LiquidationConfig,
+        new_config: &LiquidationConfig,
+        last_update_slot: Slot,
+        slot: Slot,
+    ) -> ProgramResult {
+        if config == new_config {
+            return Ok(());
+        }
+        if slot.saturating_sub(last_update_slot) < self.min_update_interval_slots {
+            msg!(
+                "Liquidation config cannot be changed again until slot {}",
+                last_update_slot.saturating_add(self.min_update_interval_slots)
+            );
+            return Err(LendingError::ReserveConfigChangeOutOfBounds.into());
+        }
+        validate_step(
+            "Close factor",
+            config.effective_close_factor(),
+            new_config.effective_close_factor(),
+            self.max_liquidation_threshold_step,
+        )?;
+        // enabling or disabling full liquidations is only delayed
+        if config.full_liquidation_threshold != 0 && new_config.full_liquidation_threshold != 0 {
+            validate_step(
+                "Full liquidation threshold",
+                config.full_liquidation_threshold,
+                new_config.full_liquidation_threshold,
+                self.max_liquidation_threshold_step,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Lending market liquidation parameters, a close factor or close amount of 0 falls back to
+/// `LIQUIDATION_CLOSE_FACTOR` or `LIQUIDATION_CLOSE_AMOUNT`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LiquidationConfig {
+    /// Percentage of an obligation's borrowed value that can be repaid during each liquidation
+    pub close_factor: u8,
+    /// Ratio of borrowed value to unhealthy borrow value, as a percentage, at or above which an
+    /// obligation can be fully liquidated, 0 to disable
+    pub full_liquidation_threshold: u8,
+    /// Borrowed value in quote currency below which an obligation can be fully liquidated,
+    /// 0 to disable
+    pub full_liquidation_dust_value: u64,
+    /// Obligation borrow amount that is small enough to close out
+    pub close_amount: u64,
+}
+
+impl LiquidationConfig {
+    /// Validate the liquidation parameters
+    pub fn validate(&self) -> ProgramResult {
+        if self.close_factor > 100 {
+            msg!("Close factor must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.full_liquidation_threshold > 0 && self.full_liquidation_threshold < 100 {
+            msg!("Full liquidation threshold must be 0 or in range [100, 255]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        Ok(())
+    }
+
+    /// Obligation borrow amount that is small enough to close out
+    pub fn close_amount(&self) -> u64 {
+        if self.close_amount == 0 {
+            LIQUIDATION_CLOSE_AMOUNT
+        } else {
+            self.close_amount
+        }
+    }
+
+    /// Share of an obligation's borrowed value that can be repaid in a single liquidation
+    pub fn close_factor(&self, obligation: &Obligation) -> Result<Rate, ProgramError> {
+        if self.is_full_liquidation(obligation)? {
+            return Ok(Rate::one());
+        }
+        Ok(Rate::from_percent(self.effective_close_factor()))
+    }
+
+    /// Close factor percentage, falling back to `LIQUIDATION_CLOSE_FACTOR`
+    pub fn effective_close_factor(&self) -> u8 {
+        if self.close_factor == 0 {
+            LIQUIDATION_CLOSE_FACTOR
+        } else {
+            self.close_factor
+        }
+    }
+
+    /// Whether an obligation is unhealthy or small enough to be fully liquidated
+    pub fn is_full_liquidation(&self, obligation: &Obligation) -> Result<bool, ProgramError> {
+        if self.full_liquidation_dust_value > 0
+            && obligation.borrowed_value < Decimal::from(self.full_liquidation_dust_value)
+        {
+            return Ok(true);
+        }
+        if self.full_liquidation_threshold > 0 {
+            let full_liquidation_borrow_value = obligation
+                .unhealthy_borrow_value
+                .try_mul(Rate::from_percent(self.full_liquidation_threshold))?;
+            return Ok(obligation.borrowed_value >= full_liquidation_borrow_value);
+        }
+        Ok(false)
+    }
+}
+
+// a step of 0 is unbounded, so it loosens any other step
+fn is_step_loosened(max_step: u8, new_max_step: u8) -> bool {
+    max_step != 0 && (new_max_step == 0 || new_max_step > max_step)
+}
+
+fn validate_step(name: &str, value: u8, new_value: u8, max_step: u8) -> ProgramResult {
+    let step = new_value.max(value) - new_value.min(value);
+    if max_step > 0 && step > max_step {
+        msg!(
+            "{} cannot change by more than {} per update, got {}",
+            name,
+            max_step,
+            step
+        );
+        return Err(LendingError::ReserveConfigChangeOutOfBounds.into());
+    }
+    Ok(())
+}
+
+fn validate_ramp(name: &str, ramp: &ReserveRamp, min_slots: Slot, slot: Slot) -> ProgramResult {
+    if ramp.start_slot < slot {
+        msg!("{} cannot start before the current slot {}", name, slot);
+        return Err(LendingError::ReserveConfigChangeOutOfBounds.into());
+    }
+    if ramp.end_slot.saturating_sub(ramp.start_slot) < min_slots {
+        msg!("{} must last at least {} slots", name, min_slots);
+        return Err(LendingError::ReserveConfigChangeOutOfBounds.into());
+    }
+    Ok(())
+}
+
 /// Initialize a lending market
 pub struct InitLendingMarketParams {
     /// Bump seed for derived authority address
@@ -71,7 +494,7 @@ impl IsInitialized for LendingMarket {
     }
 }
 
-const LENDING_MARKET_LEN: usize = 290; // 1 + 1 + 32 + 32 + 32 + 32 + 32 + 128
+const LENDING_MARKET_LEN: usize = 290; // 1 + 1 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 8 + 1 + 1 + 1 + 8 + 8 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 1 + 8 + 8 + 8 + 8 + 19
 impl Pack for LendingMarket {
     const LEN: usize = LENDING_MARKET_LEN;
 
@@ -86,6 +509,31 @@ impl Pack for LendingMarket {
             token_program_id,
             oracle_program_id,
             switchboard_oracle_program_id,
+            reserve_config_delay_slots,
+            pending_reserve_config_delay_slots,
+            pending_reserve_config_delay_slot,
+            max_loan_to_value_ratio_step,
+            max_liquidation_threshold_step,
+            max_liquidation_bonus_step,
+            max_borrow_rate_step,
+            min_update_interval_slots,
+            permissioned,
+            close_factor,
+            full_liquidation_threshold,
+            full_liquidation_dust_value,
+            close_amount,
+            pending_max_loan_to_value_ratio_step,
+            pending_max_liquidation_threshold_step,
+            pending_max_liquidation_bonus_step,
+            pending_max_borrow_rate_step,
+            pending_min_update_interval_slots,
+            pending_reserve_config_bounds_slot,
+            pending_close_factor,
+            pending_full_liquidation_threshold,
+            pending_full_liquidation_dust_value,
+            pending_close_amount,
+            pending_liquidation_config_slot,
+            last_liquidation_config_update_slot,
             _padding,
         ) = mut_array_refs![
             output,
@@ -96,7 +544,32 @@ impl Pack for LendingMarket {
             PUBKEY_BYTES,
             PUBKEY_BYTES,
             PUBKEY_BYTES,
-            128
+            8,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            8,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            1,
+            8,
+            8,
+            8,
+            8,
+            19
         ];
 
         *version = self.version.to_le_bytes();
@@ -106,6 +579,74 @@ impl Pack for LendingMarket {
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
         oracle_program_id.copy_from_slice(self.oracle_program_id.as_ref());
         switchboard_oracle_program_id.copy_from_slice(self.switchboard_oracle_program_id.as_ref());
+        *reserve_config_delay_slots = self.reserve_config_delay_slots.to_le_bytes();
+        *pending_reserve_config_delay_slots = self.pending_reserve_config_delay_slots.to_le_bytes();
+        *pending_reserve_config_delay_slot = self.pending_reserve_config_delay_slot.to_le_bytes();
+        *max_loan_to_value_ratio_step = self
+            .reserve_config_bounds
+            .max_loan_to_value_ratio_step
+            .to_le_bytes();
+        *max_liquidation_threshold_step = self
+            .reserve_config_bounds
+            .max_liquidation_threshold_step
+            .to_le_bytes();
+        *max_liquidation_bonus_step = self
+            .reserve_config_bounds
+            .max_liquidation_bonus_step
+            .to_le_bytes();
+        *max_borrow_rate_step = self
+            .reserve_config_bounds
+            .max_borrow_rate_step
+            .to_le_bytes();
+        *min_update_interval_slots = self
+            .reserve_config_bounds
+            .min_update_interval_slots
+            .to_le_bytes();
+        pack_bool(self.permissioned, permissioned);
+        *close_factor = self.liquidation_config.close_factor.to_le_bytes();
+        *full_liquidation_threshold = self
+            .liquidation_config
+            .full_liquidation_threshold
+            .to_le_bytes();
+        *full_liquidation_dust_value = self
+            .liquidation_config
+            .full_liquidation_dust_value
+            .to_le_bytes();
+        *close_amount = self.liquidation_config.close_amount.to_le_bytes();
+        *pending_max_loan_to_value_ratio_step = self
+            .pending_reserve_config_bounds
+            .max_loan_to_value_ratio_step
+            .to_le_bytes();
+        *pending_max_liquidation_threshold_step = self
+            .pending_reserve_config_bounds
+            .max_liquidation_threshold_step
+            .to_le_bytes();
+        *pending_max_liquidation_bonus_step = self
+            .pending_reserve_config_bounds
+            .max_liquidation_bonus_step
+            .to_le_bytes();
+        *pending_max_borrow_rate_step = self
+            .pending_reserve_config_bounds
+            .max_borrow_rate_step
+            .to_le_bytes();
+        *pending_min_update_interval_slots = self
+            .pending_reserve_config_bounds
+            .min_update_interval_slots
+            .to_le_bytes();
+        *pending_reserve_config_bounds_slot = self.pending_reserve_config_bounds_slot.to_le_bytes();
+        *pending_close_factor = self.pending_liquidation_config.close_factor.to_le_bytes();
+        *pending_full_liquidation_threshold = self
+            .pending_liquidation_config
+            .full_liquidation_threshold
+            .to_le_bytes();
+        *pending_full_liquidation_dust_value = self
+            .pending_liquidation_config
+            .full_liquidation_dust_value
+            .to_le_bytes();
+        *pending_close_amount = self.pending_liquidation_config.close_amount.to_le_bytes();
+        *pending_liquidation_config_slot = self.pending_liquidation_config_slot.to_le_bytes();
+        *last_liquidation_config_update_slot =
+            self.last_liquidation_config_update_slot.to_le_bytes();
     }
 
     /// Unpacks a byte buffer into a [LendingMarketInfo](struct.LendingMarketInfo.html)
@@ -120,6 +661,31 @@ impl Pack for LendingMarket {
             token_program_id,
             oracle_program_id,
             switchboard_oracle_program_id,
+            reserve_config_delay_slots,
+            pending_reserve_config_delay_slots,
+            pending_reserve_config_delay_slot,
+            max_loan_to_value_ratio_step,
+            max_liquidation_threshold_step,
+            max_liquidation_bonus_step,
+            max_borrow_rate_step,
+            min_update_interval_slots,
+            permissioned,
+            close_factor,
+            full_liquidation_threshold,
+            full_liquidation_dust_value,
+            close_amount,
+            pending_max_loan_to_value_ratio_step,
+            pending_max_liquidation_threshold_step,
+            pending_max_liquidation_bonus_step,
+            pending_max_borrow_rate_step,
+            pending_min_update_interval_slots,
+            pending_reserve_config_bounds_slot,
+            pending_close_factor,
+            pending_full_liquidation_threshold,
+            pending_full_liquidation_dust_value,
+            pending_close_amount,
+            pending_liquidation_config_slot,
+            last_liquidation_config_update_slot,
             _padding,
         ) = array_refs![
             input,
@@ -130,7 +696,32 @@ impl Pack for LendingMarket {
             PUBKEY_BYTES,
             PUBKEY_BYTES,
             PUBKEY_BYTES,
-            128
+            8,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            8,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            1,
+            8,
+            8,
+            8,
+            8,
+            19
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -147,6 +738,450 @@ impl Pack for LendingMarket {
             token_program_id: Pubkey::new_from_array(*token_program_id),
             oracle_program_id: Pubkey::new_from_array(*oracle_program_id),
             switchboard_oracle_program_id: Pubkey::new_from_array(*switchboard_oracle_program_id),
+            reserve_config_delay_slots: u64::from_le_bytes(*reserve_config_delay_slots),
+            pending_reserve_config_delay_slots: u64::from_le_bytes(
+                *pending_reserve_config_delay_slots,
+            ),
+            pending_reserve_config_delay_slot: u64::from_le_bytes(
+                *pending_reserve_config_delay_slot,
+            ),
+            reserve_config_bounds: ReserveConfigBounds {
+                max_loan_to_value_ratio_step: u8::from_le_bytes(*max_loan_to_value_ratio_step),
+                max_liquidation_threshold_step: u8::from_le_bytes(*max_liquidation_threshold_step),
+                max_liquidation_bonus_step: u8::from_le_bytes(*max_liquidation_bonus_step),
+                max_borrow_rate_step: u8::from_le_bytes(*max_borrow_rate_step),
+                min_update_interval_slots: u64::from_le_bytes(*min_update_interval_slots),
+            },
+            permissioned: unpack_bool(permissioned)?,
+            liquidation_config: LiquidationConfig {
+                close_factor: u8::from_le_bytes(*close_factor),
+                full_liquidation_threshold: u8::from_le_bytes(*full_liquidation_threshold),
+                full_liquidation_dust_value: u64::from_le_bytes(*full_liquidation_dust_value),
+                close_amount: u64::from_le_bytes(*close_amount),
+            },
+            pending_reserve_config_bounds: ReserveConfigBounds {
+                max_loan_to_value_ratio_step: u8::from_le_bytes(
+                    *pending_max_loan_to_value_ratio_step,
+                ),
+                max_liquidation_threshold_step: u8::from_le_bytes(
+                    *pending_max_liquidation_threshold_step,
+                ),
+                max_liquidation_bonus_step: u8::from_le_bytes(*pending_max_liquidation_bonus_step),
+                max_borrow_rate_step: u8::from_le_bytes(*pending_max_borrow_rate_step),
+                min_update_interval_slots: u64::from_le_bytes(*pending_min_update_interval_slots),
+            },
+            pending_reserve_config_bounds_slot: u64::from_le_bytes(
+                *pending_reserve_config_bounds_slot,
+            ),
+            pending_liquidation_config: LiquidationConfig {
+                close_factor: u8::from_le_bytes(*pending_close_factor),
+                full_liquidation_threshold: u8::from_le_bytes(*pending_full_liquidation_threshold),
+                full_liquidation_dust_value: u64::from_le_bytes(
+                    *pending_full_liquidation_dust_value,
+                ),
+                close_amount: u64::from_le_bytes(*pending_close_amount),
+            },
+            pending_liquidation_config_slot: u64::from_le_bytes(*pending_liquidation_config_slot),
+            last_liquidation_config_update_slot: u64::from_le_bytes(
+                *last_liquidation_config_update_slot,
+            ),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_config() -> ReserveConfig {
+        ReserveConfig {
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 75,
+            liquidation_bonus: 5,
+            liquidation_threshold: 80,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 8,
+            max_borrow_rate: 50,
+            ..ReserveConfig::default()
+        }
+    }
+
+    #[test]
+    fn reserve_config_bounds_unbounded() {
+        let bounds = ReserveConfigBounds::default();
+        let config = test_config();
+        let new_config = ReserveConfig {
+            loan_to_value_ratio: 0,
+            liquidation_threshold: 1,
+            ..config
+        };
+        assert!(bounds.validate_change(&config, &new_config, 10, 10).is_ok());
+    }
+
+    #[test]
+    fn reserve_config_bounds_steps() {
+        let bounds = ReserveConfigBounds {
+            max_loan_to_value_ratio_step: 5,
+            max_liquidation_threshold_step: 5,
+            max_liquidation_bonus_step: 2,
+            max_borrow_rate_step: 10,
+            min_update_interval_slots: 0,
+        };
+        let config = test_config();
+
+        let new_config = ReserveConfig {
+            loan_to_value_ratio: 70,
+            liquidation_threshold: 85,
+            liquidation_bonus: 7,
+            optimal_borrow_rate: 18,
+            max_borrow_rate: 40,
+            ..config
+        };
+        assert!(bounds.validate_change(&config, &new_config, 0, 0).is_ok());
+
+        for new_config in [
+            ReserveConfig {
+                loan_to_value_ratio: 69,
+                ..config
+            },
+            ReserveConfig {
+                liquidation_threshold: 1,
+                ..config
+            },
+            ReserveConfig {
+                liquidation_bonus: 8,
+                ..config
+            },
+            ReserveConfig {
+                min_borrow_rate: 11,
+                ..config
+            },
+            ReserveConfig {
+                optimal_borrow_rate: 19,
+                ..config
+            },
+            ReserveConfig {
+                max_borrow_rate: 39,
+                ..config
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(
+                bounds.validate_change(&config, new_config, 0, 0),
+                Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+            );
+        }
+
+        // ramps cannot start in the past or be shorter than the minimum update interval
+        let bounds = ReserveConfigBounds {
+            min_update_interval_slots: 50,
+            ..bounds
+        };
+        let ramp_config = |start_slot, end_slot| ReserveConfig {
+            loan_to_value_ratio_ramp: ReserveRamp {
+                target: 70,
+                start_slot,
+                end_slot,
+            },
+            ..config
+        };
+        assert!(bounds
+            .validate_change(&config, &ramp_config(100, 150), 0, 100)
+            .is_ok());
+        assert_eq!(
+            bounds.validate_change(&config, &ramp_config(99, 150), 0, 100),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+        assert_eq!(
+            bounds.validate_change(&config, &ramp_config(100, 149), 0, 100),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn reserve_config_bounds_interval() {
+        let bounds = ReserveConfigBounds {
+            min_update_interval_slots: 100,
+            ..ReserveConfigBounds::default()
+        };
+        let config = test_config();
+        let new_config = ReserveConfig {
+            liquidation_threshold: 79,
+            ..config
+        };
+
+        assert_eq!(
+            bounds.validate_change(&config, &new_config, 50, 149),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+        assert!(bounds
+            .validate_change(&config, &new_config, 50, 150)
+            .is_ok());
+
+        // changes to unbounded parameters are not rate limited
+        let new_config = ReserveConfig {
+            deposit_limit: 1,
+            ..config
+        };
+        assert!(bounds.validate_change(&config, &new_config, 50, 51).is_ok());
+    }
+
+    #[test]
+    fn reserve_config_bounds_ramps() {
+        let bounds = ReserveConfigBounds {
+            max_loan_to_value_ratio_step: 5,
+            max_liquidation_threshold_step: 5,
+            ..ReserveConfigBounds::default()
+        };
+        let config = test_config();
+
+        let new_config = ReserveConfig {
+            loan_to_value_ratio_ramp: ReserveRamp {
+                target: 70,
+                start_slot: 0,
+                end_slot: 100,
+            },
+            liquidation_threshold_ramp: ReserveRamp {
+                target: 85,
+                start_slot: 0,
+                end_slot: 100,
+            },
+            ..config
+        };
+        assert!(bounds.validate_change(&config, &new_config, 0, 0).is_ok());
+
+        for new_config in [
+            ReserveConfig {
+                loan_to_value_ratio_ramp: ReserveRamp {
+                    target: 69,
+                    start_slot: 0,
+                    end_slot: 100,
+                },
+                ..config
+            },
+            ReserveConfig {
+                liquidation_threshold_ramp: ReserveRamp {
+                    target: 86,
+                    start_slot: 0,
+                    end_slot: 100,
+                },
+                ..config
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(
+                bounds.validate_change(&config, new_config, 0, 0),
+                Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+            );
+        }
+
+        // ramps cannot start in the past or be shorter than the minimum update interval
+        let bounds = ReserveConfigBounds {
+            min_update_interval_slots: 50,
+            ..bounds
+        };
+        let ramp_config = |start_slot, end_slot| ReserveConfig {
+            loan_to_value_ratio_ramp: ReserveRamp {
+                target: 70,
+                start_slot,
+                end_slot,
+            },
+            ..config
+        };
+        assert!(bounds
+            .validate_change(&config, &ramp_config(100, 150), 0, 100)
+            .is_ok());
+        assert_eq!(
+            bounds.validate_change(&config, &ramp_config(99, 150), 0, 100),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+        assert_eq!(
+            bounds.validate_change(&config, &ramp_config(100, 149), 0, 100),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn set_reserve_config_bounds() {
+        let bounds = ReserveConfigBounds {
+            max_loan_to_value_ratio_step: 5,
+            max_liquidation_threshold_step: 5,
+            max_liquidation_bonus_step: 2,
+            max_borrow_rate_step: 10,
+            min_update_interval_slots: 100,
+        };
+        let mut lending_market = LendingMarket::default();
+
+        // tightening takes effect immediately
+        lending_market
+            .set_reserve_config_bounds(bounds, 10)
+            .unwrap();
+        assert_eq!(lending_market.reserve_config_bounds(10), bounds);
+
+        // loosening or removing a bound waits for the minimum update interval
+        for loosened_bounds in [
+            ReserveConfigBounds {
+                max_liquidation_threshold_step: 79,
+                ..bounds
+            },
+            ReserveConfigBounds {
+                max_loan_to_value_ratio_step: 0,
+                ..bounds
+            },
+            ReserveConfigBounds {
+                min_update_interval_slots: 0,
+                ..bounds
+            },
+            ReserveConfigBounds::default(),
+        ]
+        .iter()
+        {
+            let mut lending_market = lending_market.clone();
+            lending_market
+                .set_reserve_config_bounds(*loosened_bounds, 20)
+                .unwrap();
+            assert_eq!(lending_market.reserve_config_bounds(119), bounds);
+            assert_eq!(lending_market.reserve_config_bounds(120), *loosened_bounds);
+        }
+
+        // tightening cancels pending loosened bounds
+        lending_market
+            .set_reserve_config_bounds(ReserveConfigBounds::default(), 20)
+            .unwrap();
+        let tightened_bounds = ReserveConfigBounds {
+            max_borrow_rate_step: 5,
+            ..bounds
+        };
+        lending_market
+            .set_reserve_config_bounds(tightened_bounds, 30)
+            .unwrap();
+        assert_eq!(lending_market.reserve_config_bounds(30), tightened_bounds);
+        assert_eq!(lending_market.reserve_config_bounds(120), tightened_bounds);
+    }
+
+    #[test]
+    fn set_liquidation_config() {
+        let config = LiquidationConfig {
+            close_factor: 30,
+            full_liquidation_threshold: 90,
+            full_liquidation_dust_value: 5,
+            close_amount: 2,
+        };
+        let mut lending_market = LendingMarket::default();
+        lending_market
+            .set_reserve_config_bounds(
+                ReserveConfigBounds {
+                    max_liquidation_threshold_step: 10,
+                    min_update_interval_slots: 100,
+                    ..ReserveConfigBounds::default()
+                },
+                0,
+            )
+            .unwrap();
+
+        // close factor step is measured from the default close factor
+        assert_eq!(
+            lending_market.set_liquidation_config(
+                LiquidationConfig {
+                    close_factor: 31,
+                    ..config
+                },
+                100
+            ),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+
+        // without a delay the config takes effect immediately
+        lending_market.set_liquidation_config(config, 100).unwrap();
+        assert_eq!(lending_market.liquidation_config(100), config);
+
+        // changes wait for the minimum update interval
+        let new_config = LiquidationConfig {
+            full_liquidation_threshold: 80,
+            ..config
+        };
+        assert_eq!(
+            lending_market.set_liquidation_config(new_config, 199),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+
+        // with a delay the config takes effect once the delay has elapsed
+        lending_market.set_reserve_config_delay(50, 199).unwrap();
+        lending_market
+            .set_liquidation_config(new_config, 200)
+            .unwrap();
+        assert_eq!(lending_market.liquidation_config(249), config);
+        assert_eq!(lending_market.liquidation_config(250), new_config);
+    }
+
+    #[test]
+    fn liquidation_config_close_factor() {
+        let obligation = Obligation {
+            borrowed_value: Decimal::from(90u64),
+            unhealthy_borrow_value: Decimal::from(80u64),
+            ..Obligation::default()
+        };
+
+        let config = LiquidationConfig::default();
+        assert_eq!(
+            config.close_factor(&obligation).unwrap(),
+            Rate::from_percent(LIQUIDATION_CLOSE_FACTOR)
+        );
+        assert_eq!(config.close_amount(), LIQUIDATION_CLOSE_AMOUNT);
+
+        let config = LiquidationConfig {
+            close_factor: 50,
+            close_amount: 10,
+            ..LiquidationConfig::default()
+        };
+        assert_eq!(
+            config.close_factor(&obligation).unwrap(),
+            Rate::from_percent(50)
+        );
+        assert_eq!(config.close_amount(), 10);
+
+        // 90 / 80 = 112.5% of the unhealthy borrow value
+        let config = LiquidationConfig {
+            full_liquidation_threshold: 113,
+            ..LiquidationConfig::default()
+        };
+        assert!(!config.is_full_liquidation(&obligation).unwrap());
+        let config = LiquidationConfig {
+            full_liquidation_threshold: 112,
+            ..LiquidationConfig::default()
+        };
+        assert_eq!(config.close_factor(&obligation).unwrap(), Rate::one());
+
+        let config = LiquidationConfig {
+            full_liquidation_dust_value: 90,
+            ..LiquidationConfig::default()
+        };
+        assert!(!config.is_full_liquidation(&obligation).unwrap());
+        let config = LiquidationConfig {
+            full_liquidation_dust_value: 91,
+            ..LiquidationConfig::default()
+        };
+        assert!(config.is_full_liquidation(&obligation).unwrap());
+    }
+
+    #[test]
+    fn liquidation_config_validate() {
+        assert!(LiquidationConfig::default().validate().is_ok());
+        for config in [
+            LiquidationConfig {
+                close_factor: 101,
+                ..LiquidationConfig::default()
+            },
+            LiquidationConfig {
+                full_liquidation_threshold: 99,
+                ..LiquidationConfig::default()
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(config.validate(), Err(LendingError::InvalidConfig.into()));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+
+/// Seed used with the lending market and member addresses to derive the membership address
+pub const LENDING_MARKET_MEMBERSHIP_SEED: &[u8] = b"membership";
+
+/// Find the membership address of a member in a lending market
+pub fn find_lending_market_membership_address(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    member: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            lending_market.as_ref(),
+            member.as_ref(),
+            LENDING_MARKET_MEMBERSHIP_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Membership of a permissioned lending market
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LendingMarketMembership {
+    /// Version of the struct
+    pub version: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Member address which can open obligations and deposit
+    pub member: Pubkey,
+}
+
+impl LendingMarketMembership {
+    /// Create a new lending market membership
+    pub fn new(params: InitLendingMarketMembershipParams) -> Self {
+        let mut membership = Self::default();
+        Self::init(&mut membership, params);
+        membership
+    }
+
+    /// Initialize a lending market membership
+    pub fn init(&mut self, params: InitLendingMarketMembershipParams) {
+        self.version = PROGRAM_VERSION;
+        self.lending_market = params.lending_market;
+        self.member = params.member;
+    }
+}
+
+/// Initialize a lending market membership
+pub struct InitLendingMarketMembershipParams {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Member address which can open obligations and deposit
+    pub member: Pubkey,
+}
+
+impl Sealed for LendingMarketMembership {}
+impl IsInitialized for LendingMarketMembership {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const LENDING_MARKET_MEMBERSHIP_LEN: usize = 129; // 1 + 1 + 32 + 32 + 63
+impl Pack for LendingMarketMembership {
+    const LEN: usize = LENDING_MARKET_MEMBERSHIP_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, LENDING_MARKET_MEMBERSHIP_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, account_type, lending_market, member, _padding) =
+            mut_array_refs![output, 1, 1, PUBKEY_BYTES, PUBKEY_BYTES, 63];
+
+        *version = self.version.to_le_bytes();
+        *account_type = LENDING_MARKET_MEMBERSHIP_ACCOUNT_TYPE.to_le_bytes();
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        member.copy_from_slice(self.member.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [LendingMarketMembership](struct.LendingMarketMembership.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, LENDING_MARKET_MEMBERSHIP_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, account_type, lending_market, member, _padding) =
+            array_refs![input, 1, 1, PUBKEY_BYTES, PUBKEY_BYTES, 63];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Lending market membership version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if version != UNINITIALIZED_VERSION
+            && u8::from_le_bytes(*account_type) != LENDING_MARKET_MEMBERSHIP_ACCOUNT_TYPE
+        {
+            msg!("Lending market membership account type does not match");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            lending_market: Pubkey::new_from_array(*lending_market),
+            member: Pubkey::new_from_array(*member),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unpack_swap_program_approval() {
+        let swap_program_approval = SwapProgramApproval::new(InitSwapProgramApprovalParams {
+            lending_market: Pubkey::new_unique(),
+            swap_program_id: Pubkey::new_unique(),
+        });
+        let mut packed = [0u8; SwapProgramApproval::LEN];
+        SwapProgramApproval::pack(swap_program_approval, &mut packed).unwrap();
+        assert_eq!(
+            LendingMarketMembership::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let lending_market_membership =
+            LendingMarketMembership::new(InitLendingMarketMembershipParams {
+                lending_market: Pubkey::new_unique(),
+                member: Pubkey::new_unique(),
+            });
+        let mut packed = [0u8; LendingMarketMembership::LEN];
+        LendingMarketMembership::pack(lending_market_membership.clone(), &mut packed).unwrap();
+        assert_eq!(
+            LendingMarketMembership::unpack(&packed),
+            Ok(lending_market_membership)
+        );
+        assert_eq!(
+            SwapProgramApproval::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
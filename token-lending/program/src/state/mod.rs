@@ -1,14 +1,26 @@
 //! State types
 
+mod borrow_allowance;
+mod flash_loan_fee_exemptions;
 mod last_update;
 mod lending_market;
+mod lending_market_membership;
 mod obligation;
+mod obligation_delegation;
+mod pending_reserve_config;
 mod reserve;
+mod swap_program_approval;
 
+pub use borrow_allowance::*;
+pub use flash_loan_fee_exemptions::*;
 pub use last_update::*;
 pub use lending_market::*;
+pub use lending_market_membership::*;
 pub use obligation::*;
+pub use obligation_delegation::*;
+pub use pending_reserve_config::*;
 pub use reserve::*;
+pub use swap_program_approval::*;
 
 use crate::math::{Decimal, WAD};
 use solana_program::{msg, program_error::ProgramError};
@@ -25,6 +37,13 @@ pub const PROGRAM_VERSION: u8 = 1;
 /// will have the version set to 0.
 pub const UNINITIALIZED_VERSION: u8 = 0;
 
+/// Account type of lending market memberships, which share their layout with swap program
+/// approvals
+const LENDING_MARKET_MEMBERSHIP_ACCOUNT_TYPE: u8 = 1;
+/// Account type of swap program approvals, which share their layout with lending market
+/// memberships
+const SWAP_PROGRAM_APPROVAL_ACCOUNT_TYPE: u8 = 2;
+
 /// Number of slots per year
 // 2 (slots per second) * 60 * 60 * 24 * 365 = 63072000
 pub const SLOTS_PER_YEAR: u64 = 63072000;
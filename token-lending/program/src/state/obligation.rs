@@ -20,6 +20,27 @@ use std::{
 /// Max number of collateral and liquidity reserve accounts combined for an obligation
 pub const MAX_OBLIGATION_RESERVES: usize = 10;
 
+/// Seed used with the lending market, owner and seed index to derive a seeded obligation address
+pub const OBLIGATION_SEED: &[u8] = b"obligation";
+
+/// Find the address of an owner's seeded obligation in a lending market
+pub fn find_obligation_address(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    owner: &Pubkey,
+    seed: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            lending_market.as_ref(),
+            owner.as_ref(),
+            &seed.to_le_bytes(),
+            OBLIGATION_SEED,
+        ],
+        program_id,
+    )
+}
+
 /// Lending market obligation state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Obligation {
@@ -43,6 +64,13 @@ pub struct Obligation {
     pub allowed_borrow_value: Decimal,
     /// The dangerous borrow value at the weighted average liquidation threshold
     pub unhealthy_borrow_value: Decimal,
+    /// Owner proposed by a two-step ownership transfer - default pubkey if none
+    pub pending_owner: Pubkey,
+    /// Number of obligation delegations and borrow allowances granted by the owner, which block
+    /// ownership transfers
+    pub delegations_count: u8,
+    /// Whether the obligation address is derived from its owner, which blocks ownership transfers
+    pub seeded: bool,
 }
 
 impl Obligation {
@@ -61,6 +89,9 @@ impl Obligation {
         self.owner = params.owner;
         self.deposits = params.deposits;
         self.borrows = params.borrows;
+        self.pending_owner = Pubkey::default();
+        self.delegations_count = 0;
+        self.seeded = params.seeded;
     }
 
     /// Calculate the current ratio of borrowed value to deposited value
@@ -111,10 +142,11 @@ impl Obligation {
     pub fn max_liquidation_amount(
         &self,
         liquidity: &ObligationLiquidity,
+        liquidation_config: &LiquidationConfig,
     ) -> Result<Decimal, ProgramError> {
         let max_liquidation_value = self
             .borrowed_value
-            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
+            .try_mul(liquidation_config.close_factor(self)?)?
             .min(liquidity.market_value);
         let max_liquidation_pct = max_liquidation_value.try_div(liquidity.market_value)?;
         liquidity.borrowed_amount_wads.try_mul(max_liquidation_pct)
@@ -231,6 +263,8 @@ pub struct InitObligationParams {
     pub deposits: Vec<ObligationCollateral>,
     /// Borrowed liquidity for the obligation, unique by borrow reserve address
     pub borrows: Vec<ObligationLiquidity>,
+    /// Whether the obligation address is derived from its owner
+    pub seeded: bool,
 }
 
 impl Sealed for Obligation {}
@@ -342,7 +376,7 @@ impl ObligationLiquidity {
 
 const OBLIGATION_COLLATERAL_LEN: usize = 88; // 32 + 8 + 16 + 32
 const OBLIGATION_LIQUIDITY_LEN: usize = 112; // 32 + 16 + 16 + 16 + 32
-const OBLIGATION_LEN: usize = 1300; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 64 + 1 + 1 + (88 * 1) + (112 * 9)
+const OBLIGATION_LEN: usize = 1300; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 32 + 1 + 1 + 30 + 1 + 1 + (88 * 1) + (112 * 9)
                                     // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
 impl Pack for Obligation {
     const LEN: usize = OBLIGATION_LEN;
@@ -360,6 +394,9 @@ impl Pack for Obligation {
             borrowed_value,
             allowed_borrow_value,
             unhealthy_borrow_value,
+            pending_owner,
+            delegations_count,
+            seeded,
             _padding,
             deposits_len,
             borrows_len,
@@ -375,7 +412,10 @@ impl Pack for Obligation {
             16,
             16,
             16,
-            64,
+            PUBKEY_BYTES,
+            1,
+            1,
+            30,
             1,
             1,
             OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
@@ -391,6 +431,9 @@ impl Pack for Obligation {
         pack_decimal(self.borrowed_value, borrowed_value);
         pack_decimal(self.allowed_borrow_value, allowed_borrow_value);
         pack_decimal(self.unhealthy_borrow_value, unhealthy_borrow_value);
+        pending_owner.copy_from_slice(self.pending_owner.as_ref());
+        *delegations_count = self.delegations_count.to_le_bytes();
+        pack_bool(self.seeded, seeded);
         *deposits_len = u8::try_from(self.deposits.len()).unwrap().to_le_bytes();
         *borrows_len = u8::try_from(self.borrows.len()).unwrap().to_le_bytes();
 
@@ -444,6 +487,9 @@ impl Pack for Obligation {
             borrowed_value,
             allowed_borrow_value,
             unhealthy_borrow_value,
+            pending_owner,
+            delegations_count,
+            seeded,
             _padding,
             deposits_len,
             borrows_len,
@@ -459,7 +505,10 @@ impl Pack for Obligation {
             16,
             16,
             16,
-            64,
+            PUBKEY_BYTES,
+            1,
+            1,
+            30,
             1,
             1,
             OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
@@ -522,6 +571,9 @@ impl Pack for Obligation {
             borrowed_value: unpack_decimal(borrowed_value),
             allowed_borrow_value: unpack_decimal(allowed_borrow_value),
             unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+            pending_owner: Pubkey::new_from_array(*pending_owner),
+            delegations_count: u8::from_le_bytes(*delegations_count),
+            seeded: unpack_bool(seeded)?,
         })
     }
 }
@@ -565,6 +617,25 @@ mod test {
         );
     }
 
+    #[test]
+    fn obligation_pack_and_unpack() {
+        let mut obligation = Obligation::new(InitObligationParams {
+            current_slot: 1,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![],
+            borrows: vec![],
+            seeded: true,
+        });
+        obligation.pending_owner = Pubkey::new_unique();
+        obligation.delegations_count = 2;
+
+        let mut packed = [0u8; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        let unpacked = Obligation::unpack(&packed).unwrap();
+        assert_eq!(unpacked, obligation);
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
@@ -0,0 +1,230 @@
+use super::*;
+use crate::{
+    error::LendingError,
+    math::{Decimal, TryAdd},
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+
+/// Seed used with the obligation and delegate addresses to derive the delegation address
+pub const OBLIGATION_DELEGATION_SEED: &[u8] = b"obligation_delegation";
+
+/// Delegate can repay borrows with collateral deposited in the same reserve, which only
+/// deleverages the obligation
+pub const DELEGATE_REPAY_WITH_COLLATERAL: u8 = 1;
+/// Delegate can borrow liquidity, up to the delegation value limit
+pub const DELEGATE_BORROW: u8 = 1 << 1;
+/// Delegate can withdraw collateral, up to the delegation value limit
+pub const DELEGATE_WITHDRAW: u8 = 1 << 2;
+const DELEGATE_ALL: u8 = DELEGATE_REPAY_WITH_COLLATERAL | DELEGATE_BORROW | DELEGATE_WITHDRAW;
+
+/// Find the delegation address of a delegate on an obligation
+pub fn find_obligation_delegation_address(
+    program_id: &Pubkey,
+    obligation: &Pubkey,
+    delegate: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            obligation.as_ref(),
+            delegate.as_ref(),
+            OBLIGATION_DELEGATION_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Rights granted by an obligation owner to a delegate signer
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObligationDelegation {
+    /// Version of the struct
+    pub version: u8,
+    /// Obligation address
+    pub obligation: Pubkey,
+    /// Delegate address
+    pub delegate: Pubkey,
+    /// Granted permissions, a combination of the `DELEGATE_*` flags
+    pub permissions: u8,
+    /// Market value in quote currency the delegate can borrow and withdraw over the lifetime of
+    /// the delegation
+    pub value_limit: Decimal,
+    /// Market value in quote currency borrowed and withdrawn by the delegate. Repayments and
+    /// deposits do not restore it, only SetObligationDelegation resets it to zero.
+    pub value_used: Decimal,
+}
+
+impl ObligationDelegation {
+    /// Create a new obligation delegation
+    pub fn new(params: InitObligationDelegationParams) -> Self {
+        let mut delegation = Self::default();
+        Self::init(&mut delegation, params);
+        delegation
+    }
+
+    /// Initialize an obligation delegation
+    pub fn init(&mut self, params: InitObligationDelegationParams) {
+        self.version = PROGRAM_VERSION;
+        self.obligation = params.obligation;
+        self.delegate = params.delegate;
+        self.permissions = params.permissions;
+        self.value_limit = params.value_limit;
+        self.value_used = Decimal::zero();
+    }
+
+    /// Check that permissions only contain known `DELEGATE_*` flags
+    pub fn validate_permissions(permissions: u8) -> ProgramResult {
+        if permissions == 0 || permissions & !DELEGATE_ALL != 0 {
+            msg!("Obligation delegation permissions are invalid");
+            return Err(LendingError::InvalidObligationDelegation.into());
+        }
+        Ok(())
+    }
+
+    /// Check if the delegation grants a `DELEGATE_*` permission
+    pub fn has_permission(&self, permission: u8) -> bool {
+        self.permissions & permission == permission
+    }
+
+    /// Add market value borrowed or withdrawn by the delegate, up to the value limit
+    pub fn use_value(&mut self, value: Decimal) -> ProgramResult {
+        let value_used = self.value_used.try_add(value)?;
+        if value_used > self.value_limit {
+            msg!(
+                "Delegated value used {} cannot exceed the delegation value limit {}",
+                value_used,
+                self.value_limit
+            );
+            return Err(LendingError::ObligationDelegationLimit.into());
+        }
+        self.value_used = value_used;
+        Ok(())
+    }
+}
+
+/// Initialize an obligation delegation
+pub struct InitObligationDelegationParams {
+    /// Obligation address
+    pub obligation: Pubkey,
+    /// Delegate address
+    pub delegate: Pubkey,
+    /// Granted permissions, a combination of the `DELEGATE_*` flags
+    pub permissions: u8,
+    /// Market value in quote currency the delegate can borrow and withdraw in total
+    pub value_limit: Decimal,
+}
+
+impl Sealed for ObligationDelegation {}
+impl IsInitialized for ObligationDelegation {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const OBLIGATION_DELEGATION_LEN: usize = 162; // 1 + 32 + 32 + 1 + 16 + 16 + 64
+impl Pack for ObligationDelegation {
+    const LEN: usize = OBLIGATION_DELEGATION_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, OBLIGATION_DELEGATION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, obligation, delegate, permissions, value_limit, value_used, _padding) =
+            mut_array_refs![output, 1, PUBKEY_BYTES, PUBKEY_BYTES, 1, 16, 16, 64];
+
+        *version = self.version.to_le_bytes();
+        obligation.copy_from_slice(self.obligation.as_ref());
+        delegate.copy_from_slice(self.delegate.as_ref());
+        *permissions = self.permissions.to_le_bytes();
+        pack_decimal(self.value_limit, value_limit);
+        pack_decimal(self.value_used, value_used);
+    }
+
+    /// Unpacks a byte buffer into an [ObligationDelegation](struct.ObligationDelegation.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, OBLIGATION_DELEGATION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, obligation, delegate, permissions, value_limit, value_used, _padding) =
+            array_refs![input, 1, PUBKEY_BYTES, PUBKEY_BYTES, 1, 16, 16, 64];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Obligation delegation version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            obligation: Pubkey::new_from_array(*obligation),
+            delegate: Pubkey::new_from_array(*delegate),
+            permissions: u8::from_le_bytes(*permissions),
+            value_limit: unpack_decimal(value_limit),
+            value_used: unpack_decimal(value_used),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_delegation(permissions: u8, value_limit: u64) -> ObligationDelegation {
+        ObligationDelegation::new(InitObligationDelegationParams {
+            obligation: Pubkey::new_unique(),
+            delegate: Pubkey::new_unique(),
+            permissions,
+            value_limit: Decimal::from(value_limit),
+        })
+    }
+
+    #[test]
+    fn validate_permissions() {
+        assert!(ObligationDelegation::validate_permissions(DELEGATE_BORROW).is_ok());
+        assert!(ObligationDelegation::validate_permissions(DELEGATE_ALL).is_ok());
+        assert_eq!(
+            ObligationDelegation::validate_permissions(0),
+            Err(LendingError::InvalidObligationDelegation.into())
+        );
+        assert_eq!(
+            ObligationDelegation::validate_permissions(1 << 3),
+            Err(LendingError::InvalidObligationDelegation.into())
+        );
+    }
+
+    #[test]
+    fn has_permission() {
+        let delegation = new_delegation(DELEGATE_REPAY_WITH_COLLATERAL | DELEGATE_BORROW, 0);
+        assert!(delegation.has_permission(DELEGATE_REPAY_WITH_COLLATERAL));
+        assert!(delegation.has_permission(DELEGATE_BORROW));
+        assert!(!delegation.has_permission(DELEGATE_WITHDRAW));
+    }
+
+    #[test]
+    fn use_value() {
+        let mut delegation = new_delegation(DELEGATE_BORROW, 100);
+        delegation.use_value(Decimal::from(60u64)).unwrap();
+        delegation.use_value(Decimal::from(40u64)).unwrap();
+        assert_eq!(delegation.value_used, Decimal::from(100u64));
+
+        assert_eq!(
+            delegation.use_value(Decimal::from(1u64)),
+            Err(LendingError::ObligationDelegationLimit.into())
+        );
+        assert_eq!(delegation.value_used, Decimal::from(100u64));
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let mut delegation = new_delegation(DELEGATE_BORROW | DELEGATE_WITHDRAW, 1_000);
+        delegation.use_value(Decimal::from(250u64)).unwrap();
+
+        let mut packed = [0u8; ObligationDelegation::LEN];
+        ObligationDelegation::pack(delegation.clone(), &mut packed).unwrap();
+        let unpacked = ObligationDelegation::unpack(&packed).unwrap();
+        assert_eq!(unpacked, delegation);
+    }
+}
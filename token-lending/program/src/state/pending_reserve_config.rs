@@ -0,0 +1,305 @@
+use super::*;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    clock::Slot,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+
+/// Seed used with the reserve address to derive the pending reserve config address
+pub const PENDING_RESERVE_CONFIG_SEED: &[u8] = b"pending_config";
+
+/// Find the pending reserve config address for a reserve
+pub fn find_pending_reserve_config_address(program_id: &Pubkey, reserve: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[reserve.as_ref(), PENDING_RESERVE_CONFIG_SEED], program_id)
+}
+
+/// Reserve config change waiting for the lending market delay to elapse
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PendingReserveConfig {
+    /// Version of the struct, uninitialized when no change is pending
+    pub version: u8,
+    /// Reserve the config change applies to
+    pub reserve: Pubkey,
+    /// Slot from which the config change can be applied
+    pub earliest_apply_slot: Slot,
+    /// Pyth price oracle to switch to
+    pub pyth_oracle_pubkey: Pubkey,
+    /// Switchboard price feed to switch to
+    pub switchboard_oracle_pubkey: Pubkey,
+    /// Reserve config to switch to
+    pub config: ReserveConfig,
+}
+
+impl PendingReserveConfig {
+    /// Create a new pending reserve config
+    pub fn new(params: InitPendingReserveConfigParams) -> Self {
+        let mut pending_reserve_config = Self::default();
+        Self::init(&mut pending_reserve_config, params);
+        pending_reserve_config
+    }
+
+    /// Initialize a pending reserve config
+    pub fn init(&mut self, params: InitPendingReserveConfigParams) {
+        self.version = PROGRAM_VERSION;
+        self.reserve = params.reserve;
+        self.earliest_apply_slot = params.earliest_apply_slot;
+        self.pyth_oracle_pubkey = params.pyth_oracle_pubkey;
+        self.switchboard_oracle_pubkey = params.switchboard_oracle_pubkey;
+        self.config = params.config;
+    }
+
+    /// Whether the config change can be applied at the given slot
+    pub fn can_apply(&self, slot: Slot) -> bool {
+        slot >= self.earliest_apply_slot
+    }
+}
+
+/// Initialize a pending reserve config
+pub struct InitPendingReserveConfigParams {
+    /// Reserve the config change applies to
+    pub reserve: Pubkey,
+    /// Slot from which the config change can be applied
+    pub earliest_apply_slot: Slot,
+    /// Pyth price oracle to switch to
+    pub pyth_oracle_pubkey: Pubkey,
+    /// Switchboard price feed to switch to
+    pub switchboard_oracle_pubkey: Pubkey,
+    /// Reserve config to switch to
+    pub config: ReserveConfig,
+}
+
+impl Sealed for PendingReserveConfig {}
+impl IsInitialized for PendingReserveConfig {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const PENDING_RESERVE_CONFIG_LEN: usize = 305; // 1 + 32 + 8 + 32 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 1 + 8 + 8 + 1 + 8 + 8 + 1 + 1 + 32 + 60
+impl Pack for PendingReserveConfig {
+    const LEN: usize = PENDING_RESERVE_CONFIG_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, PENDING_RESERVE_CONFIG_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version,
+            reserve,
+            earliest_apply_slot,
+            pyth_oracle_pubkey,
+            switchboard_oracle_pubkey,
+            config_optimal_utilization_rate,
+            config_loan_to_value_ratio,
+            config_liquidation_bonus,
+            config_liquidation_threshold,
+            config_min_borrow_rate,
+            config_optimal_borrow_rate,
+            config_max_borrow_rate,
+            config_fees_borrow_fee_wad,
+            config_fees_flash_loan_fee_wad,
+            config_fees_host_fee_percentage,
+            config_deposit_limit,
+            config_borrow_limit,
+            config_fee_receiver,
+            config_loan_to_value_ratio_ramp_target,
+            config_loan_to_value_ratio_ramp_start_slot,
+            config_loan_to_value_ratio_ramp_end_slot,
+            config_liquidation_threshold_ramp_target,
+            config_liquidation_threshold_ramp_start_slot,
+            config_liquidation_threshold_ramp_end_slot,
+            config_max_liquidation_bonus,
+            config_protocol_liquidation_fee,
+            config_collateral_fee_receiver,
+            _padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            PUBKEY_BYTES,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            1,
+            1,
+            PUBKEY_BYTES,
+            60
+        ];
+
+        *version = self.version.to_le_bytes();
+        reserve.copy_from_slice(self.reserve.as_ref());
+        *earliest_apply_slot = self.earliest_apply_slot.to_le_bytes();
+        pyth_oracle_pubkey.copy_from_slice(self.pyth_oracle_pubkey.as_ref());
+        switchboard_oracle_pubkey.copy_from_slice(self.switchboard_oracle_pubkey.as_ref());
+
+        // config
+        *config_optimal_utilization_rate = self.config.optimal_utilization_rate.to_le_bytes();
+        *config_loan_to_value_ratio = self.config.loan_to_value_ratio.to_le_bytes();
+        *config_liquidation_bonus = self.config.liquidation_bonus.to_le_bytes();
+        *config_liquidation_threshold = self.config.liquidation_threshold.to_le_bytes();
+        *config_min_borrow_rate = self.config.min_borrow_rate.to_le_bytes();
+        *config_optimal_borrow_rate = self.config.optimal_borrow_rate.to_le_bytes();
+        *config_max_borrow_rate = self.config.max_borrow_rate.to_le_bytes();
+        *config_fees_borrow_fee_wad = self.config.fees.borrow_fee_wad.to_le_bytes();
+        *config_fees_flash_loan_fee_wad = self.config.fees.flash_loan_fee_wad.to_le_bytes();
+        *config_fees_host_fee_percentage = self.config.fees.host_fee_percentage.to_le_bytes();
+        *config_deposit_limit = self.config.deposit_limit.to_le_bytes();
+        *config_borrow_limit = self.config.borrow_limit.to_le_bytes();
+        config_fee_receiver.copy_from_slice(self.config.fee_receiver.as_ref());
+        *config_loan_to_value_ratio_ramp_target =
+            self.config.loan_to_value_ratio_ramp.target.to_le_bytes();
+        *config_loan_to_value_ratio_ramp_start_slot = self
+            .config
+            .loan_to_value_ratio_ramp
+            .start_slot
+            .to_le_bytes();
+        *config_loan_to_value_ratio_ramp_end_slot =
+            self.config.loan_to_value_ratio_ramp.end_slot.to_le_bytes();
+        *config_liquidation_threshold_ramp_target =
+            self.config.liquidation_threshold_ramp.target.to_le_bytes();
+        *config_liquidation_threshold_ramp_start_slot = self
+            .config
+            .liquidation_threshold_ramp
+            .start_slot
+            .to_le_bytes();
+        *config_liquidation_threshold_ramp_end_slot = self
+            .config
+            .liquidation_threshold_ramp
+            .end_slot
+            .to_le_bytes();
+        *config_max_liquidation_bonus = self.config.max_liquidation_bonus.to_le_bytes();
+        *config_protocol_liquidation_fee = self.config.protocol_liquidation_fee.to_le_bytes();
+        config_collateral_fee_receiver
+            .copy_from_slice(self.config.collateral_fee_receiver.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [PendingReserveConfig](struct.PendingReserveConfig.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, PENDING_RESERVE_CONFIG_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version,
+            reserve,
+            earliest_apply_slot,
+            pyth_oracle_pubkey,
+            switchboard_oracle_pubkey,
+            config_optimal_utilization_rate,
+            config_loan_to_value_ratio,
+            config_liquidation_bonus,
+            config_liquidation_threshold,
+            config_min_borrow_rate,
+            config_optimal_borrow_rate,
+            config_max_borrow_rate,
+            config_fees_borrow_fee_wad,
+            config_fees_flash_loan_fee_wad,
+            config_fees_host_fee_percentage,
+            config_deposit_limit,
+            config_borrow_limit,
+            config_fee_receiver,
+            config_loan_to_value_ratio_ramp_target,
+            config_loan_to_value_ratio_ramp_start_slot,
+            config_loan_to_value_ratio_ramp_end_slot,
+            config_liquidation_threshold_ramp_target,
+            config_liquidation_threshold_ramp_start_slot,
+            config_liquidation_threshold_ramp_end_slot,
+            config_max_liquidation_bonus,
+            config_protocol_liquidation_fee,
+            config_collateral_fee_receiver,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            PUBKEY_BYTES,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            1,
+            1,
+            PUBKEY_BYTES,
+            60
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Pending reserve config version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            reserve: Pubkey::new_from_array(*reserve),
+            earliest_apply_slot: u64::from_le_bytes(*earliest_apply_slot),
+            pyth_oracle_pubkey: Pubkey::new_from_array(*pyth_oracle_pubkey),
+            switchboard_oracle_pubkey: Pubkey::new_from_array(*switchboard_oracle_pubkey),
+            config: ReserveConfig {
+                optimal_utilization_rate: u8::from_le_bytes(*config_optimal_utilization_rate),
+                loan_to_value_ratio: u8::from_le_bytes(*config_loan_to_value_ratio),
+                liquidation_bonus: u8::from_le_bytes(*config_liquidation_bonus),
+                liquidation_threshold: u8::from_le_bytes(*config_liquidation_threshold),
+                min_borrow_rate: u8::from_le_bytes(*config_min_borrow_rate),
+                optimal_borrow_rate: u8::from_le_bytes(*config_optimal_borrow_rate),
+                max_borrow_rate: u8::from_le_bytes(*config_max_borrow_rate),
+                fees: ReserveFees {
+                    borrow_fee_wad: u64::from_le_bytes(*config_fees_borrow_fee_wad),
+                    flash_loan_fee_wad: u64::from_le_bytes(*config_fees_flash_loan_fee_wad),
+                    host_fee_percentage: u8::from_le_bytes(*config_fees_host_fee_percentage),
+                },
+                deposit_limit: u64::from_le_bytes(*config_deposit_limit),
+                borrow_limit: u64::from_le_bytes(*config_borrow_limit),
+                fee_receiver: Pubkey::new_from_array(*config_fee_receiver),
+                loan_to_value_ratio_ramp: ReserveRamp {
+                    target: u8::from_le_bytes(*config_loan_to_value_ratio_ramp_target),
+                    start_slot: u64::from_le_bytes(*config_loan_to_value_ratio_ramp_start_slot),
+                    end_slot: u64::from_le_bytes(*config_loan_to_value_ratio_ramp_end_slot),
+                },
+                liquidation_threshold_ramp: ReserveRamp {
+                    target: u8::from_le_bytes(*config_liquidation_threshold_ramp_target),
+                    start_slot: u64::from_le_bytes(*config_liquidation_threshold_ramp_start_slot),
+                    end_slot: u64::from_le_bytes(*config_liquidation_threshold_ramp_end_slot),
+                },
+                max_liquidation_bonus: u8::from_le_bytes(*config_max_liquidation_bonus),
+                protocol_liquidation_fee: u8::from_le_bytes(*config_protocol_liquidation_fee),
+                collateral_fee_receiver: Pubkey::new_from_array(*config_collateral_fee_receiver),
+            },
+        })
+    }
+}
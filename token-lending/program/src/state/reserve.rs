@@ -17,10 +17,12 @@ use std::{
     convert::{TryFrom, TryInto},
 };
 
-/// Percentage of an obligation that can be repaid during each liquidation call
+/// Percentage of an obligation that can be repaid during each liquidation call, unless the
+/// lending market liquidation config overrides it
 pub const LIQUIDATION_CLOSE_FACTOR: u8 = 20;
 
-/// Obligation borrow amount that is small enough to close out
+/// Obligation borrow amount that is small enough to close out, unless the lending market
+/// liquidation config overrides it
 pub const LIQUIDATION_CLOSE_AMOUNT: u64 = 2;
 
 /// Lending market reserve state
@@ -38,6 +40,10 @@ pub struct Reserve {
     pub collateral: ReserveCollateral,
     /// Reserve configuration values
     pub config: ReserveConfig,
+    /// Last slot when a bounded reserve config parameter changed
+    pub last_config_update_slot: Slot,
+    /// Lifecycle status of the reserve
+    pub status: ReserveStatus,
 }
 
 impl Reserve {
@@ -58,6 +64,88 @@ impl Reserve {
         self.config = params.config;
     }
 
+    /// Update the reserve config within the lending market bounds
+    pub fn update_config(
+        &mut self,
+        config: ReserveConfig,
+        bounds: &ReserveConfigBounds,
+        slot: Slot,
+    ) -> ProgramResult {
+        bounds.validate_change(&self.config, &config, self.last_config_update_slot, slot)?;
+        if ReserveConfigBounds::is_bounded_change(&self.config, &config) {
+            self.last_config_update_slot = slot;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Loan to value ratio in effect at the given slot, as a percentage
+    pub fn loan_to_value_ratio(&self, slot: Slot) -> u8 {
+        self.config
+            .loan_to_value_ratio_ramp
+            .value(self.config.loan_to_value_ratio, slot)
+    }
+
+    /// Liquidation threshold in effect at the given slot, as a percentage
+    pub fn liquidation_threshold(&self, slot: Slot) -> u8 {
+        self.config
+            .liquidation_threshold_ramp
+            .value(self.config.liquidation_threshold, slot)
+    }
+
+    /// Write the target of finished ramps back into the reserve config
+    pub fn finish_ramps(&mut self, slot: Slot) {
+        if self.config.loan_to_value_ratio_ramp.is_finished(slot) {
+            self.config.loan_to_value_ratio = self.config.loan_to_value_ratio_ramp.target;
+            self.config.loan_to_value_ratio_ramp = ReserveRamp::default();
+        }
+        if self.config.liquidation_threshold_ramp.is_finished(slot) {
+            self.config.liquidation_threshold = self.config.liquidation_threshold_ramp.target;
+            self.config.liquidation_threshold_ramp = ReserveRamp::default();
+        }
+    }
+
+    /// Change the reserve status, scheduled ramps are kept. Reduce-only reserves ramp the loan
+    /// to value ratio down to the target over the given number of slots, within the lending
+    /// market bounds.
+    pub fn set_status(
+        &mut self,
+        status: ReserveStatus,
+        loan_to_value_ratio_target: u8,
+        ramp_slots: u64,
+        bounds: &ReserveConfigBounds,
+        slot: Slot,
+    ) -> ProgramResult {
+        self.finish_ramps(slot);
+
+        if status == ReserveStatus::ReduceOnly
+            && loan_to_value_ratio_target != self.config.loan_to_value_ratio
+        {
+            if loan_to_value_ratio_target > self.config.loan_to_value_ratio {
+                msg!("Loan to value ratio target cannot exceed the current loan to value ratio");
+                return Err(LendingError::InvalidConfig.into());
+            }
+            if self.config.loan_to_value_ratio_ramp.is_active() {
+                msg!("Loan to value ratio ramp must finish before another one can be started");
+                return Err(LendingError::ReserveRampInProgress.into());
+            }
+            let config = ReserveConfig {
+                loan_to_value_ratio_ramp: ReserveRamp {
+                    target: loan_to_value_ratio_target,
+                    start_slot: slot,
+                    end_slot: slot
+                        .checked_add(ramp_slots)
+                        .ok_or(LendingError::MathOverflow)?,
+                },
+                ..self.config
+            };
+            self.update_config(config, bounds, slot)?;
+        }
+
+        self.status = status;
+        Ok(())
+    }
+
     /// Record deposited liquidity and return amount of collateral tokens to mint
     pub fn deposit_liquidity(&mut self, liquidity_amount: u64) -> Result<u64, ProgramError> {
         let collateral_amount = self
@@ -82,6 +170,22 @@ impl Reserve {
         Ok(liquidity_amount)
     }
 
+    /// Burn collateral to settle borrowed liquidity of the same reserve, leaving the available
+    /// liquidity untouched
+    pub fn repay_with_collateral(
+        &mut self,
+        collateral_amount: u64,
+        settle_amount: Decimal,
+    ) -> ProgramResult {
+        self.collateral.burn(collateral_amount)?;
+        let safe_settle_amount = settle_amount.min(self.liquidity.borrowed_amount_wads);
+        self.liquidity.borrowed_amount_wads = self
+            .liquidity
+            .borrowed_amount_wads
+            .try_sub(safe_settle_amount)?;
+        Ok(())
+    }
+
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
@@ -212,6 +316,68 @@ impl Reserve {
         })
     }
 
+    /// Repay borrowed liquidity with deposited collateral of the same reserve, up to the borrowed
+    /// amount. Rounds in favor of the reserve so the collateral exchange rate never worsens.
+    pub fn calculate_repay_with_collateral(
+        &self,
+        amount_to_burn: u64,
+        deposited_amount: u64,
+        borrowed_amount: Decimal,
+    ) -> Result<CalculateRepayWithCollateralResult, ProgramError> {
+        let collateral_exchange_rate = self.collateral_exchange_rate()?;
+        let max_collateral_amount = amount_to_burn.min(deposited_amount);
+        let max_settle_amount = collateral_exchange_rate
+            .decimal_collateral_to_liquidity(max_collateral_amount.into())?;
+
+        if max_settle_amount >= borrowed_amount {
+            let collateral_amount = collateral_exchange_rate
+                .decimal_liquidity_to_collateral(borrowed_amount)?
+                .try_ceil_u64()?
+                .min(max_collateral_amount);
+            Ok(CalculateRepayWithCollateralResult {
+                collateral_amount,
+                settle_amount: borrowed_amount,
+            })
+        } else {
+            let settle_amount = max_settle_amount.try_floor_u64()?;
+            Ok(CalculateRepayWithCollateralResult {
+                collateral_amount: max_collateral_amount,
+                settle_amount: settle_amount.into(),
+            })
+        }
+    }
+
+    /// Liquidation bonus for an obligation, scaled from the min to the max bonus as the
+    /// obligation's borrowed value moves from its unhealthy borrow value to its deposited value
+    pub fn liquidation_bonus(&self, obligation: &Obligation) -> Result<Rate, ProgramError> {
+        let min_bonus = Rate::from_percent(self.config.liquidation_bonus);
+        if self.config.max_liquidation_bonus <= self.config.liquidation_bonus
+            || obligation.borrowed_value <= obligation.unhealthy_borrow_value
+        {
+            return Ok(min_bonus);
+        }
+        let max_bonus = Rate::from_percent(self.config.max_liquidation_bonus);
+        if obligation.borrowed_value >= obligation.deposited_value {
+            return Ok(max_bonus);
+        }
+
+        // unhealthy_borrow_value < borrowed_value < deposited_value
+        let health_pct = Rate::try_from(
+            obligation
+                .borrowed_value
+                .try_sub(obligation.unhealthy_borrow_value)?
+                .try_div(
+                    obligation
+                        .deposited_value
+                        .try_sub(obligation.unhealthy_borrow_value)?,
+                )?,
+        )?;
+        max_bonus
+            .try_sub(min_bonus)?
+            .try_mul(health_pct)?
+            .try_add(min_bonus)
+    }
+
     /// Liquidate some or all of an unhealthy obligation
     pub fn calculate_liquidation(
         &self,
@@ -219,8 +385,9 @@ impl Reserve {
         obligation: &Obligation,
         liquidity: &ObligationLiquidity,
         collateral: &ObligationCollateral,
+        liquidation_config: &LiquidationConfig,
     ) -> Result<CalculateLiquidationResult, ProgramError> {
-        let bonus_rate = Rate::from_percent(self.config.liquidation_bonus).try_add(Rate::one())?;
+        let bonus_rate = self.liquidation_bonus(obligation)?.try_add(Rate::one())?;
 
         let max_amount = if amount_to_liquidate == u64::MAX {
             liquidity.borrowed_amount_wads
@@ -233,7 +400,7 @@ impl Reserve {
         let withdraw_amount;
 
         // Close out obligations that are too small to liquidate normally
-        if liquidity.borrowed_amount_wads < LIQUIDATION_CLOSE_AMOUNT.into() {
+        if liquidity.borrowed_amount_wads < liquidation_config.close_amount().into() {
             // settle_amount is fixed, calculate withdraw_amount and repay_amount
             settle_amount = liquidity.borrowed_amount_wads;
 
@@ -259,7 +426,7 @@ impl Reserve {
         } else {
             // calculate settle_amount and withdraw_amount, repay_amount is settle_amount rounded
             let liquidation_amount = obligation
-                .max_liquidation_amount(liquidity)?
+                .max_liquidation_amount(liquidity, liquidation_config)?
                 .min(max_amount);
             let liquidation_pct = liquidation_amount.try_div(liquidity.borrowed_amount_wads)?;
             let liquidation_value = liquidity
@@ -290,12 +457,33 @@ impl Reserve {
             }
         }
 
+        let protocol_liquidation_fee =
+            self.calculate_protocol_liquidation_fee(withdraw_amount, bonus_rate)?;
+
         Ok(CalculateLiquidationResult {
             settle_amount,
             repay_amount,
             withdraw_amount,
+            protocol_liquidation_fee,
         })
     }
+
+    /// Calculate the protocol's share of the bonus included in a liquidation withdraw amount
+    pub fn calculate_protocol_liquidation_fee(
+        &self,
+        withdraw_amount: u64,
+        bonus_rate: Rate,
+    ) -> Result<u64, ProgramError> {
+        if self.config.protocol_liquidation_fee == 0 {
+            return Ok(0);
+        }
+        let bonus_amount = Decimal::from(withdraw_amount)
+            .try_mul(bonus_rate.try_sub(Rate::one())?)?
+            .try_div(bonus_rate)?;
+        bonus_amount
+            .try_mul(Rate::from_percent(self.config.protocol_liquidation_fee))?
+            .try_ceil_u64()
+    }
 }
 
 /// Initialize a reserve
@@ -334,6 +522,15 @@ pub struct CalculateRepayResult {
     pub repay_amount: u64,
 }
 
+/// Calculate repay with collateral result
+#[derive(Debug)]
+pub struct CalculateRepayWithCollateralResult {
+    /// Amount of collateral that is burned from the obligation
+    pub collateral_amount: u64,
+    /// Amount of liquidity that is settled from the obligation
+    pub settle_amount: Decimal,
+}
+
 /// Calculate liquidation result
 #[derive(Debug)]
 pub struct CalculateLiquidationResult {
@@ -342,8 +539,11 @@ pub struct CalculateLiquidationResult {
     pub settle_amount: Decimal,
     /// Amount that will be repaid as u64
     pub repay_amount: u64,
-    /// Amount of collateral to withdraw in exchange for repay amount
+    /// Amount of collateral to withdraw in exchange for repay amount, including the protocol
+    /// liquidation fee
     pub withdraw_amount: u64,
+    /// Portion of the withdraw amount paid to the collateral fee receiver
+    pub protocol_liquidation_fee: u64,
 }
 
 /// Reserve liquidity
@@ -599,6 +799,13 @@ pub struct ReserveConfig {
     pub loan_to_value_ratio: u8,
     /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage
     pub liquidation_bonus: u8,
+    /// Bonus a liquidator gets once an obligation's borrowed value reaches its deposited value,
+    /// as a percentage. The bonus grows linearly from `liquidation_bonus` as the obligation
+    /// becomes less healthy, a value at or below `liquidation_bonus` keeps the bonus flat.
+    pub max_liquidation_bonus: u8,
+    /// Share of the liquidation bonus paid to the collateral fee receiver instead of the
+    /// liquidator, as a percentage
+    pub protocol_liquidation_fee: u8,
     /// Loan to value ratio at which an obligation can be liquidated, as a percentage
     pub liquidation_threshold: u8,
     /// Min borrow APY
@@ -615,6 +822,100 @@ pub struct ReserveConfig {
     pub borrow_limit: u64,
     /// Reserve liquidity fee receiver address
     pub fee_receiver: Pubkey,
+    /// Reserve collateral fee receiver address, receives the protocol liquidation fee
+    pub collateral_fee_receiver: Pubkey,
+    /// Schedule moving the loan to value ratio to a target
+    pub loan_to_value_ratio_ramp: ReserveRamp,
+    /// Schedule moving the liquidation threshold to a target
+    pub liquidation_threshold_ramp: ReserveRamp,
+}
+
+impl ReserveConfig {
+    /// Whether moving from this config to the new config only reduces risk, i.e. it lowers the
+    /// loan to value ratio or limits and leaves everything else untouched
+    pub fn is_risk_reducing(&self, new_config: &ReserveConfig) -> bool {
+        new_config.loan_to_value_ratio <= self.loan_to_value_ratio
+            && new_config.deposit_limit <= self.deposit_limit
+            && new_config.borrow_limit <= self.borrow_limit
+            && *new_config
+                == ReserveConfig {
+                    loan_to_value_ratio: new_config.loan_to_value_ratio,
+                    deposit_limit: new_config.deposit_limit,
+                    borrow_limit: new_config.borrow_limit,
+                    ..*self
+                }
+    }
+}
+
+/// Lifecycle status of a reserve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReserveStatus {
+    /// Deposits and borrows are allowed
+    #[default]
+    Active = 0,
+    /// Positions can only be reduced, new deposits and borrows are rejected
+    ReduceOnly = 1,
+    /// Only repayments and liquidations are allowed
+    Frozen = 2,
+}
+
+impl TryFrom<u8> for ReserveStatus {
+    type Error = ProgramError;
+
+    fn try_from(status: u8) -> Result<Self, Self::Error> {
+        match status {
+            0 => Ok(Self::Active),
+            1 => Ok(Self::ReduceOnly),
+            2 => Ok(Self::Frozen),
+            _ => {
+                msg!("Reserve status is invalid");
+                Err(LendingError::InvalidReserveStatus.into())
+            }
+        }
+    }
+}
+
+/// Linear change of a reserve config percentage over a range of slots
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReserveRamp {
+    /// Value reached at the end of the ramp, as a percentage
+    pub target: u8,
+    /// Slot the ramp starts at
+    pub start_slot: Slot,
+    /// Slot the ramp ends at, 0 if there is no ramp
+    pub end_slot: Slot,
+}
+
+impl ReserveRamp {
+    /// Whether a ramp is set
+    pub fn is_active(&self) -> bool {
+        self.end_slot != 0
+    }
+
+    /// Whether the ramp has reached its target at the given slot
+    pub fn is_finished(&self, slot: Slot) -> bool {
+        self.is_active() && slot >= self.end_slot
+    }
+
+    /// Value at the given slot, moving linearly from the initial value to the target
+    pub fn value(&self, initial: u8, slot: Slot) -> u8 {
+        if !self.is_active() || slot <= self.start_slot {
+            return initial;
+        }
+        if slot >= self.end_slot {
+            return self.target;
+        }
+
+        let elapsed = (slot - self.start_slot) as u128;
+        let duration = (self.end_slot - self.start_slot) as u128;
+        let distance = (initial.max(self.target) - initial.min(self.target)) as u128;
+        let change = (distance * elapsed / duration) as u8;
+        if self.target < initial {
+            initial - change
+        } else {
+            initial + change
+        }
+    }
 }
 
 /// Additional fee information on a reserve
@@ -652,12 +953,25 @@ impl ReserveFees {
     pub fn calculate_flash_loan_fees(
         &self,
         flash_loan_amount: Decimal,
+        fee_calculation: FeeCalculation,
     ) -> Result<(u64, u64), ProgramError> {
-        self.calculate_fees(
-            flash_loan_amount,
-            self.flash_loan_fee_wad,
-            FeeCalculation::Exclusive,
-        )
+        self.calculate_fees(flash_loan_amount, self.flash_loan_fee_wad, fee_calculation)
+    }
+
+    /// Calculate the owner and host fees on flash loan, after a fee discount as a percentage
+    pub fn calculate_discounted_flash_loan_fees(
+        &self,
+        flash_loan_amount: Decimal,
+        fee_discount: u8,
+        fee_calculation: FeeCalculation,
+    ) -> Result<(u64, u64), ProgramError> {
+        let fee_discount = u64::from(fee_discount.min(100));
+        let flash_loan_fee_wad = u128::from(self.flash_loan_fee_wad)
+            .checked_mul(u128::from(100 - fee_discount))
+            .and_then(|fee_wad| fee_wad.checked_div(100))
+            .and_then(|fee_wad| u64::try_from(fee_wad).ok())
+            .ok_or(LendingError::MathOverflow)?;
+        self.calculate_fees(flash_loan_amount, flash_loan_fee_wad, fee_calculation)
     }
 
     fn calculate_fees(
@@ -725,7 +1039,7 @@ impl IsInitialized for Reserve {
     }
 }
 
-const RESERVE_LEN: usize = 619; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 248
+const RESERVE_LEN: usize = 619; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 1 + 1 + 32 + 171
 impl Pack for Reserve {
     const LEN: usize = RESERVE_LEN;
 
@@ -763,6 +1077,17 @@ impl Pack for Reserve {
             config_deposit_limit,
             config_borrow_limit,
             config_fee_receiver,
+            last_config_update_slot,
+            status,
+            config_loan_to_value_ratio_ramp_target,
+            config_loan_to_value_ratio_ramp_start_slot,
+            config_loan_to_value_ratio_ramp_end_slot,
+            config_liquidation_threshold_ramp_target,
+            config_liquidation_threshold_ramp_start_slot,
+            config_liquidation_threshold_ramp_end_slot,
+            config_max_liquidation_bonus,
+            config_protocol_liquidation_fee,
+            config_collateral_fee_receiver,
             _padding,
         ) = mut_array_refs![
             output,
@@ -795,7 +1120,18 @@ impl Pack for Reserve {
             8,
             8,
             PUBKEY_BYTES,
-            248
+            8,
+            1,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            1,
+            1,
+            PUBKEY_BYTES,
+            171
         ];
 
         // reserve
@@ -841,6 +1177,34 @@ impl Pack for Reserve {
         *config_deposit_limit = self.config.deposit_limit.to_le_bytes();
         *config_borrow_limit = self.config.borrow_limit.to_le_bytes();
         config_fee_receiver.copy_from_slice(self.config.fee_receiver.as_ref());
+
+        *last_config_update_slot = self.last_config_update_slot.to_le_bytes();
+        *status = (self.status as u8).to_le_bytes();
+        *config_loan_to_value_ratio_ramp_target =
+            self.config.loan_to_value_ratio_ramp.target.to_le_bytes();
+        *config_loan_to_value_ratio_ramp_start_slot = self
+            .config
+            .loan_to_value_ratio_ramp
+            .start_slot
+            .to_le_bytes();
+        *config_loan_to_value_ratio_ramp_end_slot =
+            self.config.loan_to_value_ratio_ramp.end_slot.to_le_bytes();
+        *config_liquidation_threshold_ramp_target =
+            self.config.liquidation_threshold_ramp.target.to_le_bytes();
+        *config_liquidation_threshold_ramp_start_slot = self
+            .config
+            .liquidation_threshold_ramp
+            .start_slot
+            .to_le_bytes();
+        *config_liquidation_threshold_ramp_end_slot = self
+            .config
+            .liquidation_threshold_ramp
+            .end_slot
+            .to_le_bytes();
+        *config_max_liquidation_bonus = self.config.max_liquidation_bonus.to_le_bytes();
+        *config_protocol_liquidation_fee = self.config.protocol_liquidation_fee.to_le_bytes();
+        config_collateral_fee_receiver
+            .copy_from_slice(self.config.collateral_fee_receiver.as_ref());
     }
 
     /// Unpacks a byte buffer into a [ReserveInfo](struct.ReserveInfo.html).
@@ -877,6 +1241,17 @@ impl Pack for Reserve {
             config_deposit_limit,
             config_borrow_limit,
             config_fee_receiver,
+            last_config_update_slot,
+            status,
+            config_loan_to_value_ratio_ramp_target,
+            config_loan_to_value_ratio_ramp_start_slot,
+            config_loan_to_value_ratio_ramp_end_slot,
+            config_liquidation_threshold_ramp_target,
+            config_liquidation_threshold_ramp_start_slot,
+            config_liquidation_threshold_ramp_end_slot,
+            config_max_liquidation_bonus,
+            config_protocol_liquidation_fee,
+            config_collateral_fee_receiver,
             _padding,
         ) = array_refs![
             input,
@@ -909,7 +1284,18 @@ impl Pack for Reserve {
             8,
             8,
             PUBKEY_BYTES,
-            248
+            8,
+            1,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            1,
+            1,
+            PUBKEY_BYTES,
+            171
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -959,7 +1345,22 @@ impl Pack for Reserve {
                 deposit_limit: u64::from_le_bytes(*config_deposit_limit),
                 borrow_limit: u64::from_le_bytes(*config_borrow_limit),
                 fee_receiver: Pubkey::new_from_array(*config_fee_receiver),
+                loan_to_value_ratio_ramp: ReserveRamp {
+                    target: u8::from_le_bytes(*config_loan_to_value_ratio_ramp_target),
+                    start_slot: u64::from_le_bytes(*config_loan_to_value_ratio_ramp_start_slot),
+                    end_slot: u64::from_le_bytes(*config_loan_to_value_ratio_ramp_end_slot),
+                },
+                liquidation_threshold_ramp: ReserveRamp {
+                    target: u8::from_le_bytes(*config_liquidation_threshold_ramp_target),
+                    start_slot: u64::from_le_bytes(*config_liquidation_threshold_ramp_start_slot),
+                    end_slot: u64::from_le_bytes(*config_liquidation_threshold_ramp_end_slot),
+                },
+                max_liquidation_bonus: u8::from_le_bytes(*config_max_liquidation_bonus),
+                protocol_liquidation_fee: u8::from_le_bytes(*config_protocol_liquidation_fee),
+                collateral_fee_receiver: Pubkey::new_from_array(*config_collateral_fee_receiver),
             },
+            last_config_update_slot: u64::from_le_bytes(*last_config_update_slot),
+            status: ReserveStatus::try_from(u8::from_le_bytes(*status))?,
         })
     }
 }
@@ -1221,7 +1622,7 @@ mod test {
                 flash_loan_fee_wad,
                 host_fee_percentage,
             };
-            let (total_fee, host_fee) = fees.calculate_flash_loan_fees(Decimal::from(borrow_amount))?;
+            let (total_fee, host_fee) = fees.calculate_flash_loan_fees(Decimal::from(borrow_amount), FeeCalculation::Exclusive)?;
 
             // The total fee can't be greater than the amount borrowed, as long
             // as amount borrowed is greater than 2.
@@ -1251,6 +1652,81 @@ mod test {
         }
     }
 
+    proptest! {
+        #[test]
+        fn repay_with_collateral(
+            // token amounts small enough that collateral exchange rate precision is not a factor
+            available_amount in 0..=u32::MAX as u64,
+            borrowed_amount in 1..=u32::MAX as u64,
+            mint_total_supply in 1..=u32::MAX as u64,
+            deposited_percent in 1..=100u64,
+            borrow_percent in 1..=100u64,
+            amount_to_burn in 0..=u64::MAX,
+        ) {
+            let mut reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    available_amount,
+                    borrowed_amount_wads: Decimal::from(borrowed_amount),
+                    ..ReserveLiquidity::default()
+                },
+                collateral: ReserveCollateral {
+                    mint_total_supply,
+                    ..ReserveCollateral::default()
+                },
+                ..Reserve::default()
+            };
+            let deposited_amount = mint_total_supply / 100 * deposited_percent;
+            let obligation_borrowed_amount = Decimal::from(borrowed_amount / 100 * borrow_percent);
+            let exchange_rate = Rate::from(reserve.collateral_exchange_rate()?);
+
+            let CalculateRepayWithCollateralResult {
+                collateral_amount,
+                settle_amount,
+            } = reserve.calculate_repay_with_collateral(
+                amount_to_burn,
+                deposited_amount,
+                obligation_borrowed_amount,
+            )?;
+            assert!(collateral_amount <= amount_to_burn.min(deposited_amount));
+            assert!(settle_amount <= obligation_borrowed_amount);
+
+            reserve.repay_with_collateral(collateral_amount, settle_amount)?;
+            assert_eq!(reserve.liquidity.available_amount, available_amount);
+            if reserve.collateral.mint_total_supply > 0 && reserve.liquidity.total_supply()? > Decimal::zero() {
+                assert!(Rate::from(reserve.collateral_exchange_rate()?) <= exchange_rate);
+            }
+        }
+    }
+
+    #[test]
+    fn repay_with_collateral_full_and_partial() {
+        // 2 collateral per liquidity
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 50,
+                borrowed_amount_wads: Decimal::from(50u64),
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 200,
+                ..ReserveCollateral::default()
+            },
+            ..Reserve::default()
+        };
+
+        let result = reserve
+            .calculate_repay_with_collateral(u64::MAX, 100, Decimal::from(10u64))
+            .unwrap();
+        assert_eq!(result.collateral_amount, 20);
+        assert_eq!(result.settle_amount, Decimal::from(10u64));
+
+        let result = reserve
+            .calculate_repay_with_collateral(u64::MAX, 15, Decimal::from(10u64))
+            .unwrap();
+        assert_eq!(result.collateral_amount, 15);
+        assert_eq!(result.settle_amount, Decimal::from(7u64));
+    }
+
     #[test]
     fn borrow_fee_calculation_min_host() {
         let fees = ReserveFees {
@@ -1339,4 +1815,433 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn flash_loan_fee_calculation_inclusive() {
+        let fees = ReserveFees {
+            borrow_fee_wad: 0,
+            flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+            host_fee_percentage: 20,
+        };
+
+        let (total_fee, host_fee) = fees
+            .calculate_flash_loan_fees(Decimal::from(1_003_000u64), FeeCalculation::Inclusive)
+            .unwrap();
+
+        assert_eq!(total_fee, 3_000); // 0.3% of 1_000_000 borrowed
+        assert_eq!(host_fee, 600); // 20% of total fee
+
+        let (total_fee, host_fee) = fees
+            .calculate_flash_loan_fees(Decimal::from(1_000_000u64), FeeCalculation::Exclusive)
+            .unwrap();
+
+        assert_eq!(total_fee, 3_000);
+        assert_eq!(host_fee, 600);
+    }
+
+    #[test]
+    fn flash_loan_fee_calculation_discounted() {
+        let fees = ReserveFees {
+            borrow_fee_wad: 0,
+            flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+            host_fee_percentage: 20,
+        };
+
+        let (total_fee, host_fee) = fees
+            .calculate_discounted_flash_loan_fees(
+                Decimal::from(1_000_000u64),
+                50,
+                FeeCalculation::Exclusive,
+            )
+            .unwrap();
+
+        assert_eq!(total_fee, 1_500); // 0.15% of 1_000_000 borrowed
+        assert_eq!(host_fee, 300); // 20% of total fee
+
+        let (total_fee, host_fee) = fees
+            .calculate_discounted_flash_loan_fees(
+                Decimal::from(1_000_000u64),
+                100,
+                FeeCalculation::Exclusive,
+            )
+            .unwrap();
+
+        assert_eq!(total_fee, 0);
+        assert_eq!(host_fee, 0);
+    }
+
+    #[test]
+    fn reserve_config_risk_reducing() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            max_liquidation_bonus: 0,
+            protocol_liquidation_fee: 0,
+            liquidation_threshold: 55,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 30,
+            fees: ReserveFees {
+                borrow_fee_wad: 10_000_000_000_000_000, // 1%
+                flash_loan_fee_wad: 0,
+                host_fee_percentage: 20,
+            },
+            deposit_limit: 1_000_000,
+            borrow_limit: 500_000,
+            fee_receiver: Pubkey::new_unique(),
+            collateral_fee_receiver: Pubkey::new_unique(),
+            loan_to_value_ratio_ramp: ReserveRamp::default(),
+            liquidation_threshold_ramp: ReserveRamp::default(),
+        };
+
+        assert!(config.is_risk_reducing(&config));
+        assert!(config.is_risk_reducing(&ReserveConfig {
+            loan_to_value_ratio: 40,
+            deposit_limit: 100,
+            borrow_limit: 0,
+            ..config
+        }));
+
+        // raising the loan to value ratio or limits needs to wait for the delay
+        assert!(!config.is_risk_reducing(&ReserveConfig {
+            loan_to_value_ratio: 51,
+            ..config
+        }));
+        assert!(!config.is_risk_reducing(&ReserveConfig {
+            deposit_limit: 1_000_001,
+            ..config
+        }));
+        assert!(!config.is_risk_reducing(&ReserveConfig {
+            borrow_limit: 500_001,
+            ..config
+        }));
+
+        // changing the liquidation threshold affects existing obligations
+        assert!(!config.is_risk_reducing(&ReserveConfig {
+            liquidation_threshold: 54,
+            ..config
+        }));
+        assert!(!config.is_risk_reducing(&ReserveConfig {
+            fee_receiver: Pubkey::new_unique(),
+            ..config
+        }));
+    }
+
+    #[test]
+    fn reserve_ramp_value() {
+        let ramp = ReserveRamp {
+            target: 20,
+            start_slot: 100,
+            end_slot: 200,
+        };
+        assert_eq!(ramp.value(60, 50), 60);
+        assert_eq!(ramp.value(60, 100), 60);
+        assert_eq!(ramp.value(60, 125), 50);
+        assert_eq!(ramp.value(60, 150), 40);
+        assert_eq!(ramp.value(60, 199), 21);
+        assert_eq!(ramp.value(60, 200), 20);
+        assert_eq!(ramp.value(60, 1_000), 20);
+
+        // ramps can move upwards as well
+        assert_eq!(ramp.value(10, 150), 15);
+
+        // no ramp keeps the initial value
+        assert_eq!(ReserveRamp::default().value(60, 150), 60);
+    }
+
+    #[test]
+    fn reserve_set_status() {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                loan_to_value_ratio: 60,
+                liquidation_threshold: 70,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let bounds = ReserveConfigBounds {
+            max_loan_to_value_ratio_step: 60,
+            min_update_interval_slots: 100,
+            ..ReserveConfigBounds::default()
+        };
+
+        assert_eq!(
+            reserve.set_status(ReserveStatus::ReduceOnly, 61, 100, &bounds, 1010),
+            Err(LendingError::InvalidConfig.into())
+        );
+        assert_eq!(
+            reserve.set_status(ReserveStatus::ReduceOnly, 0, 99, &bounds, 1010),
+            Err(LendingError::ReserveConfigChangeOutOfBounds.into())
+        );
+
+        reserve
+            .set_status(ReserveStatus::ReduceOnly, 0, 100, &bounds, 1010)
+            .unwrap();
+        assert_eq!(reserve.status, ReserveStatus::ReduceOnly);
+        assert_eq!(reserve.last_config_update_slot, 1010);
+        assert_eq!(reserve.loan_to_value_ratio(1010), 60);
+        assert_eq!(reserve.loan_to_value_ratio(1060), 30);
+        assert_eq!(reserve.loan_to_value_ratio(1110), 0);
+
+        // changing status keeps the scheduled ramp
+        reserve
+            .set_status(ReserveStatus::Frozen, 0, 0, &bounds, 1060)
+            .unwrap();
+        assert_eq!(reserve.status, ReserveStatus::Frozen);
+        assert_eq!(reserve.loan_to_value_ratio(1060), 30);
+        assert_eq!(reserve.loan_to_value_ratio(1110), 0);
+
+        // a second ramp cannot replace the running one
+        assert_eq!(
+            reserve.set_status(ReserveStatus::ReduceOnly, 10, 100, &bounds, 1070),
+            Err(LendingError::ReserveRampInProgress.into())
+        );
+
+        // finished ramps are written back into the config
+        reserve
+            .set_status(ReserveStatus::Active, 0, 0, &bounds, 1110)
+            .unwrap();
+        assert_eq!(reserve.status, ReserveStatus::Active);
+        assert_eq!(reserve.config.loan_to_value_ratio, 0);
+        assert_eq!(
+            reserve.config.loan_to_value_ratio_ramp,
+            ReserveRamp::default()
+        );
+    }
+
+    #[test]
+    fn reserve_finish_ramps() {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                loan_to_value_ratio: 60,
+                liquidation_threshold: 70,
+                liquidation_threshold_ramp: ReserveRamp {
+                    target: 80,
+                    start_slot: 100,
+                    end_slot: 200,
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        reserve.finish_ramps(199);
+        assert_eq!(reserve.config.liquidation_threshold, 70);
+        assert_eq!(reserve.liquidation_threshold(199), 79);
+
+        reserve.finish_ramps(200);
+        assert_eq!(reserve.config.liquidation_threshold, 80);
+        assert_eq!(
+            reserve.config.liquidation_threshold_ramp,
+            ReserveRamp::default()
+        );
+        assert_eq!(reserve.liquidation_threshold(300), 80);
+    }
+
+    #[test]
+    fn reserve_liquidation_threshold_ramp() {
+        let reserve = Reserve {
+            config: ReserveConfig {
+                loan_to_value_ratio: 60,
+                liquidation_threshold: 70,
+                liquidation_threshold_ramp: ReserveRamp {
+                    target: 80,
+                    start_slot: 100,
+                    end_slot: 200,
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        assert_eq!(reserve.liquidation_threshold(50), 70);
+        assert_eq!(reserve.liquidation_threshold(150), 75);
+        assert_eq!(reserve.liquidation_threshold(250), 80);
+        assert_eq!(reserve.loan_to_value_ratio(250), 60);
+    }
+
+    #[test]
+    fn reserve_liquidation_bonus() {
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 5,
+                max_liquidation_bonus: 15,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let obligation = |borrowed_value: u64| Obligation {
+            deposited_value: Decimal::from(100u64),
+            borrowed_value: Decimal::from(borrowed_value),
+            unhealthy_borrow_value: Decimal::from(80u64),
+            ..Obligation::default()
+        };
+
+        // healthy or barely unhealthy obligations get the min bonus
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(70)).unwrap(),
+            Rate::from_percent(5)
+        );
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(80)).unwrap(),
+            Rate::from_percent(5)
+        );
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(85)).unwrap(),
+            Rate::from_scaled_val(75_000_000_000_000_000)
+        );
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(90)).unwrap(),
+            Rate::from_percent(10)
+        );
+        // underwater obligations get the max bonus
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(100)).unwrap(),
+            Rate::from_percent(15)
+        );
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(150)).unwrap(),
+            Rate::from_percent(15)
+        );
+
+        // a max bonus at or below the min bonus keeps the bonus flat
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 5,
+                max_liquidation_bonus: 5,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation(150)).unwrap(),
+            Rate::from_percent(5)
+        );
+
+        // no collateral counts towards the unhealthy borrow value
+        let obligation = Obligation {
+            deposited_value: Decimal::from(100u64),
+            borrowed_value: Decimal::from(50u64),
+            ..Obligation::default()
+        };
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 5,
+                max_liquidation_bonus: 15,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation).unwrap(),
+            Rate::from_percent(10)
+        );
+    }
+
+    #[test]
+    fn reserve_calculate_liquidation_scaled_bonus() {
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 5,
+                max_liquidation_bonus: 15,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let obligation = Obligation {
+            deposited_value: Decimal::from(100u64),
+            borrowed_value: Decimal::from(90u64),
+            unhealthy_borrow_value: Decimal::from(80u64),
+            ..Obligation::default()
+        };
+        let liquidity = ObligationLiquidity {
+            borrowed_amount_wads: Decimal::from(90u64),
+            market_value: Decimal::from(90u64),
+            ..ObligationLiquidity::default()
+        };
+        let collateral = ObligationCollateral {
+            deposited_amount: 100,
+            market_value: Decimal::from(100u64),
+            ..ObligationCollateral::default()
+        };
+
+        // 20% close factor -> 18 repaid + 10% bonus -> 19.8 withdrawn
+        let result = reserve
+            .calculate_liquidation(
+                u64::MAX,
+                &obligation,
+                &liquidity,
+                &collateral,
+                &LiquidationConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(result.repay_amount, 18);
+        assert_eq!(result.withdraw_amount, 19);
+    }
+
+    #[test]
+    fn reserve_calculate_liquidation_protocol_fee() {
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 10,
+                protocol_liquidation_fee: 50,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let obligation = Obligation {
+            deposited_value: Decimal::from(1_000u64),
+            borrowed_value: Decimal::from(100u64),
+            unhealthy_borrow_value: Decimal::from(90u64),
+            ..Obligation::default()
+        };
+        let liquidity = ObligationLiquidity {
+            borrowed_amount_wads: Decimal::from(100u64),
+            market_value: Decimal::from(100u64),
+            ..ObligationLiquidity::default()
+        };
+        let collateral = ObligationCollateral {
+            deposited_amount: 1_000,
+            market_value: Decimal::from(1_000u64),
+            ..ObligationCollateral::default()
+        };
+
+        // 20% close factor -> 20 repaid + 10% bonus -> 22 withdrawn, half of the 2 bonus is fee
+        let result = reserve
+            .calculate_liquidation(
+                u64::MAX,
+                &obligation,
+                &liquidity,
+                &collateral,
+                &LiquidationConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(result.repay_amount, 20);
+        assert_eq!(result.withdraw_amount, 22);
+        assert_eq!(result.protocol_liquidation_fee, 1);
+
+        // the protocol fee rounds up
+        let bonus_rate = Rate::from_percent(110);
+        assert_eq!(
+            reserve
+                .calculate_protocol_liquidation_fee(23, bonus_rate)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            reserve
+                .calculate_protocol_liquidation_fee(0, bonus_rate)
+                .unwrap(),
+            0
+        );
+
+        // no fee without a bonus
+        assert_eq!(
+            reserve
+                .calculate_protocol_liquidation_fee(22, Rate::one())
+                .unwrap(),
+            0
+        );
+    }
 }
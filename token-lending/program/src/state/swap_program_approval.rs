@@ -0,0 +1,112 @@
+use super::*;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+
+/// Seed used with the lending market and swap program addresses to derive the approval address
+pub const SWAP_PROGRAM_APPROVAL_SEED: &[u8] = b"swap_program";
+
+/// Find the approval address of a swap program in a lending market
+pub fn find_swap_program_approval_address(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    swap_program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            lending_market.as_ref(),
+            swap_program_id.as_ref(),
+            SWAP_PROGRAM_APPROVAL_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Approval of a swap program which collateral can be swapped through
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapProgramApproval {
+    /// Version of the struct
+    pub version: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Approved swap program id
+    pub swap_program_id: Pubkey,
+}
+
+impl SwapProgramApproval {
+    /// Create a new swap program approval
+    pub fn new(params: InitSwapProgramApprovalParams) -> Self {
+        let mut approval = Self::default();
+        Self::init(&mut approval, params);
+        approval
+    }
+
+    /// Initialize a swap program approval
+    pub fn init(&mut self, params: InitSwapProgramApprovalParams) {
+        self.version = PROGRAM_VERSION;
+        self.lending_market = params.lending_market;
+        self.swap_program_id = params.swap_program_id;
+    }
+}
+
+/// Initialize a swap program approval
+pub struct InitSwapProgramApprovalParams {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Approved swap program id
+    pub swap_program_id: Pubkey,
+}
+
+impl Sealed for SwapProgramApproval {}
+impl IsInitialized for SwapProgramApproval {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const SWAP_PROGRAM_APPROVAL_LEN: usize = 129; // 1 + 1 + 32 + 32 + 63
+impl Pack for SwapProgramApproval {
+    const LEN: usize = SWAP_PROGRAM_APPROVAL_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, SWAP_PROGRAM_APPROVAL_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, account_type, lending_market, swap_program_id, _padding) =
+            mut_array_refs![output, 1, 1, PUBKEY_BYTES, PUBKEY_BYTES, 63];
+
+        *version = self.version.to_le_bytes();
+        *account_type = SWAP_PROGRAM_APPROVAL_ACCOUNT_TYPE.to_le_bytes();
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        swap_program_id.copy_from_slice(self.swap_program_id.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [SwapProgramApproval](struct.SwapProgramApproval.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, SWAP_PROGRAM_APPROVAL_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, account_type, lending_market, swap_program_id, _padding) =
+            array_refs![input, 1, 1, PUBKEY_BYTES, PUBKEY_BYTES, 63];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Swap program approval version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if version != UNINITIALIZED_VERSION
+            && u8::from_le_bytes(*account_type) != SWAP_PROGRAM_APPROVAL_ACCOUNT_TYPE
+        {
+            msg!("Swap program approval account type does not match");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            lending_market: Pubkey::new_from_array(*lending_market),
+            swap_program_id: Pubkey::new_from_array(*swap_program_id),
+        })
+    }
+}
@@ -0,0 +1,236 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{batch, BatchOp, BatchReserve},
+    math::Decimal,
+    processor::process_instruction,
+    state::{FeeCalculation, INITIAL_COLLATERAL_RATIO},
+};
+
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+fn batch_instruction(
+    lending_market: &TestLendingMarket,
+    usdc_test_reserve: &TestReserve,
+    test_obligation: &TestObligation,
+    user_accounts_owner: &Keypair,
+    ops: Vec<BatchOp>,
+) -> Instruction {
+    batch(
+        spl_token_lending::id(),
+        ops,
+        vec![BatchReserve {
+            reserve_pubkey: usdc_test_reserve.pubkey,
+            reserve_liquidity_pyth_oracle_pubkey: usdc_test_reserve.liquidity_pyth_oracle_pubkey,
+            reserve_liquidity_switchboard_oracle_pubkey: usdc_test_reserve
+                .liquidity_switchboard_oracle_pubkey,
+            reserve_liquidity_supply_pubkey: usdc_test_reserve.liquidity_supply_pubkey,
+            reserve_liquidity_fee_receiver_pubkey: usdc_test_reserve.config.fee_receiver,
+            reserve_collateral_mint_pubkey: usdc_test_reserve.collateral_mint_pubkey,
+            reserve_collateral_supply_pubkey: usdc_test_reserve.collateral_supply_pubkey,
+            user_liquidity_pubkey: usdc_test_reserve.user_liquidity_pubkey,
+        }],
+        test_obligation.pubkey,
+        lending_market.pubkey,
+        test_obligation.owner,
+        user_accounts_owner.pubkey(),
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 40 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            user_liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the borrow runs before the deposit, health is only checked once both have run
+    let mut transaction = Transaction::new_with_payer(
+        &[batch_instruction(
+            &setup.lending_market,
+            usdc_test_reserve,
+            &setup.test_obligation,
+            &setup.user_accounts_owner,
+            vec![
+                BatchOp::Borrow {
+                    reserve_index: 0,
+                    liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+                },
+                BatchOp::Deposit {
+                    reserve_index: 0,
+                    liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+                },
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            usdc_test_reserve.collateral_supply_pubkey
+        )
+        .await,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO
+    );
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert!(usdc_reserve.last_update.stale);
+    let (borrow_fee, _host_fee) = usdc_reserve
+        .config
+        .fees
+        .calculate_borrow_fees(
+            USDC_BORROW_AMOUNT_FRACTIONAL.into(),
+            FeeCalculation::Exclusive,
+        )
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await,
+        borrow_fee
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.last_update.stale);
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL + borrow_fee)
+    );
+}
+
+#[tokio::test]
+async fn test_fail_unhealthy() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 100 USDC of collateral at a 50% loan to value ratio cannot cover a 60 USDC borrow
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 60 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            user_liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[batch_instruction(
+            &setup.lending_market,
+            usdc_test_reserve,
+            &setup.test_obligation,
+            &setup.user_accounts_owner,
+            vec![
+                BatchOp::Deposit {
+                    reserve_index: 0,
+                    liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+                },
+                BatchOp::Borrow {
+                    reserve_index: 0,
+                    liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+                },
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ObligationUnhealthy as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_invalid_reserve_index() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            user_liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[batch_instruction(
+            &setup.lending_market,
+            usdc_test_reserve,
+            &setup.test_obligation,
+            &setup.user_accounts_owner,
+            vec![BatchOp::Deposit {
+                reserve_index: 1,
+                liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            }],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
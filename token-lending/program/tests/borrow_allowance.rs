@@ -0,0 +1,433 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{
+        add_lending_market_member, approve_borrow_allowance,
+        borrow_obligation_liquidity_with_allowance, refresh_obligation, revoke_borrow_allowance,
+        set_lending_market_permissioned,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{
+        find_borrow_allowance_address, BorrowAllowance, FeeCalculation, INITIAL_COLLATERAL_RATIO,
+    },
+};
+use std::u64;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+// 10 SOL at $20 and a 50% loan to value ratio allows borrowing $100, the delegate only $50
+const USDC_BORROW_ALLOWANCE_FRACTIONAL: u64 = 50 * FRACTIONAL_TO_USDC;
+
+async fn approve_allowance(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: solana_sdk::hash::Hash,
+    setup: &TestSetup,
+    delegate: &Keypair,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[approve_borrow_allowance(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            setup.lending_market.pubkey,
+            setup.reserve("usdc").pubkey,
+            setup.test_obligation.owner,
+            delegate.pubkey(),
+            USDC_BORROW_ALLOWANCE_FRACTIONAL,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+}
+
+fn borrow_with_allowance_transaction(
+    payer: &Keypair,
+    recent_blockhash: solana_sdk::hash::Hash,
+    setup: &TestSetup,
+    delegate: &Keypair,
+    liquidity_amount: u64,
+) -> Transaction {
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![setup.reserve("sol").pubkey],
+            ),
+            borrow_obligation_liquidity_with_allowance(
+                spl_token_lending::id(),
+                liquidity_amount,
+                0,
+                u64::MAX,
+                setup.reserve("usdc").liquidity_supply_pubkey,
+                setup.reserve("usdc").user_liquidity_pubkey,
+                setup.reserve("usdc").pubkey,
+                setup.reserve("usdc").config.fee_receiver,
+                setup.test_obligation.pubkey,
+                setup.lending_market.pubkey,
+                delegate.pubkey(),
+                Some(setup.reserve("usdc").liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, delegate], recent_blockhash);
+    transaction
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 40 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_allowance(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+    )
+    .await;
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.delegations_count, 1);
+
+    let transaction = borrow_with_allowance_transaction(
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").user_liquidity_pubkey
+        )
+        .await,
+        USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+
+    let usdc_reserve = setup.reserve("usdc").get_state(&mut banks_client).await;
+    let (borrow_fee, _host_fee) = usdc_reserve
+        .config
+        .fees
+        .calculate_borrow_fees(
+            USDC_BORROW_AMOUNT_FRACTIONAL.into(),
+            FeeCalculation::Exclusive,
+        )
+        .unwrap();
+    let borrow_amount = USDC_BORROW_AMOUNT_FRACTIONAL + borrow_fee;
+
+    // the debt is recorded on the obligation of the delegator
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    let (liquidity, _) = obligation
+        .find_liquidity_in_borrows(setup.reserve("usdc").pubkey)
+        .unwrap();
+    assert_eq!(liquidity.borrowed_amount_wads, Decimal::from(borrow_amount));
+
+    let (borrow_allowance_pubkey, _bump_seed) = find_borrow_allowance_address(
+        &spl_token_lending::id(),
+        &setup.test_obligation.pubkey,
+        &delegate.pubkey(),
+        &setup.reserve("usdc").pubkey,
+    );
+    let borrow_allowance_account = banks_client
+        .get_account(borrow_allowance_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let borrow_allowance = BorrowAllowance::unpack(&borrow_allowance_account.data[..]).unwrap();
+    assert_eq!(borrow_allowance.delegate, delegate.pubkey());
+    assert_eq!(
+        borrow_allowance.allowance,
+        USDC_BORROW_ALLOWANCE_FRACTIONAL - borrow_amount
+    );
+    assert_eq!(
+        borrow_allowance.cumulative_borrowed_amount_wads,
+        Decimal::from(borrow_amount)
+    );
+}
+
+#[tokio::test]
+async fn test_fail_allowance_exceeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 60 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_allowance(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+    )
+    .await;
+
+    let transaction = borrow_with_allowance_transaction(
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowAllowanceExceeded as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_revoke() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_allowance(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[revoke_borrow_allowance(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            setup.reserve("usdc").pubkey,
+            setup.test_obligation.owner,
+            delegate.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let (borrow_allowance_pubkey, _bump_seed) = find_borrow_allowance_address(
+        &spl_token_lending::id(),
+        &setup.test_obligation.pubkey,
+        &delegate.pubkey(),
+        &setup.reserve("usdc").pubkey,
+    );
+    assert!(banks_client
+        .get_account(borrow_allowance_pubkey)
+        .await
+        .unwrap()
+        .is_none());
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.delegations_count, 0);
+
+    let transaction = borrow_with_allowance_transaction(
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_delegate_not_a_member() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_allowance(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_permissioned(
+                spl_token_lending::id(),
+                setup.lending_market.pubkey,
+                setup.lending_market.owner.pubkey(),
+                true,
+            ),
+            add_lending_market_member(
+                spl_token_lending::id(),
+                setup.lending_market.pubkey,
+                setup.lending_market.owner.pubkey(),
+                setup.test_obligation.owner,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // an allowance approved before the market became permissioned cannot be used
+    let transaction = borrow_with_allowance_transaction(
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidLendingMarketMembership as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[approve_borrow_allowance(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            setup.lending_market.pubkey,
+            setup.reserve("usdc").pubkey,
+            setup.test_obligation.owner,
+            delegate.pubkey(),
+            USDC_BORROW_ALLOWANCE_FRACTIONAL,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidLendingMarketMembership as u32)
+        )
+    );
+}
@@ -0,0 +1,327 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{flash_borrow_reserve_liquidity, flash_repay_reserve_liquidity},
+    processor::process_instruction,
+    state::FeeCalculation,
+};
+
+const FLASH_LOAN_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const FEE_AMOUNT: u64 = 3_000_000;
+const HOST_FEE_AMOUNT: u64 = 600_000;
+
+fn flash_borrow(
+    lending_market: &TestLendingMarket,
+    reserve: &TestReserve,
+    liquidity_amount: u64,
+) -> Instruction {
+    flash_borrow_reserve_liquidity(
+        spl_token_lending::id(),
+        liquidity_amount,
+        reserve.liquidity_supply_pubkey,
+        reserve.user_liquidity_pubkey,
+        reserve.pubkey,
+        lending_market.pubkey,
+    )
+}
+
+fn flash_repay(
+    lending_market: &TestLendingMarket,
+    reserve: &TestReserve,
+    liquidity_amount: u64,
+    borrow_instruction_index: u8,
+    user_transfer_authority: &Keypair,
+) -> Instruction {
+    flash_repay_reserve_liquidity(
+        spl_token_lending::id(),
+        liquidity_amount,
+        borrow_instruction_index,
+        reserve.user_liquidity_pubkey,
+        reserve.liquidity_supply_pubkey,
+        reserve.config.fee_receiver,
+        reserve.liquidity_host_pubkey,
+        reserve.pubkey,
+        lending_market.pubkey,
+        user_transfer_authority.pubkey(),
+    )
+}
+
+fn approve_repay(
+    reserve: &TestReserve,
+    user_accounts_owner: &Keypair,
+    user_transfer_authority: &Keypair,
+) -> Instruction {
+    approve(
+        &spl_token::id(),
+        &reserve.user_liquidity_pubkey,
+        &user_transfer_authority.pubkey(),
+        &user_accounts_owner.pubkey(),
+        &[],
+        FLASH_LOAN_AMOUNT + FEE_AMOUNT,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(60_000);
+
+    let user_transfer_authority = Keypair::new();
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            user_liquidity_amount: FEE_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(initial_liquidity_supply, FLASH_LOAN_AMOUNT);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            flash_borrow(&setup.lending_market, usdc_test_reserve, FLASH_LOAN_AMOUNT),
+            approve_repay(
+                usdc_test_reserve,
+                &setup.user_accounts_owner,
+                &user_transfer_authority,
+            ),
+            flash_repay(
+                &setup.lending_market,
+                usdc_test_reserve,
+                FLASH_LOAN_AMOUNT,
+                0,
+                &user_transfer_authority,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &setup.user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(usdc_reserve.liquidity.available_amount, FLASH_LOAN_AMOUNT);
+    assert_eq!(usdc_reserve.liquidity.borrowed_amount_wads, 0.into());
+
+    let (total_fee, host_fee) = usdc_reserve
+        .config
+        .fees
+        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into(), FeeCalculation::Exclusive)
+        .unwrap();
+    assert_eq!(total_fee, FEE_AMOUNT);
+    assert_eq!(host_fee, HOST_FEE_AMOUNT);
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, initial_liquidity_supply);
+
+    let user_liquidity_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await;
+    assert_eq!(user_liquidity_balance, 0);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, FEE_AMOUNT - HOST_FEE_AMOUNT);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, HOST_FEE_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_missing_repay() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            user_liquidity_amount: FEE_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_borrow(
+            &setup.lending_market,
+            usdc_test_reserve,
+            FLASH_LOAN_AMOUNT,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidFlashBorrow as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_repay_amount_mismatch() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_transfer_authority = Keypair::new();
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            user_liquidity_amount: FEE_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            flash_borrow(&setup.lending_market, usdc_test_reserve, FLASH_LOAN_AMOUNT),
+            approve_repay(
+                usdc_test_reserve,
+                &setup.user_accounts_owner,
+                &user_transfer_authority,
+            ),
+            flash_repay(
+                &setup.lending_market,
+                usdc_test_reserve,
+                FLASH_LOAN_AMOUNT - 1,
+                0,
+                &user_transfer_authority,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &setup.user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidFlashRepay as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_nested_borrow() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const HALF_FLASH_LOAN_AMOUNT: u64 = FLASH_LOAN_AMOUNT / 2;
+
+    let user_transfer_authority = Keypair::new();
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            user_liquidity_amount: FEE_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            flash_borrow(
+                &setup.lending_market,
+                usdc_test_reserve,
+                HALF_FLASH_LOAN_AMOUNT,
+            ),
+            flash_borrow(
+                &setup.lending_market,
+                usdc_test_reserve,
+                HALF_FLASH_LOAN_AMOUNT,
+            ),
+            approve_repay(
+                usdc_test_reserve,
+                &setup.user_accounts_owner,
+                &user_transfer_authority,
+            ),
+            flash_repay(
+                &setup.lending_market,
+                usdc_test_reserve,
+                HALF_FLASH_LOAN_AMOUNT,
+                1,
+                &user_transfer_authority,
+            ),
+            flash_repay(
+                &setup.lending_market,
+                usdc_test_reserve,
+                HALF_FLASH_LOAN_AMOUNT,
+                0,
+                &user_transfer_authority,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &setup.user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::NestedFlashBorrow as u32)
+        )
+    );
+}
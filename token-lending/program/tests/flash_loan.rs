@@ -12,7 +12,10 @@ use solana_sdk::{
 };
 use spl_token::solana_program::instruction::InstructionError;
 use spl_token_lending::{
-    error::LendingError, instruction::flash_loan, processor::process_instruction,
+    error::LendingError,
+    instruction::{flash_loan, DEFAULT_FLASH_LOAN_RECEIVER_PREFIX},
+    processor::process_instruction,
+    state::FeeCalculation,
 };
 
 #[tokio::test]
@@ -88,6 +91,7 @@ async fn test_success() {
         &[flash_loan(
             spl_token_lending::id(),
             FLASH_LOAN_AMOUNT,
+            DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(),
             usdc_test_reserve.liquidity_supply_pubkey,
             program_owned_token_account,
             usdc_test_reserve.pubkey,
@@ -95,6 +99,7 @@ async fn test_success() {
             usdc_test_reserve.liquidity_host_pubkey,
             lending_market.pubkey,
             receiver_program_id.clone(),
+            false,
             vec![AccountMeta::new_readonly(
                 receiver_authority_pubkey.clone(),
                 false,
@@ -115,7 +120,7 @@ async fn test_success() {
     let (total_fee, host_fee) = usdc_reserve
         .config
         .fees
-        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into())
+        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into(), FeeCalculation::Exclusive)
         .unwrap();
     assert_eq!(total_fee, FEE_AMOUNT);
     assert_eq!(host_fee, HOST_FEE_AMOUNT);
@@ -197,6 +202,7 @@ async fn test_failure() {
         &[flash_loan(
             spl_token_lending::id(),
             FLASH_LOAN_AMOUNT,
+            DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(),
             usdc_test_reserve.liquidity_supply_pubkey,
             program_owned_token_account,
             usdc_test_reserve.pubkey,
@@ -204,6 +210,7 @@ async fn test_failure() {
             usdc_test_reserve.liquidity_host_pubkey,
             lending_market.pubkey,
             flash_loan_receiver_program_id.clone(),
+            false,
             vec![AccountMeta::new_readonly(
                 receiver_authority_pubkey.clone(),
                 false,
@@ -225,3 +232,192 @@ async fn test_failure() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_receiver_instruction_prefix() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const FLASH_LOAN_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const FEE_AMOUNT: u64 = 3_000_000;
+
+    let receiver_program_account = Keypair::new();
+    let receiver_program_id = receiver_program_account.pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id.clone(),
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        FEE_AMOUNT,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            spl_token_lending::id(),
+            FLASH_LOAN_AMOUNT,
+            helpers::flash_loan_receiver::RECEIVE_FLASH_LOAN_SIGHASH.to_vec(),
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.config.fee_receiver,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id.clone(),
+            false,
+            vec![AccountMeta::new_readonly(
+                receiver_authority_pubkey.clone(),
+                false,
+            )],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, FLASH_LOAN_AMOUNT);
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, 0);
+}
+
+#[tokio::test]
+async fn test_max_amount() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const RESERVE_LIQUIDITY_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+    let receiver_program_account = Keypair::new();
+    let receiver_program_id = receiver_program_account.pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id.clone(),
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+
+    // fees are included in the available liquidity when flash loaning the max amount
+    let (total_fee, host_fee) = reserve_config
+        .fees
+        .calculate_flash_loan_fees(RESERVE_LIQUIDITY_AMOUNT.into(), FeeCalculation::Inclusive)
+        .unwrap();
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: RESERVE_LIQUIDITY_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        total_fee,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            spl_token_lending::id(),
+            u64::MAX,
+            DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(),
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.config.fee_receiver,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id.clone(),
+            false,
+            vec![AccountMeta::new_readonly(
+                receiver_authority_pubkey.clone(),
+                false,
+            )],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.available_amount,
+        RESERVE_LIQUIDITY_AMOUNT
+    );
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, RESERVE_LIQUIDITY_AMOUNT);
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, 0);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, total_fee - host_fee);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, host_fee);
+}
@@ -0,0 +1,278 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::{instruction::AccountMeta, program_pack::Pack};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{flash_loan, set_flash_loan_fee_exemption, DEFAULT_FLASH_LOAN_RECEIVER_PREFIX},
+    processor::process_instruction,
+    state::{find_flash_loan_fee_exemptions_address, FlashLoanFeeExemptions},
+};
+
+const FLASH_LOAN_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const FEE_AMOUNT: u64 = 3_000_000;
+
+async fn test_discounted_flash_loan(
+    fee_discount: u8,
+    expected_fee_amount: u64,
+    expected_host_fee_amount: u64,
+) {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let receiver_program_id = Keypair::new().pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        FEE_AMOUNT,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (flash_loan_fee_exemptions_pubkey, _bump_seed) =
+        find_flash_loan_fee_exemptions_address(&spl_token_lending::id(), &lending_market.pubkey);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_flash_loan_fee_exemption(
+                spl_token_lending::id(),
+                receiver_program_id,
+                fee_discount,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+            flash_loan(
+                spl_token_lending::id(),
+                FLASH_LOAN_AMOUNT,
+                DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(),
+                usdc_test_reserve.liquidity_supply_pubkey,
+                program_owned_token_account,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                usdc_test_reserve.liquidity_host_pubkey,
+                lending_market.pubkey,
+                receiver_program_id,
+                true,
+                vec![AccountMeta::new_readonly(receiver_authority_pubkey, false)],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let flash_loan_fee_exemptions_account = banks_client
+        .get_account(flash_loan_fee_exemptions_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let flash_loan_fee_exemptions =
+        FlashLoanFeeExemptions::unpack(&flash_loan_fee_exemptions_account.data[..]).unwrap();
+    assert_eq!(
+        flash_loan_fee_exemptions.lending_market,
+        lending_market.pubkey
+    );
+    assert_eq!(
+        flash_loan_fee_exemptions.fee_discount(&[receiver_program_id]),
+        fee_discount
+    );
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, FLASH_LOAN_AMOUNT);
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, FEE_AMOUNT - expected_fee_amount);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, expected_fee_amount - expected_host_fee_amount);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, expected_host_fee_amount);
+}
+
+#[tokio::test]
+async fn test_fee_exempt() {
+    test_discounted_flash_loan(100, 0, 0).await;
+}
+
+#[tokio::test]
+async fn test_fee_discount() {
+    test_discounted_flash_loan(50, 1_500_000, 300_000).await;
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_flash_loan_fee_exemption(
+            spl_token_lending::id(),
+            Pubkey::new_unique(),
+            100,
+            lending_market.pubkey,
+            invalid_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_misplaced_fee_exemptions() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let receiver_program_id = Keypair::new().pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        FEE_AMOUNT,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (flash_loan_fee_exemptions_pubkey, _bump_seed) =
+        find_flash_loan_fee_exemptions_address(&spl_token_lending::id(), &lending_market.pubkey);
+
+    // fee exemptions passed among the receiver accounts are rejected instead of ignored
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_flash_loan_fee_exemption(
+                spl_token_lending::id(),
+                receiver_program_id,
+                100,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+            flash_loan(
+                spl_token_lending::id(),
+                FLASH_LOAN_AMOUNT,
+                DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(),
+                usdc_test_reserve.liquidity_supply_pubkey,
+                program_owned_token_account,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                usdc_test_reserve.liquidity_host_pubkey,
+                lending_market.pubkey,
+                receiver_program_id,
+                false,
+                vec![
+                    AccountMeta::new_readonly(receiver_authority_pubkey, false),
+                    AccountMeta::new_readonly(flash_loan_fee_exemptions_pubkey, false),
+                ],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
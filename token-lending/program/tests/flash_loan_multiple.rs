@@ -0,0 +1,323 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::instruction::AccountMeta;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::solana_program::instruction::InstructionError;
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{flash_loan_multiple, FlashLoanReserve, DEFAULT_FLASH_LOAN_RECEIVER_PREFIX},
+    processor::process_instruction,
+};
+
+const USDC_FLASH_LOAN_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const USDC_FEE_AMOUNT: u64 = 3_000_000;
+const USDC_HOST_FEE_AMOUNT: u64 = 600_000;
+
+const SOL_FLASH_LOAN_AMOUNT: u64 = 100 * LAMPORTS_TO_SOL;
+const SOL_FEE_AMOUNT: u64 = 300_000_000;
+const SOL_HOST_FEE_AMOUNT: u64 = 60_000_000;
+
+fn flash_loan_reserve(
+    test_reserve: &TestReserve,
+    amount: u64,
+    receiver_account: Pubkey,
+) -> FlashLoanReserve {
+    FlashLoanReserve {
+        amount,
+        source_liquidity_pubkey: test_reserve.liquidity_supply_pubkey,
+        destination_liquidity_pubkey: receiver_account,
+        reserve_pubkey: test_reserve.pubkey,
+        reserve_liquidity_fee_receiver_pubkey: test_reserve.config.fee_receiver,
+        host_fee_receiver_pubkey: test_reserve.liquidity_host_pubkey,
+    }
+}
+
+fn flash_loan_multiple_instruction(
+    lending_market: &TestLendingMarket,
+    receiver_program_id: Pubkey,
+    flash_loan_reserves: Vec<FlashLoanReserve>,
+) -> Instruction {
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    flash_loan_multiple(
+        spl_token_lending::id(),
+        flash_loan_reserves,
+        DEFAULT_FLASH_LOAN_RECEIVER_PREFIX.to_vec(),
+        lending_market.pubkey,
+        receiver_program_id,
+        false,
+        vec![AccountMeta::new_readonly(receiver_authority_pubkey, false)],
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(90_000);
+
+    let receiver_program_id = Pubkey::new_unique();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    // a non-native mint so the receiver can repay it with a plain token transfer
+    let sol_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 9);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_FLASH_LOAN_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: SOL_FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: sol_mint_pubkey,
+            liquidity_mint_decimals: 9,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let usdc_receiver_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        USDC_FEE_AMOUNT,
+        &setup.usdc_mint.pubkey,
+    );
+    let sol_receiver_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        SOL_FEE_AMOUNT,
+        &sol_mint_pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan_multiple_instruction(
+            &setup.lending_market,
+            receiver_program_id,
+            vec![
+                flash_loan_reserve(
+                    setup.reserve("usdc"),
+                    USDC_FLASH_LOAN_AMOUNT,
+                    usdc_receiver_account,
+                ),
+                flash_loan_reserve(
+                    setup.reserve("sol"),
+                    SOL_FLASH_LOAN_AMOUNT,
+                    sol_receiver_account,
+                ),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    for (test_reserve, receiver_account, flash_loan_amount, fee_amount, host_fee_amount) in [
+        (
+            setup.reserve("usdc"),
+            usdc_receiver_account,
+            USDC_FLASH_LOAN_AMOUNT,
+            USDC_FEE_AMOUNT,
+            USDC_HOST_FEE_AMOUNT,
+        ),
+        (
+            setup.reserve("sol"),
+            sol_receiver_account,
+            SOL_FLASH_LOAN_AMOUNT,
+            SOL_FEE_AMOUNT,
+            SOL_HOST_FEE_AMOUNT,
+        ),
+    ]
+    .iter()
+    {
+        let reserve = test_reserve.get_state(&mut banks_client).await;
+        assert_eq!(reserve.liquidity.available_amount, *flash_loan_amount);
+
+        let liquidity_supply =
+            get_token_balance(&mut banks_client, test_reserve.liquidity_supply_pubkey).await;
+        assert_eq!(liquidity_supply, *flash_loan_amount);
+
+        let receiver_balance = get_token_balance(&mut banks_client, *receiver_account).await;
+        assert_eq!(receiver_balance, 0);
+
+        let fee_balance =
+            get_token_balance(&mut banks_client, test_reserve.config.fee_receiver).await;
+        assert_eq!(fee_balance, fee_amount - host_fee_amount);
+
+        let host_fee_balance =
+            get_token_balance(&mut banks_client, test_reserve.liquidity_host_pubkey).await;
+        assert_eq!(host_fee_balance, *host_fee_amount);
+    }
+}
+
+#[tokio::test]
+async fn test_one_reserve_not_repaid() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let receiver_program_id = Pubkey::new_unique();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    // a non-native mint so the receiver can repay it with a plain token transfer
+    let sol_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 9);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_FLASH_LOAN_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: SOL_FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: sol_mint_pubkey,
+            liquidity_mint_decimals: 9,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let usdc_receiver_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        USDC_FEE_AMOUNT,
+        &setup.usdc_mint.pubkey,
+    );
+    let sol_receiver_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        SOL_FEE_AMOUNT - 1,
+        &sol_mint_pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan_multiple_instruction(
+            &setup.lending_market,
+            receiver_program_id,
+            vec![
+                flash_loan_reserve(
+                    setup.reserve("usdc"),
+                    USDC_FLASH_LOAN_AMOUNT,
+                    usdc_receiver_account,
+                ),
+                flash_loan_reserve(
+                    setup.reserve("sol"),
+                    SOL_FLASH_LOAN_AMOUNT,
+                    sol_receiver_account,
+                ),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::NotEnoughLiquidityAfterFlashLoan as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_duplicate_reserve() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let receiver_program_id = Pubkey::new_unique();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_FLASH_LOAN_AMOUNT,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let usdc_receiver_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        USDC_FEE_AMOUNT,
+        &setup.usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan_multiple_instruction(
+            &setup.lending_market,
+            receiver_program_id,
+            vec![
+                flash_loan_reserve(
+                    setup.reserve("usdc"),
+                    USDC_FLASH_LOAN_AMOUNT / 2,
+                    usdc_receiver_account,
+                ),
+                flash_loan_reserve(
+                    setup.reserve("usdc"),
+                    USDC_FLASH_LOAN_AMOUNT / 2,
+                    usdc_receiver_account,
+                ),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -21,11 +21,12 @@ pub enum FlashLoanReceiverInstruction {
     ///
     ///   0. `[writable]` Source liquidity (matching the destination from above).
     ///   1. `[writable]` Destination liquidity (matching the source from above).
+    ///   .. Source and destination liquidity for each additional amount, in order.
     ///   2. `[]` Token program id
     ///   .. `[any]` Additional accounts provided to the lending program's `FlashLoan` instruction above.
     ReceiveFlashLoan {
-        /// The amount that is loaned
-        amount: u64,
+        /// The amount that is loaned from each reserve
+        amounts: Vec<u64>,
     },
 }
 
@@ -48,27 +49,31 @@ impl Processor {
         let instruction = FlashLoanReceiverInstruction::unpack(instruction_data)?;
 
         match instruction {
-            FlashLoanReceiverInstruction::ReceiveFlashLoan { amount } => {
+            FlashLoanReceiverInstruction::ReceiveFlashLoan { amounts } => {
                 msg!("Instruction: Receive Flash Loan");
-                Self::process_receive_flash_loan(accounts, amount, program_id)
+                Self::process_receive_flash_loan(accounts, &amounts, program_id)
             }
         }
     }
 
     fn process_receive_flash_loan(
         accounts: &[AccountInfo],
-        amount: u64,
+        amounts: &[u64],
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let source_liquidity_token_account_info = next_account_info(account_info_iter)?;
-        let destination_liquidity_token_account_info = next_account_info(account_info_iter)?;
+        let mut liquidity_token_account_infos = Vec::with_capacity(amounts.len());
+        for _ in amounts {
+            let source_liquidity_token_account_info = next_account_info(account_info_iter)?;
+            let destination_liquidity_token_account_info = next_account_info(account_info_iter)?;
+            liquidity_token_account_infos.push((
+                source_liquidity_token_account_info,
+                destination_liquidity_token_account_info,
+            ));
+        }
         let token_program_id = next_account_info(account_info_iter)?;
         let program_derived_account_info = next_account_info(account_info_iter)?;
 
-        let destination_liquidity_token_account = Account::unpack_from_slice(
-            &source_liquidity_token_account_info.try_borrow_mut_data()?,
-        )?;
         let (expected_program_derived_account_pubkey, bump_seed) =
             Pubkey::find_program_address(&[b"flashloan"], program_id);
 
@@ -76,57 +81,75 @@ impl Processor {
             msg!("Supplied program derived account doesn't match with expectation.")
         }
 
-        if destination_liquidity_token_account.owner != expected_program_derived_account_pubkey {
-            msg!("Destination liquidity token account is not owned by the program");
-            return Err(ProgramError::IncorrectProgramId);
-        }
+        for (
+            &amount,
+            (source_liquidity_token_account_info, destination_liquidity_token_account_info),
+        ) in amounts.iter().zip(liquidity_token_account_infos)
+        {
+            let source_liquidity_token_account = Account::unpack_from_slice(
+                &source_liquidity_token_account_info.try_borrow_data()?,
+            )?;
+            if source_liquidity_token_account.owner != expected_program_derived_account_pubkey {
+                msg!("Destination liquidity token account is not owned by the program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
 
-        let balance_in_token_account =
-            Account::unpack_from_slice(&source_liquidity_token_account_info.try_borrow_data()?)?
-                .amount;
-        let transfer_ix = spl_token::instruction::transfer(
-            token_program_id.key,
-            source_liquidity_token_account_info.key,
-            destination_liquidity_token_account_info.key,
-            &expected_program_derived_account_pubkey,
-            &[],
-            min(balance_in_token_account, amount),
-        )?;
-
-        invoke_signed(
-            &transfer_ix,
-            &[
-                source_liquidity_token_account_info.clone(),
-                destination_liquidity_token_account_info.clone(),
-                program_derived_account_info.clone(),
-                token_program_id.clone(),
-            ],
-            &[&[&b"flashloan"[..], &[bump_seed]]],
-        )?;
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program_id.key,
+                source_liquidity_token_account_info.key,
+                destination_liquidity_token_account_info.key,
+                &expected_program_derived_account_pubkey,
+                &[],
+                min(source_liquidity_token_account.amount, amount),
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    source_liquidity_token_account_info.clone(),
+                    destination_liquidity_token_account_info.clone(),
+                    program_derived_account_info.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[&b"flashloan"[..], &[bump_seed]]],
+            )?;
+        }
 
         Ok(())
     }
 }
 
+/// 8-byte discriminator also accepted for `ReceiveFlashLoan`, as used by Anchor programs
+pub const RECEIVE_FLASH_LOAN_SIGHASH: [u8; 8] = [0xa1, 0x7c, 0x3e, 0x52, 0x09, 0xd4, 0x6b, 0xf8];
+
 impl FlashLoanReceiverInstruction {
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.starts_with(&RECEIVE_FLASH_LOAN_SIGHASH) {
+            return Ok(Self::ReceiveFlashLoan {
+                amounts: Self::unpack_amounts(&input[RECEIVE_FLASH_LOAN_SIGHASH.len()..])?,
+            });
+        }
+
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
 
         Ok(match tag {
             0 => Self::ReceiveFlashLoan {
-                amount: Self::unpack_amount(rest)?,
+                amounts: Self::unpack_amounts(rest)?,
             },
             _ => return Err(InvalidInstruction.into()),
         })
     }
 
-    fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
-        let amount = input
-            .get(..8)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u64::from_le_bytes)
-            .ok_or(InvalidInstruction)?;
-        Ok(amount)
+    fn unpack_amounts(input: &[u8]) -> Result<Vec<u64>, ProgramError> {
+        if input.is_empty() || input.len() % 8 != 0 {
+            return Err(InvalidInstruction.into());
+        }
+        let amounts = input
+            .chunks(8)
+            .map(|slice| slice.try_into().map(u64::from_le_bytes))
+            .collect::<Result<_, _>>()
+            .map_err(|_| InvalidInstruction)?;
+        Ok(amounts)
     }
 }
 
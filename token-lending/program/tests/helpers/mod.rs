@@ -2,9 +2,12 @@
 
 pub mod flash_loan_receiver;
 pub mod genesis;
+pub mod token_swap;
 
 use assert_matches::*;
-use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use solana_program::{
+    instruction::AccountMeta, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
@@ -28,8 +31,16 @@ use spl_token_lending::{
         InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
         NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, ObligationCollateral,
         ObligationLiquidity, Reserve, ReserveCollateral, ReserveConfig, ReserveFees,
-        ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+        ReserveLiquidity, ReserveRamp, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+    },
+};
+use spl_token_swap::{
+    curve::{
+        base::{CurveType, SwapCurve},
+        constant_product::ConstantProductCurve,
+        fees::Fees,
     },
+    state::{SwapV1, SwapVersion},
 };
 use std::{convert::TryInto, str::FromStr};
 
@@ -44,6 +55,8 @@ pub fn test_reserve_config() -> ReserveConfig {
         optimal_utilization_rate: 80,
         loan_to_value_ratio: 50,
         liquidation_bonus: 5,
+        max_liquidation_bonus: 0,
+        protocol_liquidation_fee: 0,
         liquidation_threshold: 55,
         min_borrow_rate: 0,
         optimal_borrow_rate: 4,
@@ -56,6 +69,9 @@ pub fn test_reserve_config() -> ReserveConfig {
         deposit_limit: 100_000_000_000,
         borrow_limit: u64::MAX,
         fee_receiver: Keypair::new().pubkey(),
+        collateral_fee_receiver: Keypair::new().pubkey(),
+        loan_to_value_ratio_ramp: ReserveRamp::default(),
+        liquidation_threshold_ramp: ReserveRamp::default(),
     }
 }
 
@@ -69,7 +85,7 @@ pub const SRM_SWITCHBOARD_FEED: &str = "BAoygKcKN7wk8yKzLD6sxzUQUqLvhBV1rjMA4UJq
 
 pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 
-trait AddPacked {
+pub trait AddPacked {
     fn add_packable_account<T: Pack>(
         &mut self,
         pubkey: Pubkey,
@@ -197,6 +213,7 @@ pub fn add_obligation(
         owner: user_accounts_owner.pubkey(),
         deposits: obligation_deposits,
         borrows: obligation_borrows,
+        seeded: false,
     });
 
     if mark_fresh {
@@ -292,6 +309,19 @@ pub fn add_reserve(
         &spl_token::id(),
     );
 
+    test.add_packable_account(
+        config.collateral_fee_receiver,
+        u32::MAX as u64,
+        &Token {
+            mint: collateral_mint_pubkey,
+            owner: lending_market.owner.pubkey(),
+            amount: 0,
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+
     let amount = if let COption::Some(rent_reserve) = is_native {
         liquidity_amount + rent_reserve
     } else {
@@ -454,6 +484,156 @@ pub fn add_account_for_program(
     program_owned_token_account.pubkey()
 }
 
+/// Reserves and an obligation to add to a new lending market, shared by tests which need more
+/// than a single reserve
+#[derive(Default)]
+pub struct TestSetupBuilder {
+    fund_user_accounts_owner: bool,
+    reserves: Vec<(TestSetupOracle, AddReserveArgs)>,
+    deposits: Vec<(String, u64)>,
+    borrows: Vec<(String, u64)>,
+}
+
+enum TestSetupOracle {
+    Sol,
+    Usdc,
+}
+
+impl TestSetupBuilder {
+    /// Fund the user accounts owner so it can pay for accounts created by the program
+    pub fn fund_user_accounts_owner(mut self) -> Self {
+        self.fund_user_accounts_owner = true;
+        self
+    }
+
+    /// Add a reserve priced by the SOL oracle, named "sol" and holding native SOL unless the
+    /// args provide a name and mint
+    pub fn sol_reserve(mut self, args: AddReserveArgs) -> Self {
+        self.reserves.push((TestSetupOracle::Sol, args));
+        self
+    }
+
+    /// Add a reserve priced by the USDC oracle, named "usdc" and holding USDC unless the args
+    /// provide a name and mint
+    pub fn usdc_reserve(mut self, args: AddReserveArgs) -> Self {
+        self.reserves.push((TestSetupOracle::Usdc, args));
+        self
+    }
+
+    /// Deposit collateral of the named reserve to the obligation
+    pub fn deposit(mut self, reserve_name: &str, collateral_amount: u64) -> Self {
+        self.deposits
+            .push((reserve_name.to_owned(), collateral_amount));
+        self
+    }
+
+    /// Borrow liquidity of the named reserve in the obligation
+    pub fn borrow(mut self, reserve_name: &str, liquidity_amount: u64) -> Self {
+        self.borrows
+            .push((reserve_name.to_owned(), liquidity_amount));
+        self
+    }
+
+    pub fn build(self, test: &mut ProgramTest) -> TestSetup {
+        let user_accounts_owner = Keypair::new();
+        let lending_market = add_lending_market(test);
+
+        if self.fund_user_accounts_owner {
+            test.add_account(
+                user_accounts_owner.pubkey(),
+                Account {
+                    lamports: u32::MAX as u64,
+                    ..Account::default()
+                },
+            );
+        }
+
+        let sol_oracle = add_sol_oracle(test);
+        let usdc_oracle = add_usdc_oracle(test);
+        let usdc_mint = add_usdc_mint(test);
+
+        let mut reserves = Vec::with_capacity(self.reserves.len());
+        for (oracle, mut args) in self.reserves {
+            let (oracle, name, mint_pubkey, mint_decimals) = match oracle {
+                TestSetupOracle::Sol => (&sol_oracle, "sol", spl_token::native_mint::id(), 9),
+                TestSetupOracle::Usdc => {
+                    (&usdc_oracle, "usdc", usdc_mint.pubkey, usdc_mint.decimals)
+                }
+            };
+            if args.name.is_empty() {
+                args.name = name.to_owned();
+            }
+            if args.liquidity_mint_pubkey == Pubkey::default() {
+                args.liquidity_mint_pubkey = mint_pubkey;
+                args.liquidity_mint_decimals = mint_decimals;
+            }
+            reserves.push(add_reserve(
+                test,
+                &lending_market,
+                oracle,
+                &user_accounts_owner,
+                args,
+            ));
+        }
+
+        let find_reserve = |name: &str| {
+            reserves
+                .iter()
+                .find(|reserve| reserve.name == name)
+                .unwrap_or_else(|| panic!("No test reserve named {}", name))
+        };
+        let deposits = self
+            .deposits
+            .iter()
+            .map(|(name, amount)| (find_reserve(name), *amount))
+            .collect::<Vec<_>>();
+        let borrows = self
+            .borrows
+            .iter()
+            .map(|(name, amount)| (find_reserve(name), *amount))
+            .collect::<Vec<_>>();
+        let test_obligation = add_obligation(
+            test,
+            &lending_market,
+            &user_accounts_owner,
+            AddObligationArgs {
+                deposits: &deposits,
+                borrows: &borrows,
+                ..AddObligationArgs::default()
+            },
+        );
+
+        TestSetup {
+            lending_market,
+            user_accounts_owner,
+            sol_oracle,
+            usdc_oracle,
+            usdc_mint,
+            reserves,
+            test_obligation,
+        }
+    }
+}
+
+pub struct TestSetup {
+    pub lending_market: TestLendingMarket,
+    pub user_accounts_owner: Keypair,
+    pub sol_oracle: TestOracle,
+    pub usdc_oracle: TestOracle,
+    pub usdc_mint: TestMint,
+    pub reserves: Vec<TestReserve>,
+    pub test_obligation: TestObligation,
+}
+
+impl TestSetup {
+    pub fn reserve(&self, name: &str) -> &TestReserve {
+        self.reserves
+            .iter()
+            .find(|reserve| reserve.name == name)
+            .unwrap_or_else(|| panic!("No test reserve named {}", name))
+    }
+}
+
 pub struct TestLendingMarket {
     pub pubkey: Pubkey,
     pub owner: Keypair,
@@ -683,12 +863,14 @@ impl TestLendingMarket {
                 liquidate_obligation(
                     spl_token_lending::id(),
                     liquidity_amount,
+                    0,
                     repay_reserve.user_liquidity_pubkey,
                     withdraw_reserve.user_collateral_pubkey,
                     repay_reserve.pubkey,
                     repay_reserve.liquidity_supply_pubkey,
                     withdraw_reserve.pubkey,
                     withdraw_reserve.collateral_supply_pubkey,
+                    withdraw_reserve.config.collateral_fee_receiver,
                     obligation.pubkey,
                     self.pubkey,
                     user_transfer_authority.pubkey(),
@@ -722,6 +904,8 @@ impl TestLendingMarket {
             &[borrow_obligation_liquidity(
                 spl_token_lending::id(),
                 liquidity_amount,
+                0,
+                u64::MAX,
                 borrow_reserve.liquidity_supply_pubkey,
                 borrow_reserve.user_liquidity_pubkey,
                 borrow_reserve.pubkey,
@@ -784,7 +968,7 @@ impl TestReserve {
         lending_market: &TestLendingMarket,
         oracle: &TestOracle,
         liquidity_amount: u64,
-        config: ReserveConfig,
+        mut config: ReserveConfig,
         liquidity_mint_pubkey: Pubkey,
         user_liquidity_pubkey: Pubkey,
         liquidity_fee_receiver_keypair: &Keypair,
@@ -795,10 +979,12 @@ impl TestReserve {
         let reserve_pubkey = reserve_keypair.pubkey();
         let collateral_mint_keypair = Keypair::new();
         let collateral_supply_keypair = Keypair::new();
+        let collateral_fee_receiver_keypair = Keypair::new();
         let liquidity_supply_keypair = Keypair::new();
         let liquidity_host_keypair = Keypair::new();
         let user_collateral_token_keypair = Keypair::new();
         let user_transfer_authority_keypair = Keypair::new();
+        config.collateral_fee_receiver = collateral_fee_receiver_keypair.pubkey();
 
         let liquidity_mint_account = banks_client
             .get_account(liquidity_mint_pubkey)
@@ -833,6 +1019,13 @@ impl TestReserve {
                     Token::LEN as u64,
                     &spl_token::id(),
                 ),
+                create_account(
+                    &payer.pubkey(),
+                    &collateral_fee_receiver_keypair.pubkey(),
+                    rent.minimum_balance(Token::LEN),
+                    Token::LEN as u64,
+                    &spl_token::id(),
+                ),
                 create_account(
                     &payer.pubkey(),
                     &liquidity_supply_keypair.pubkey(),
@@ -898,6 +1091,7 @@ impl TestReserve {
                 &lending_market.owner,
                 &collateral_mint_keypair,
                 &collateral_supply_keypair,
+                &collateral_fee_receiver_keypair,
                 &liquidity_supply_keypair,
                 &liquidity_fee_receiver_keypair,
                 &liquidity_host_keypair,
@@ -1136,6 +1330,157 @@ pub fn add_usdc_mint(test: &mut ProgramTest) -> TestMint {
     }
 }
 
+pub fn add_mint(test: &mut ProgramTest, mint_authority: Pubkey, decimals: u8) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        pubkey,
+        u32::MAX as u64,
+        &Mint {
+            is_initialized: true,
+            mint_authority: COption::Some(mint_authority),
+            decimals,
+            ..Mint::default()
+        },
+        &spl_token::id(),
+    );
+    pubkey
+}
+
+pub enum TestSwapProgram {
+    Mock,
+    SplTokenSwap,
+}
+
+pub struct TestSwap {
+    pub program_id: Pubkey,
+    pub pubkey: Pubkey,
+    pub authority_pubkey: Pubkey,
+    pub token_a_pubkey: Pubkey,
+    pub token_b_pubkey: Pubkey,
+    pub pool_mint_pubkey: Pubkey,
+    pub pool_fee_pubkey: Pubkey,
+}
+
+/// Add a swap program and a pool of two tokens. The SPL token-swap pool uses
+/// `spl_token_swap_fees` and `spl_token_swap_curve`, the mock ignores the pool state.
+pub fn add_token_swap(
+    test: &mut ProgramTest,
+    swap_program: TestSwapProgram,
+    token_a_mint_pubkey: Pubkey,
+    token_a_amount: u64,
+    token_b_mint_pubkey: Pubkey,
+    token_b_amount: u64,
+) -> TestSwap {
+    let program_id = Pubkey::new_unique();
+    test.prefer_bpf(false);
+    match swap_program {
+        TestSwapProgram::Mock => test.add_program(
+            "token_swap",
+            program_id,
+            processor!(token_swap::process_instruction),
+        ),
+        TestSwapProgram::SplTokenSwap => test.add_program(
+            "spl_token_swap",
+            program_id,
+            processor!(spl_token_swap::processor::Processor::process),
+        ),
+    }
+
+    let pubkey = Pubkey::new_unique();
+    let (authority_pubkey, bump_seed) =
+        Pubkey::find_program_address(&[pubkey.as_ref()], &program_id);
+    let token_a_pubkey = add_account_for_program(
+        test,
+        &authority_pubkey,
+        token_a_amount,
+        &token_a_mint_pubkey,
+    );
+    let token_b_pubkey = add_account_for_program(
+        test,
+        &authority_pubkey,
+        token_b_amount,
+        &token_b_mint_pubkey,
+    );
+    let pool_mint_pubkey = add_mint(test, authority_pubkey, 6);
+    let pool_fee_pubkey = add_account_for_program(test, &authority_pubkey, 0, &pool_mint_pubkey);
+
+    if let TestSwapProgram::SplTokenSwap = swap_program {
+        let mut swap_account = Account::new(u32::MAX as u64, SwapVersion::LATEST_LEN, &program_id);
+        SwapVersion::pack(
+            SwapVersion::SwapV1(SwapV1 {
+                is_initialized: true,
+                nonce: bump_seed,
+                token_program_id: spl_token::id(),
+                token_a: token_a_pubkey,
+                token_b: token_b_pubkey,
+                pool_mint: pool_mint_pubkey,
+                token_a_mint: token_a_mint_pubkey,
+                token_b_mint: token_b_mint_pubkey,
+                pool_fee_account: pool_fee_pubkey,
+                fees: spl_token_swap_fees(),
+                swap_curve: spl_token_swap_curve(),
+            }),
+            &mut swap_account.data,
+        )
+        .unwrap();
+        test.add_account(pubkey, swap_account);
+    }
+
+    TestSwap {
+        program_id,
+        pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_mint_pubkey,
+        pool_fee_pubkey,
+    }
+}
+
+impl TestSwap {
+    /// Accounts of the token-swap `Swap` instruction, from token a to token b or the reverse
+    pub fn swap_accounts(
+        &self,
+        user_transfer_authority: Pubkey,
+        user_source_pubkey: Pubkey,
+        user_destination_pubkey: Pubkey,
+        a_to_b: bool,
+    ) -> Vec<AccountMeta> {
+        let (swap_source_pubkey, swap_destination_pubkey) = if a_to_b {
+            (self.token_a_pubkey, self.token_b_pubkey)
+        } else {
+            (self.token_b_pubkey, self.token_a_pubkey)
+        };
+        vec![
+            AccountMeta::new_readonly(self.pubkey, false),
+            AccountMeta::new_readonly(self.authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority, true),
+            AccountMeta::new(user_source_pubkey, false),
+            AccountMeta::new(swap_source_pubkey, false),
+            AccountMeta::new(swap_destination_pubkey, false),
+            AccountMeta::new(user_destination_pubkey, false),
+            AccountMeta::new(self.pool_mint_pubkey, false),
+            AccountMeta::new(self.pool_fee_pubkey, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]
+    }
+}
+
+pub fn spl_token_swap_fees() -> Fees {
+    Fees {
+        trade_fee_numerator: 25,
+        trade_fee_denominator: 10_000,
+        ..Fees::default()
+    }
+}
+
+pub fn spl_token_swap_curve() -> SwapCurve {
+    SwapCurve {
+        curve_type: CurveType::ConstantProduct,
+        calculator: Box::new(ConstantProductCurve),
+    }
+}
+
 pub struct TestOracle {
     pub pyth_product_pubkey: Pubkey,
     pub pyth_price_pubkey: Pubkey,
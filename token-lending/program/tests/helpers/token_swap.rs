@@ -0,0 +1,102 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use spl_token::solana_program::{
+    account_info::next_account_info,
+    program::{invoke, invoke_signed},
+};
+use std::convert::TryInto;
+
+/// Fee taken by the mock swap, as a percentage of the amount in
+pub const SWAP_FEE_PERCENT: u64 = 1;
+
+/// Minimal stand-in for the SPL token-swap program which swaps tokens one for one, less
+/// `SWAP_FEE_PERCENT`. It does not enforce `minimum_amount_out`, so that the caller's own
+/// slippage check can be exercised.
+///
+/// `Swap` instruction data: tag 1, amount_in u64, minimum_amount_out u64.
+///
+/// Accounts expected:
+///
+///   0. `[]` Swap account.
+///   1. `[]` Swap authority, PDA of the swap account address.
+///   2. `[signer]` User transfer authority.
+///   3. `[writable]` User source token account.
+///   4. `[writable]` Swap source token account.
+///   5. `[writable]` Swap destination token account.
+///   6. `[writable]` User destination token account.
+///   7. `[writable]` Pool token mint, unused.
+///   8. `[writable]` Fee account, unused.
+///   9. `[]` Token program id.
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (tag, rest) = instruction_data
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    if *tag != 1 || rest.len() != 16 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount_in = u64::from_le_bytes(rest[..8].try_into().unwrap());
+    msg!("Instruction: Swap");
+
+    let account_info_iter = &mut accounts.iter();
+    let swap_info = next_account_info(account_info_iter)?;
+    let swap_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let swap_source_info = next_account_info(account_info_iter)?;
+    let swap_destination_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let _pool_mint_info = next_account_info(account_info_iter)?;
+    let _pool_fee_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    let (swap_authority_pubkey, bump_seed) =
+        Pubkey::find_program_address(&[swap_info.key.as_ref()], program_id);
+    if &swap_authority_pubkey != swap_authority_info.key {
+        msg!("Swap authority provided does not match the derived swap authority");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            user_source_info.key,
+            swap_source_info.key,
+            user_transfer_authority_info.key,
+            &[],
+            amount_in,
+        )?,
+        &[
+            user_source_info.clone(),
+            swap_source_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+    )?;
+
+    let amount_out = amount_in - amount_in * SWAP_FEE_PERCENT / 100;
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            swap_destination_info.key,
+            user_destination_info.key,
+            swap_authority_info.key,
+            &[],
+            amount_out,
+        )?,
+        &[
+            swap_destination_info.clone(),
+            user_destination_info.clone(),
+            swap_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[&[swap_info.key.as_ref(), &[bump_seed]]],
+    )?;
+
+    Ok(())
+}
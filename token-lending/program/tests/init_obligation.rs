@@ -11,7 +11,10 @@ use solana_sdk::{
     transaction::{Transaction, TransactionError},
 };
 use spl_token_lending::{
-    error::LendingError, instruction::init_obligation, processor::process_instruction,
+    error::LendingError,
+    instruction::{init_obligation, init_seeded_obligation},
+    processor::process_instruction,
+    state::find_obligation_address,
 };
 
 #[tokio::test]
@@ -86,3 +89,98 @@ async fn test_already_initialized() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_success_seeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            init_seeded_obligation(
+                spl_token_lending::id(),
+                0,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                payer.pubkey(),
+            ),
+            init_seeded_obligation(
+                spl_token_lending::id(),
+                1,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                payer.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    for seed in 0..2 {
+        let (obligation_pubkey, _bump_seed) = find_obligation_address(
+            &spl_token_lending::id(),
+            &lending_market.pubkey,
+            &user_accounts_owner.pubkey(),
+            seed,
+        );
+        let obligation = TestObligation {
+            pubkey: obligation_pubkey,
+            keypair: Keypair::new(),
+            lending_market: lending_market.pubkey,
+            owner: user_accounts_owner.pubkey(),
+            deposits: vec![],
+            borrows: vec![],
+        };
+        obligation.validate_state(&mut banks_client).await;
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_seeded_address() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut instruction = init_seeded_obligation(
+        spl_token_lending::id(),
+        0,
+        lending_market.pubkey,
+        user_accounts_owner.pubkey(),
+        payer.pubkey(),
+    );
+    let (other_obligation_pubkey, _bump_seed) = find_obligation_address(
+        &spl_token_lending::id(),
+        &lending_market.pubkey,
+        &user_accounts_owner.pubkey(),
+        1,
+    );
+    instruction.accounts[0].pubkey = other_obligation_pubkey;
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -10,12 +10,13 @@ use solana_sdk::{
     signature::{Keypair, Signer},
     transaction::{Transaction, TransactionError},
 };
+use spl_token::state::{Account as Token, AccountState};
 use spl_token_lending::{
     error::LendingError,
     instruction::{init_reserve, update_reserve_config},
     math::Decimal,
     processor::process_instruction,
-    state::{ReserveConfig, ReserveFees, INITIAL_COLLATERAL_RATIO},
+    state::{ReserveConfig, ReserveFees, ReserveRamp, INITIAL_COLLATERAL_RATIO},
 };
 
 #[tokio::test]
@@ -406,6 +407,8 @@ async fn test_update_reserve_config() {
         optimal_utilization_rate: 75,
         loan_to_value_ratio: 45,
         liquidation_bonus: 10,
+        max_liquidation_bonus: 15,
+        protocol_liquidation_fee: 20,
         liquidation_threshold: 65,
         min_borrow_rate: 1,
         optimal_borrow_rate: 5,
@@ -418,6 +421,9 @@ async fn test_update_reserve_config() {
         deposit_limit: 1_000_000,
         borrow_limit: 300_000,
         fee_receiver: Keypair::new().pubkey(),
+        collateral_fee_receiver: test_reserve.config.collateral_fee_receiver,
+        loan_to_value_ratio_ramp: ReserveRamp::default(),
+        liquidation_threshold_ramp: ReserveRamp::default(),
     };
 
     let (mut banks_client, payer, recent_blockhash) = test.start().await;
@@ -441,6 +447,111 @@ async fn test_update_reserve_config() {
     assert_eq!(updated_reserve.config, new_config);
 }
 
+#[tokio::test]
+async fn test_update_collateral_fee_receiver() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mint = add_usdc_mint(&mut test);
+    let oracle = add_usdc_oracle(&mut test);
+    let test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 42,
+            liquidity_mint_decimals: mint.decimals,
+            liquidity_mint_pubkey: mint.pubkey,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let add_token_account = |test: &mut ProgramTest, mint: Pubkey| {
+        let pubkey = Pubkey::new_unique();
+        test.add_packable_account(
+            pubkey,
+            u32::MAX as u64,
+            &Token {
+                mint,
+                owner: lending_market.owner.pubkey(),
+                state: AccountState::Initialized,
+                ..Token::default()
+            },
+            &spl_token::id(),
+        );
+        pubkey
+    };
+    let liquidity_token_pubkey = add_token_account(&mut test, mint.pubkey);
+    let collateral_token_pubkey = add_token_account(&mut test, test_reserve.collateral_mint_pubkey);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the collateral fee receiver must hold reserve collateral
+    let config = ReserveConfig {
+        collateral_fee_receiver: liquidity_token_pubkey,
+        ..test_reserve.config
+    };
+    let mut transaction = Transaction::new_with_payer(
+        &[update_reserve_config(
+            spl_token_lending::id(),
+            config,
+            test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            oracle.pyth_product_pubkey,
+            oracle.pyth_price_pubkey,
+            oracle.switchboard_feed_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidTokenMint as u32)
+        )
+    );
+
+    let config = ReserveConfig {
+        collateral_fee_receiver: collateral_token_pubkey,
+        ..test_reserve.config
+    };
+    let mut transaction = Transaction::new_with_payer(
+        &[update_reserve_config(
+            spl_token_lending::id(),
+            config,
+            test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            oracle.pyth_product_pubkey,
+            oracle.pyth_price_pubkey,
+            oracle.switchboard_feed_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let updated_reserve = test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        updated_reserve.config.collateral_fee_receiver,
+        collateral_token_pubkey
+    );
+}
+
 #[tokio::test]
 async fn test_update_invalid_oracle_config() {
     let mut test = ProgramTest::new(
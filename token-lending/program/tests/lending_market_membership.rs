@@ -0,0 +1,196 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{
+        add_lending_market_member, remove_lending_market_member, set_lending_market_permissioned,
+    },
+    processor::process_instruction,
+    state::{find_lending_market_membership_address, LendingMarketMembership},
+};
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_permissioned(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                true,
+            ),
+            add_lending_market_member(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market_info = lending_market.get_state(&mut banks_client).await;
+    assert!(lending_market_info.permissioned);
+
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &spl_token_lending::id(),
+        &lending_market.pubkey,
+        &user_accounts_owner.pubkey(),
+    );
+    let lending_market_membership_account = banks_client
+        .get_account(lending_market_membership_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let lending_market_membership =
+        LendingMarketMembership::unpack(&lending_market_membership_account.data[..]).unwrap();
+    assert_eq!(
+        lending_market_membership.lending_market,
+        lending_market.pubkey
+    );
+    assert_eq!(
+        lending_market_membership.member,
+        user_accounts_owner.pubkey()
+    );
+
+    let obligation = TestObligation::init(
+        &mut banks_client,
+        &lending_market,
+        &user_accounts_owner,
+        &payer,
+    )
+    .await
+    .unwrap();
+
+    obligation.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_not_a_member() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_permissioned(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            true,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        TestObligation::init(
+            &mut banks_client,
+            &lending_market,
+            &user_accounts_owner,
+            &payer,
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidLendingMarketMembership as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_removed_member() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_permissioned(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                true,
+            ),
+            add_lending_market_member(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+            remove_lending_market_member(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let (lending_market_membership_pubkey, _bump_seed) = find_lending_market_membership_address(
+        &spl_token_lending::id(),
+        &lending_market.pubkey,
+        &user_accounts_owner.pubkey(),
+    );
+    assert!(banks_client
+        .get_account(lending_market_membership_pubkey)
+        .await
+        .unwrap()
+        .is_none());
+
+    assert_eq!(
+        TestObligation::init(
+            &mut banks_client,
+            &lending_market,
+            &user_accounts_owner,
+            &payer,
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidLendingMarketMembership as u32)
+        )
+    );
+}
@@ -0,0 +1,674 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{
+        add_swap_program, close_leveraged_position, open_leveraged_position, refresh_obligation,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, ReserveFees, INITIAL_COLLATERAL_RATIO},
+};
+
+const TOKEN_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const TOKEN_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const SWAP_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+fn refresh_obligation_instruction(setup: &TestSetup) -> Instruction {
+    let deposit_reserves = setup
+        .test_obligation
+        .deposits
+        .iter()
+        .map(|deposit| deposit.deposit_reserve);
+    let borrow_reserves = setup
+        .test_obligation
+        .borrows
+        .iter()
+        .map(|borrow| borrow.borrow_reserve);
+    let reserves = deposit_reserves.chain(borrow_reserves).collect();
+    refresh_obligation(
+        spl_token_lending::id(),
+        setup.test_obligation.pubkey,
+        reserves,
+    )
+}
+
+fn open_leveraged_position_instructions(
+    setup: &TestSetup,
+    swap: &TestSwap,
+    liquidity_amount: u64,
+    minimum_amount_out: u64,
+    max_leverage_percent: u16,
+) -> Vec<Instruction> {
+    vec![
+        refresh_obligation_instruction(setup),
+        open_leveraged_position(
+            spl_token_lending::id(),
+            liquidity_amount,
+            minimum_amount_out,
+            max_leverage_percent,
+            setup.reserve("usdc").pubkey,
+            setup.reserve("usdc").liquidity_supply_pubkey,
+            setup.reserve("usdc").config.fee_receiver,
+            setup.reserve("usdc").liquidity_host_pubkey,
+            setup.reserve("token").pubkey,
+            setup.reserve("token").liquidity_supply_pubkey,
+            setup.reserve("token").collateral_mint_pubkey,
+            setup.reserve("token").collateral_supply_pubkey,
+            setup.reserve("usdc").user_liquidity_pubkey,
+            setup.reserve("token").user_liquidity_pubkey,
+            setup.test_obligation.pubkey,
+            setup.lending_market.pubkey,
+            setup.test_obligation.owner,
+            swap.program_id,
+            swap.swap_accounts(
+                setup.user_accounts_owner.pubkey(),
+                setup.reserve("usdc").user_liquidity_pubkey,
+                setup.reserve("token").user_liquidity_pubkey,
+                true,
+            ),
+        ),
+    ]
+}
+
+fn close_leveraged_position_instructions(
+    setup: &TestSetup,
+    swap: &TestSwap,
+    collateral_amount: u64,
+    minimum_amount_out: u64,
+) -> Vec<Instruction> {
+    vec![
+        refresh_obligation_instruction(setup),
+        close_leveraged_position(
+            spl_token_lending::id(),
+            collateral_amount,
+            minimum_amount_out,
+            setup.reserve("token").pubkey,
+            setup.reserve("token").collateral_supply_pubkey,
+            setup.reserve("token").collateral_mint_pubkey,
+            setup.reserve("token").liquidity_supply_pubkey,
+            setup.reserve("usdc").pubkey,
+            setup.reserve("usdc").liquidity_supply_pubkey,
+            setup.reserve("token").user_liquidity_pubkey,
+            setup.reserve("usdc").user_liquidity_pubkey,
+            setup.test_obligation.pubkey,
+            setup.lending_market.pubkey,
+            setup.test_obligation.owner,
+            swap.program_id,
+            swap.swap_accounts(
+                setup.user_accounts_owner.pubkey(),
+                setup.reserve("token").user_liquidity_pubkey,
+                setup.reserve("usdc").user_liquidity_pubkey,
+                false,
+            ),
+        ),
+    ]
+}
+
+fn swap_amount_out(amount_in: u64) -> u64 {
+    amount_in - amount_in * token_swap::SWAP_FEE_PERCENT / 100
+}
+
+async fn approve_swap_program(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: solana_sdk::hash::Hash,
+    setup: &TestSetup,
+    swap: &TestSwap,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[add_swap_program(
+            spl_token_lending::id(),
+            setup.lending_market.pubkey,
+            setup.lending_market.owner.pubkey(),
+            swap.program_id,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &setup.lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_open_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // USDC is borrowed against collateral of a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    ..test_reserve_config().fees
+                },
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            collateral_amount: TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "token",
+            TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    const BORROW_AMOUNT: u64 = 50 * FRACTIONAL_TO_USDC;
+    let amount_out = swap_amount_out(BORROW_AMOUNT);
+
+    let initial_usdc_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc").liquidity_supply_pubkey,
+    )
+    .await;
+    let initial_token_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("token").liquidity_supply_pubkey,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &open_leveraged_position_instructions(&setup, &swap, BORROW_AMOUNT, amount_out, 200),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the borrowed liquidity was swapped in full
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").liquidity_supply_pubkey
+        )
+        .await,
+        initial_usdc_liquidity_supply_balance - BORROW_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, swap.token_a_pubkey).await,
+        SWAP_LIQUIDITY_FRACTIONAL + BORROW_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").user_liquidity_pubkey
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").user_liquidity_pubkey
+        )
+        .await,
+        0
+    );
+
+    // the swap output was deposited as collateral
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").liquidity_supply_pubkey
+        )
+        .await,
+        initial_token_liquidity_supply_balance + amount_out
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").collateral_supply_pubkey
+        )
+        .await,
+        (TOKEN_DEPOSIT_AMOUNT_FRACTIONAL + amount_out) * INITIAL_COLLATERAL_RATIO
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        (TOKEN_DEPOSIT_AMOUNT_FRACTIONAL + amount_out) * INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.borrows[0].borrow_reserve,
+        setup.reserve("usdc").pubkey
+    );
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        Decimal::from(BORROW_AMOUNT)
+    );
+}
+
+#[tokio::test]
+async fn test_open_fail_leverage_too_high() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    ..test_reserve_config().fees
+                },
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            collateral_amount: TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "token",
+            TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    // 149.5 USDC deposited against 50 USDC borrowed is just over 150% leverage
+    const BORROW_AMOUNT: u64 = 50 * FRACTIONAL_TO_USDC;
+    let mut transaction = Transaction::new_with_payer(
+        &open_leveraged_position_instructions(
+            &setup,
+            &swap,
+            BORROW_AMOUNT,
+            swap_amount_out(BORROW_AMOUNT),
+            150,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::LeverageTooHigh as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_open_fail_borrow_too_large() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    ..test_reserve_config().fees
+                },
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            collateral_amount: TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "token",
+            TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    // 100 USDC of collateral at a 50% loan to value ratio can borrow at most 100 USDC once the
+    // borrowed liquidity is deposited back as collateral
+    const BORROW_AMOUNT: u64 = 101 * FRACTIONAL_TO_USDC;
+    let mut transaction = Transaction::new_with_payer(
+        &open_leveraged_position_instructions(
+            &setup,
+            &swap,
+            BORROW_AMOUNT,
+            swap_amount_out(BORROW_AMOUNT),
+            1_000,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_open_fail_max_liquidity_amount() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    ..test_reserve_config().fees
+                },
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            collateral_amount: TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "token",
+            TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &open_leveraged_position_instructions(&setup, &swap, u64::MAX, 0, 1_000),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAmount as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_close_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT: u64 = 10 * FRACTIONAL_TO_USDC;
+    const COLLATERAL_AMOUNT: u64 = 20 * FRACTIONAL_TO_USDC;
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT,
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    ..test_reserve_config().fees
+                },
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            collateral_amount: TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "token",
+            TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("usdc", USDC_BORROW_AMOUNT)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let amount_out = swap_amount_out(COLLATERAL_AMOUNT / INITIAL_COLLATERAL_RATIO);
+    let initial_usdc_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc").liquidity_supply_pubkey,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &close_leveraged_position_instructions(&setup, &swap, COLLATERAL_AMOUNT, amount_out),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the borrow was repaid in full and the rest of the swap output left with the owner
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").liquidity_supply_pubkey
+        )
+        .await,
+        initial_usdc_liquidity_supply_balance + USDC_BORROW_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").user_liquidity_pubkey
+        )
+        .await,
+        amount_out - USDC_BORROW_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").user_liquidity_pubkey
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").collateral_supply_pubkey
+        )
+        .await,
+        TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO - COLLATERAL_AMOUNT
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrows.is_empty());
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO - COLLATERAL_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn test_close_fail_exceeded_slippage() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT: u64 = 10 * FRACTIONAL_TO_USDC;
+    const COLLATERAL_AMOUNT: u64 = 20 * FRACTIONAL_TO_USDC;
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT,
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    ..test_reserve_config().fees
+                },
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            collateral_amount: TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "token",
+            TOKEN_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("usdc", USDC_BORROW_AMOUNT)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        SWAP_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &close_leveraged_position_instructions(
+            &setup,
+            &swap,
+            COLLATERAL_AMOUNT,
+            swap_amount_out(COLLATERAL_AMOUNT / INITIAL_COLLATERAL_RATIO) + 1,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ExceededSlippage as u32)
+        )
+    );
+}
@@ -0,0 +1,282 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{liquidate_obligation_and_redeem_reserve_collateral, refresh_obligation},
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+// 100 SOL collateral
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+// 100 SOL * 80% LTV -> 80 SOL * 20 USDC -> 1600 USDC borrow
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_600 * FRACTIONAL_TO_USDC;
+// 1600 USDC * 20% -> 320 USDC liquidation
+const USDC_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT_FRACTIONAL / 5;
+// 320 USDC / 20 USDC per SOL -> 16 SOL + 10% bonus -> 17.6 SOL (88/5)
+const SOL_LIQUIDATION_AMOUNT_LAMPORTS: u64 = LAMPORTS_TO_SOL * 88 / 5;
+
+const SOL_RESERVE_LIQUIDITY_LAMPORTS: u64 = 2 * 100 * LAMPORTS_TO_SOL;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+fn liquidate_and_redeem_transaction(
+    setup: &TestSetup,
+    payer: &Keypair,
+    user_transfer_authority: &Keypair,
+) -> Transaction {
+    let TestSetup {
+        lending_market,
+        user_accounts_owner,
+        test_obligation,
+        ..
+    } = setup;
+    let sol_test_reserve = setup.reserve("sol");
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_LIQUIDATION_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            approve(
+                &spl_token::id(),
+                &sol_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                SOL_LIQUIDATION_AMOUNT_LAMPORTS * INITIAL_COLLATERAL_RATIO,
+            )
+            .unwrap(),
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            liquidate_obligation_and_redeem_reserve_collateral(
+                spl_token_lending::id(),
+                USDC_LIQUIDATION_AMOUNT_FRACTIONAL,
+                0,
+                usdc_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.config.collateral_fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(80_000);
+
+    let user_transfer_authority = Keypair::new();
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS,
+            collateral_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS * INITIAL_COLLATERAL_RATIO,
+            borrow_amount: 0,
+            config: ReserveConfig {
+                liquidation_threshold: 80,
+                liquidation_bonus: 10,
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: ReserveConfig {
+                liquidation_threshold: 80,
+                liquidation_bonus: 10,
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .borrow("usdc", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_user_liquidity_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc").user_liquidity_pubkey,
+    )
+    .await;
+    let initial_user_collateral_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("sol").user_collateral_pubkey,
+    )
+    .await;
+    let initial_user_sol_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("sol").user_liquidity_pubkey,
+    )
+    .await;
+    let initial_sol_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("sol").liquidity_supply_pubkey,
+    )
+    .await;
+
+    let mut transaction =
+        liquidate_and_redeem_transaction(&setup, &payer, &user_transfer_authority);
+    transaction.sign(
+        &[&payer, &setup.user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let user_liquidity_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc").user_liquidity_pubkey,
+    )
+    .await;
+    assert_eq!(
+        user_liquidity_balance,
+        initial_user_liquidity_balance - USDC_LIQUIDATION_AMOUNT_FRACTIONAL
+    );
+
+    // collateral is redeemed in the same instruction
+    let user_collateral_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("sol").user_collateral_pubkey,
+    )
+    .await;
+    assert_eq!(user_collateral_balance, initial_user_collateral_balance);
+
+    let user_sol_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("sol").user_liquidity_pubkey,
+    )
+    .await;
+    assert_eq!(
+        user_sol_balance,
+        initial_user_sol_balance + SOL_LIQUIDATION_AMOUNT_LAMPORTS
+    );
+
+    let sol_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("sol").liquidity_supply_pubkey,
+    )
+    .await;
+    assert_eq!(
+        sol_liquidity_supply_balance,
+        initial_sol_liquidity_supply_balance - SOL_LIQUIDATION_AMOUNT_LAMPORTS
+    );
+
+    let sol_reserve = setup.reserve("sol").get_state(&mut banks_client).await;
+    assert_eq!(
+        sol_reserve.liquidity.available_amount,
+        SOL_RESERVE_LIQUIDITY_LAMPORTS - SOL_LIQUIDATION_AMOUNT_LAMPORTS
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS - SOL_LIQUIDATION_AMOUNT_LAMPORTS * INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        (USDC_BORROW_AMOUNT_FRACTIONAL - USDC_LIQUIDATION_AMOUNT_FRACTIONAL).into()
+    );
+}
+
+#[tokio::test]
+async fn test_insufficient_withdraw_reserve_liquidity() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 200 SOL - 190 SOL borrowed -> 10 SOL available, less than the 17.6 SOL to redeem
+    const SOL_BORROW_AMOUNT_LAMPORTS: u64 = 190 * LAMPORTS_TO_SOL;
+
+    let user_transfer_authority = Keypair::new();
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS,
+            collateral_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS * INITIAL_COLLATERAL_RATIO,
+            borrow_amount: SOL_BORROW_AMOUNT_LAMPORTS,
+            config: ReserveConfig {
+                liquidation_threshold: 80,
+                liquidation_bonus: 10,
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: ReserveConfig {
+                liquidation_threshold: 80,
+                liquidation_bonus: 10,
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .borrow("usdc", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction =
+        liquidate_and_redeem_transaction(&setup, &payer, &user_transfer_authority);
+    transaction.sign(
+        &[&payer, &setup.user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::InsufficientLiquidity as u32)
+        )
+    );
+}
@@ -0,0 +1,482 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, borrow_obligation_liquidity_with_delegation,
+        refresh_obligation, revoke_obligation_delegation, set_obligation_delegation,
+        withdraw_obligation_collateral,
+    },
+    math::{Decimal, TryDiv},
+    processor::process_instruction,
+    state::{
+        find_obligation_delegation_address, FeeCalculation, ObligationDelegation, DELEGATE_BORROW,
+        DELEGATE_REPAY_WITH_COLLATERAL, INITIAL_COLLATERAL_RATIO,
+    },
+};
+use std::u64;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+// 10 SOL at $20 and a 50% loan to value ratio allows borrowing $100, the delegate only $50
+const DELEGATION_VALUE_LIMIT: u64 = 50;
+
+async fn set_delegation(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: solana_sdk::hash::Hash,
+    setup: &TestSetup,
+    delegate: &Keypair,
+    permissions: u8,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[set_obligation_delegation(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            setup.test_obligation.owner,
+            delegate.pubkey(),
+            permissions,
+            DELEGATION_VALUE_LIMIT,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+}
+
+fn delegated_borrow_instruction(
+    setup: &TestSetup,
+    delegate: &Keypair,
+    liquidity_amount: u64,
+) -> Instruction {
+    borrow_obligation_liquidity_with_delegation(
+        spl_token_lending::id(),
+        liquidity_amount,
+        0,
+        u64::MAX,
+        setup.reserve("usdc").liquidity_supply_pubkey,
+        setup.reserve("usdc").user_liquidity_pubkey,
+        setup.reserve("usdc").pubkey,
+        setup.reserve("usdc").config.fee_receiver,
+        setup.test_obligation.pubkey,
+        setup.lending_market.pubkey,
+        delegate.pubkey(),
+        Some(setup.reserve("usdc").liquidity_host_pubkey),
+    )
+}
+
+#[tokio::test]
+async fn test_borrow_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 40 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_delegation(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        DELEGATE_BORROW,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![setup.reserve("sol").pubkey],
+            ),
+            delegated_borrow_instruction(&setup, &delegate, USDC_BORROW_AMOUNT_FRACTIONAL),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &delegate], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").user_liquidity_pubkey
+        )
+        .await,
+        USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+
+    let usdc_reserve = setup.reserve("usdc").get_state(&mut banks_client).await;
+    let (borrow_fee, _host_fee) = usdc_reserve
+        .config
+        .fees
+        .calculate_borrow_fees(
+            USDC_BORROW_AMOUNT_FRACTIONAL.into(),
+            FeeCalculation::Exclusive,
+        )
+        .unwrap();
+
+    let (obligation_delegation_pubkey, _bump_seed) = find_obligation_delegation_address(
+        &spl_token_lending::id(),
+        &setup.test_obligation.pubkey,
+        &delegate.pubkey(),
+    );
+    let obligation_delegation_account = banks_client
+        .get_account(obligation_delegation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let obligation_delegation =
+        ObligationDelegation::unpack(&obligation_delegation_account.data[..]).unwrap();
+    assert_eq!(obligation_delegation.delegate, delegate.pubkey());
+    assert_eq!(
+        obligation_delegation.value_limit,
+        Decimal::from(DELEGATION_VALUE_LIMIT)
+    );
+    // USDC is priced at $1
+    assert_eq!(
+        obligation_delegation.value_used,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL + borrow_fee)
+            .try_div(FRACTIONAL_TO_USDC)
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_fail_value_limit_exceeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 60 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_delegation(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        DELEGATE_BORROW,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![setup.reserve("sol").pubkey],
+            ),
+            delegated_borrow_instruction(&setup, &delegate, USDC_BORROW_AMOUNT_FRACTIONAL),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &delegate], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationDelegationLimit as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_fail_without_delegation_account() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_delegation(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        DELEGATE_BORROW,
+    )
+    .await;
+
+    // BorrowObligationLiquidity only accepts the obligation owner
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![setup.reserve("sol").pubkey],
+            ),
+            borrow_obligation_liquidity(
+                spl_token_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                0,
+                u64::MAX,
+                setup.reserve("usdc").liquidity_supply_pubkey,
+                setup.reserve("usdc").user_liquidity_pubkey,
+                setup.reserve("usdc").pubkey,
+                setup.reserve("usdc").config.fee_receiver,
+                setup.test_obligation.pubkey,
+                setup.lending_market.pubkey,
+                delegate.pubkey(),
+                Some(setup.reserve("usdc").liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &delegate], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_withdraw_fail_without_permission() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_delegation(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        DELEGATE_BORROW | DELEGATE_REPAY_WITH_COLLATERAL,
+    )
+    .await;
+
+    let (obligation_delegation_pubkey, _bump_seed) = find_obligation_delegation_address(
+        &spl_token_lending::id(),
+        &setup.test_obligation.pubkey,
+        &delegate.pubkey(),
+    );
+    let mut withdraw_instruction = withdraw_obligation_collateral(
+        spl_token_lending::id(),
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+        setup.reserve("sol").collateral_supply_pubkey,
+        setup.reserve("sol").user_collateral_pubkey,
+        setup.reserve("sol").pubkey,
+        setup.test_obligation.pubkey,
+        setup.lending_market.pubkey,
+        delegate.pubkey(),
+    );
+    withdraw_instruction
+        .accounts
+        .push(AccountMeta::new(obligation_delegation_pubkey, false));
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![setup.reserve("sol").pubkey],
+            ),
+            withdraw_instruction,
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &delegate], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidObligationDelegation as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_revoke() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let setup = TestSetupBuilder::default()
+        .fund_user_accounts_owner()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .build(&mut test);
+    let delegate = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_delegation(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &setup,
+        &delegate,
+        DELEGATE_BORROW,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[revoke_obligation_delegation(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            setup.test_obligation.owner,
+            delegate.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let (obligation_delegation_pubkey, _bump_seed) = find_obligation_delegation_address(
+        &spl_token_lending::id(),
+        &setup.test_obligation.pubkey,
+        &delegate.pubkey(),
+    );
+    assert!(banks_client
+        .get_account(obligation_delegation_pubkey)
+        .await
+        .unwrap()
+        .is_none());
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.delegations_count, 0);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![setup.reserve("sol").pubkey],
+            ),
+            delegated_borrow_instruction(&setup, &delegate, USDC_BORROW_AMOUNT_FRACTIONAL),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &delegate], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountOwner as u32)
+        )
+    );
+}
@@ -0,0 +1,458 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{
+        apply_reserve_config, cancel_reserve_config, queue_reserve_config,
+        set_reserve_config_bounds, set_reserve_config_delay, update_reserve_config,
+    },
+    processor::process_instruction,
+    state::{
+        find_pending_reserve_config_address, PendingReserveConfig, ReserveConfig,
+        ReserveConfigBounds,
+    },
+};
+
+const DELAY_SLOTS: u64 = 100;
+
+async fn set_config_delay(
+    test_context: &mut ProgramTestContext,
+    lending_market: &TestLendingMarket,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[set_reserve_config_delay(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            DELAY_SLOTS,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+}
+
+fn queue(
+    lending_market: &TestLendingMarket,
+    reserve: &TestReserve,
+    oracle: &TestOracle,
+    config: ReserveConfig,
+) -> solana_program::instruction::Instruction {
+    queue_reserve_config(
+        spl_token_lending::id(),
+        config,
+        reserve.pubkey,
+        lending_market.pubkey,
+        lending_market.owner.pubkey(),
+        oracle.pyth_product_pubkey,
+        oracle.pyth_price_pubkey,
+        oracle.switchboard_feed_pubkey,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let sol_test_reserve = setup.reserve("sol");
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+    set_config_delay(&mut test_context, &setup.lending_market).await;
+
+    let new_config = ReserveConfig {
+        loan_to_value_ratio: 52,
+        liquidation_threshold: 60,
+        ..sol_test_reserve.config
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[queue(
+            &setup.lending_market,
+            sol_test_reserve,
+            &setup.sol_oracle,
+            new_config,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &setup.lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(&spl_token_lending::id(), sol_test_reserve.pubkey);
+    let pending_reserve_config_account = test_context
+        .banks_client
+        .get_account(pending_reserve_config_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let pending_reserve_config =
+        PendingReserveConfig::unpack(&pending_reserve_config_account.data[..]).unwrap();
+    assert_eq!(pending_reserve_config.reserve, sol_test_reserve.pubkey);
+    assert_eq!(pending_reserve_config.earliest_apply_slot, 3 + DELAY_SLOTS);
+    assert_eq!(pending_reserve_config.config, new_config);
+
+    // applying before the delay has elapsed fails
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_reserve_config(
+            spl_token_lending::id(),
+            sol_test_reserve.pubkey,
+            setup.lending_market.pubkey,
+            setup.lending_market.owner.pubkey(),
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], test_context.last_blockhash);
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveConfigDelayNotElapsed as u32)
+        )
+    );
+
+    test_context.warp_to_slot(3 + DELAY_SLOTS).unwrap();
+    let recent_blockhash = test_context
+        .banks_client
+        .get_recent_blockhash()
+        .await
+        .unwrap();
+
+    // anyone can apply once the delay has elapsed
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_reserve_config(
+            spl_token_lending::id(),
+            sol_test_reserve.pubkey,
+            setup.lending_market.pubkey,
+            setup.lending_market.owner.pubkey(),
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], recent_blockhash);
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    let sol_reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(sol_reserve.config, new_config);
+
+    // the pending reserve config account is closed once applied
+    assert!(test_context
+        .banks_client
+        .get_account(pending_reserve_config_pubkey)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_risk_reducing() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let sol_test_reserve = setup.reserve("sol");
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+    set_config_delay(&mut test_context, &setup.lending_market).await;
+
+    let new_config = ReserveConfig {
+        loan_to_value_ratio: 40,
+        deposit_limit: 0,
+        ..sol_test_reserve.config
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            queue(
+                &setup.lending_market,
+                sol_test_reserve,
+                &setup.sol_oracle,
+                new_config,
+            ),
+            apply_reserve_config(
+                spl_token_lending::id(),
+                sol_test_reserve.pubkey,
+                setup.lending_market.pubkey,
+                setup.lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &setup.lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    let sol_reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(sol_reserve.config, new_config);
+}
+
+#[tokio::test]
+async fn test_update_timelocked() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let sol_test_reserve = setup.reserve("sol");
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+    set_config_delay(&mut test_context, &setup.lending_market).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[update_reserve_config(
+            spl_token_lending::id(),
+            ReserveConfig {
+                liquidation_threshold: 51,
+                ..sol_test_reserve.config
+            },
+            sol_test_reserve.pubkey,
+            setup.lending_market.pubkey,
+            setup.lending_market.owner.pubkey(),
+            setup.sol_oracle.pyth_product_pubkey,
+            setup.sol_oracle.pyth_price_pubkey,
+            setup.sol_oracle.switchboard_feed_pubkey,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &setup.lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveConfigTimelocked as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_cancel() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let sol_test_reserve = setup.reserve("sol");
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+    set_config_delay(&mut test_context, &setup.lending_market).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            queue(
+                &setup.lending_market,
+                sol_test_reserve,
+                &setup.sol_oracle,
+                ReserveConfig {
+                    liquidation_threshold: 51,
+                    ..sol_test_reserve.config
+                },
+            ),
+            cancel_reserve_config(
+                spl_token_lending::id(),
+                sol_test_reserve.pubkey,
+                setup.lending_market.pubkey,
+                setup.lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &setup.lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    let (pending_reserve_config_pubkey, _bump_seed) =
+        find_pending_reserve_config_address(&spl_token_lending::id(), sol_test_reserve.pubkey);
+    assert!(test_context
+        .banks_client
+        .get_account(pending_reserve_config_pubkey)
+        .await
+        .unwrap()
+        .is_none());
+
+    test_context.warp_to_slot(3 + DELAY_SLOTS).unwrap();
+    let recent_blockhash = test_context
+        .banks_client
+        .get_recent_blockhash()
+        .await
+        .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_reserve_config(
+            spl_token_lending::id(),
+            sol_test_reserve.pubkey,
+            setup.lending_market.pubkey,
+            setup.lending_market.owner.pubkey(),
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], recent_blockhash);
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveConfigNotPending as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_queue_out_of_bounds() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        })
+        .build(&mut test);
+    let sol_test_reserve = setup.reserve("sol");
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+    set_config_delay(&mut test_context, &setup.lending_market).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_reserve_config_bounds(
+                spl_token_lending::id(),
+                setup.lending_market.pubkey,
+                setup.lending_market.owner.pubkey(),
+                ReserveConfigBounds {
+                    max_liquidation_threshold_step: 5,
+                    ..ReserveConfigBounds::default()
+                },
+            ),
+            queue(
+                &setup.lending_market,
+                sol_test_reserve,
+                &setup.sol_oracle,
+                ReserveConfig {
+                    liquidation_threshold: 1,
+                    ..sol_test_reserve.config
+                },
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &setup.lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ReserveConfigChangeOutOfBounds as u32)
+        )
+    );
+}
@@ -80,6 +80,7 @@ async fn test_success() {
             redeem_reserve_collateral(
                 spl_token_lending::id(),
                 COLLATERAL_AMOUNT,
+                0,
                 usdc_test_reserve.user_collateral_pubkey,
                 usdc_test_reserve.user_liquidity_pubkey,
                 usdc_test_reserve.pubkey,
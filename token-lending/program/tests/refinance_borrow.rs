@@ -0,0 +1,223 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{refinance_borrow, refresh_obligation},
+    math::Decimal,
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use std::u64;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+// 0.00001% borrow fee on the borrow reserve
+const USDC_BORROW_FEE_FRACTIONAL: u64 = 10;
+
+fn refinance_borrow_instructions(
+    setup: &TestSetup,
+    max_borrow_amount_including_fees: u64,
+) -> Vec<Instruction> {
+    vec![
+        refresh_obligation(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            vec![
+                setup.reserve("sol").pubkey,
+                setup.reserve("usdc repay").pubkey,
+            ],
+        ),
+        refinance_borrow(
+            spl_token_lending::id(),
+            u64::MAX,
+            max_borrow_amount_including_fees,
+            setup.reserve("usdc repay").pubkey,
+            setup.reserve("usdc repay").liquidity_supply_pubkey,
+            setup.reserve("usdc borrow").pubkey,
+            setup.reserve("usdc borrow").liquidity_supply_pubkey,
+            setup.reserve("usdc borrow").config.fee_receiver,
+            setup.test_obligation.pubkey,
+            setup.lending_market.pubkey,
+            setup.test_obligation.owner,
+            None,
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "usdc repay".to_owned(),
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "usdc borrow".to_owned(),
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .borrow("usdc repay", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_repay_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc repay").liquidity_supply_pubkey,
+    )
+    .await;
+    let initial_borrow_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc borrow").liquidity_supply_pubkey,
+    )
+    .await;
+    let initial_fee_receiver_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("usdc borrow").config.fee_receiver,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &refinance_borrow_instructions(&setup, u64::MAX),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // check that liquidity moved between the reserve supplies, less the borrow fee
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc repay").liquidity_supply_pubkey
+        )
+        .await,
+        initial_repay_liquidity_supply_balance + USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc borrow").liquidity_supply_pubkey
+        )
+        .await,
+        initial_borrow_liquidity_supply_balance
+            - USDC_BORROW_AMOUNT_FRACTIONAL
+            - USDC_BORROW_FEE_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc borrow").config.fee_receiver
+        )
+        .await,
+        initial_fee_receiver_balance + USDC_BORROW_FEE_FRACTIONAL
+    );
+
+    let repay_reserve = setup
+        .reserve("usdc repay")
+        .get_state(&mut banks_client)
+        .await;
+    assert_eq!(
+        repay_reserve.liquidity.borrowed_amount_wads,
+        Decimal::zero()
+    );
+    let borrow_reserve = setup
+        .reserve("usdc borrow")
+        .get_state(&mut banks_client)
+        .await;
+    assert_eq!(
+        borrow_reserve.liquidity.borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL + USDC_BORROW_FEE_FRACTIONAL)
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.borrows[0].borrow_reserve,
+        setup.reserve("usdc borrow").pubkey
+    );
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL + USDC_BORROW_FEE_FRACTIONAL)
+    );
+}
+
+#[tokio::test]
+async fn test_fail_exceeded_max_borrow_amount() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .sol_reserve(AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "usdc repay".to_owned(),
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "usdc borrow".to_owned(),
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("sol", SOL_DEPOSIT_AMOUNT_LAMPORTS)
+        .borrow("usdc repay", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &refinance_borrow_instructions(&setup, USDC_BORROW_AMOUNT_FRACTIONAL),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ExceededSlippage as u32)
+        )
+    );
+}
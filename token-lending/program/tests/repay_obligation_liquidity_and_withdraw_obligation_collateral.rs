@@ -0,0 +1,176 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::repay_obligation_liquidity_and_withdraw_obligation_collateral,
+    processor::process_instruction, state::INITIAL_COLLATERAL_RATIO,
+};
+use std::u64;
+
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 40 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+fn repay_and_withdraw_instruction(
+    lending_market: &TestLendingMarket,
+    usdc_test_reserve: &TestReserve,
+    test_obligation: &TestObligation,
+    user_accounts_owner: &Keypair,
+    repay_liquidity_amount: u64,
+    withdraw_collateral_amount: u64,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    repay_obligation_liquidity_and_withdraw_obligation_collateral(
+        spl_token_lending::id(),
+        repay_liquidity_amount,
+        withdraw_collateral_amount,
+        0,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.collateral_supply_pubkey,
+        usdc_test_reserve.collateral_mint_pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.user_collateral_pubkey,
+        usdc_test_reserve.user_liquidity_pubkey,
+        test_obligation.pubkey,
+        lending_market.pubkey,
+        test_obligation.owner,
+        user_accounts_owner.pubkey(),
+        reserve_pubkeys,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("usdc", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the borrow is repaid in full, so only the deposit reserve refreshes the obligation
+    let mut transaction = Transaction::new_with_payer(
+        &[repay_and_withdraw_instruction(
+            &setup.lending_market,
+            usdc_test_reserve,
+            &setup.test_obligation,
+            &setup.user_accounts_owner,
+            u64::MAX,
+            u64::MAX,
+            vec![usdc_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_collateral_pubkey).await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            usdc_test_reserve.collateral_supply_pubkey
+        )
+        .await,
+        0
+    );
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert!(usdc_reserve.last_update.stale);
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.deposits.is_empty());
+    assert!(obligation.borrows.is_empty());
+}
+
+#[tokio::test]
+async fn test_fail_withdraw_too_large() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("usdc", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // 30 USDC is still borrowed after the repay, which 100 USDC of collateral at a 50% loan to
+    // value ratio cannot cover once it is all withdrawn
+    let mut transaction = Transaction::new_with_payer(
+        &[repay_and_withdraw_instruction(
+            &setup.lending_market,
+            usdc_test_reserve,
+            &setup.test_obligation,
+            &setup.user_accounts_owner,
+            10 * FRACTIONAL_TO_USDC,
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            vec![usdc_test_reserve.pubkey, usdc_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
+        )
+    );
+}
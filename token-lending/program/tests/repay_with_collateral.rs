@@ -0,0 +1,235 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{refresh_obligation, repay_with_collateral},
+    math::Decimal,
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use std::u64;
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 =
+    USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("usdc", USDC_DEPOSIT_AMOUNT_FRACTIONAL)
+        .borrow("usdc", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_collateral_supply_balance = get_token_balance(
+        &mut banks_client,
+        usdc_test_reserve.collateral_supply_pubkey,
+    )
+    .await;
+    let initial_liquidity_supply_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    let initial_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![usdc_test_reserve.pubkey],
+            ),
+            repay_with_collateral(
+                spl_token_lending::id(),
+                u64::MAX,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                setup.test_obligation.pubkey,
+                setup.lending_market.pubkey,
+                setup.test_obligation.owner,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let burn_amount = USDC_BORROW_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+
+    // check that collateral was burned and no liquidity left the reserve
+    let collateral_supply_balance = get_token_balance(
+        &mut banks_client,
+        usdc_test_reserve.collateral_supply_pubkey,
+    )
+    .await;
+    assert_eq!(
+        collateral_supply_balance,
+        initial_collateral_supply_balance - burn_amount
+    );
+    let liquidity_supply_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply_balance, initial_liquidity_supply_balance);
+
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        initial_reserve.liquidity.available_amount
+    );
+    assert_eq!(reserve.liquidity.borrowed_amount_wads, Decimal::zero());
+    assert_eq!(
+        reserve.collateral.mint_total_supply,
+        initial_reserve.collateral.mint_total_supply - burn_amount
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrows.is_empty());
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL - burn_amount
+    );
+}
+
+#[tokio::test]
+async fn test_partial_repay() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("usdc", USDC_DEPOSIT_AMOUNT_FRACTIONAL)
+        .borrow("usdc", USDC_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    const REPAY_AMOUNT_FRACTIONAL: u64 = 40 * FRACTIONAL_TO_USDC;
+    let burn_amount = REPAY_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![usdc_test_reserve.pubkey],
+            ),
+            repay_with_collateral(
+                spl_token_lending::id(),
+                burn_amount,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                setup.test_obligation.pubkey,
+                setup.lending_market.pubkey,
+                setup.test_obligation.owner,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL - REPAY_AMOUNT_FRACTIONAL)
+    );
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL - burn_amount
+    );
+}
+
+#[tokio::test]
+async fn test_fail_no_borrow() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit("usdc", USDC_DEPOSIT_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let usdc_test_reserve = setup.reserve("usdc");
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                setup.test_obligation.pubkey,
+                vec![usdc_test_reserve.pubkey],
+            ),
+            repay_with_collateral(
+                spl_token_lending::id(),
+                u64::MAX,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                setup.test_obligation.pubkey,
+                setup.lending_market.pubkey,
+                setup.test_obligation.owner,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationBorrowsEmpty as u32)
+        )
+    );
+}
@@ -0,0 +1,545 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{add_swap_program, refresh_obligation, swap_collateral},
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+use spl_token_swap::{curve::calculator::TradeDirection, error::SwapError};
+use std::u64;
+
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const TOKEN_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const TOKEN_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+const TOKEN_SWAP_AMOUNT_OUT: u64 = USDC_DEPOSIT_AMOUNT_FRACTIONAL
+    - USDC_DEPOSIT_AMOUNT_FRACTIONAL * token_swap::SWAP_FEE_PERCENT / 100;
+const SWAP_POOL_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+/// Amount of tokens the constant product pool returns for the swapped collateral
+fn spl_token_swap_amount_out() -> u64 {
+    spl_token_swap_curve()
+        .swap(
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL as u128,
+            SWAP_POOL_LIQUIDITY_FRACTIONAL as u128,
+            TOKEN_RESERVE_LIQUIDITY_FRACTIONAL as u128,
+            TradeDirection::AtoB,
+            &spl_token_swap_fees(),
+        )
+        .unwrap()
+        .destination_amount_swapped as u64
+}
+
+fn swap_collateral_instructions(
+    setup: &TestSetup,
+    swap: &TestSwap,
+    minimum_amount_out: u64,
+) -> Vec<Instruction> {
+    vec![
+        refresh_obligation(
+            spl_token_lending::id(),
+            setup.test_obligation.pubkey,
+            vec![setup.reserve("usdc").pubkey, setup.reserve("token").pubkey],
+        ),
+        swap_collateral(
+            spl_token_lending::id(),
+            u64::MAX,
+            minimum_amount_out,
+            setup.reserve("usdc").pubkey,
+            setup.reserve("usdc").collateral_supply_pubkey,
+            setup.reserve("usdc").collateral_mint_pubkey,
+            setup.reserve("usdc").liquidity_supply_pubkey,
+            setup.reserve("token").pubkey,
+            setup.reserve("token").liquidity_supply_pubkey,
+            setup.reserve("token").collateral_mint_pubkey,
+            setup.reserve("token").collateral_supply_pubkey,
+            setup.reserve("usdc").user_liquidity_pubkey,
+            setup.reserve("token").user_liquidity_pubkey,
+            setup.test_obligation.pubkey,
+            setup.lending_market.pubkey,
+            setup.test_obligation.owner,
+            swap.program_id,
+            swap.swap_accounts(
+                setup.user_accounts_owner.pubkey(),
+                setup.reserve("usdc").user_liquidity_pubkey,
+                setup.reserve("token").user_liquidity_pubkey,
+                true,
+            ),
+        ),
+    ]
+}
+
+async fn approve_swap_program(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: solana_sdk::hash::Hash,
+    setup: &TestSetup,
+    swap: &TestSwap,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[add_swap_program(
+            spl_token_lending::id(),
+            setup.lending_market.pubkey,
+            setup.lending_market.owner.pubkey(),
+            swap.program_id,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &setup.lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            borrow_amount: TOKEN_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("token", TOKEN_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        0,
+        token_mint_pubkey,
+        TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let initial_token_liquidity_supply_balance = get_token_balance(
+        &mut banks_client,
+        setup.reserve("token").liquidity_supply_pubkey,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &swap_collateral_instructions(&setup, &swap, TOKEN_SWAP_AMOUNT_OUT),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the withdrawn collateral was redeemed and swapped in full
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").collateral_supply_pubkey
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, swap.token_a_pubkey).await,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("usdc").user_liquidity_pubkey
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").user_liquidity_pubkey
+        )
+        .await,
+        0
+    );
+
+    // the swap output was deposited as collateral
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").liquidity_supply_pubkey
+        )
+        .await,
+        initial_token_liquidity_supply_balance + TOKEN_SWAP_AMOUNT_OUT
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            setup.reserve("token").collateral_supply_pubkey
+        )
+        .await,
+        TOKEN_SWAP_AMOUNT_OUT * INITIAL_COLLATERAL_RATIO
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposit_reserve,
+        setup.reserve("token").pubkey
+    );
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        TOKEN_SWAP_AMOUNT_OUT * INITIAL_COLLATERAL_RATIO
+    );
+}
+
+#[tokio::test]
+async fn test_fail_swap_program_not_approved() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            borrow_amount: TOKEN_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("token", TOKEN_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        0,
+        token_mint_pubkey,
+        TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &swap_collateral_instructions(&setup, &swap, TOKEN_SWAP_AMOUNT_OUT),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidSwapProgram as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_exceeded_slippage() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            borrow_amount: TOKEN_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("token", TOKEN_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        0,
+        token_mint_pubkey,
+        TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &swap_collateral_instructions(&setup, &swap, TOKEN_SWAP_AMOUNT_OUT + 1),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ExceededSlippage as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_unhealthy_after_swap() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 99 USDC of collateral at a 5% loan to value ratio cannot cover a 10 USDC borrow
+    // a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            borrow_amount: TOKEN_BORROW_AMOUNT_FRACTIONAL,
+            config: ReserveConfig {
+                loan_to_value_ratio: 5,
+                ..test_reserve_config()
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("token", TOKEN_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::Mock,
+        setup.usdc_mint.pubkey,
+        0,
+        token_mint_pubkey,
+        TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &swap_collateral_instructions(&setup, &swap, TOKEN_SWAP_AMOUNT_OUT),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_success_spl_token_swap() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            borrow_amount: TOKEN_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("token", TOKEN_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::SplTokenSwap,
+        setup.usdc_mint.pubkey,
+        SWAP_POOL_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    let amount_out = spl_token_swap_amount_out();
+    let mut transaction = Transaction::new_with_payer(
+        &swap_collateral_instructions(&setup, &swap, amount_out),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, swap.token_a_pubkey).await,
+        SWAP_POOL_LIQUIDITY_FRACTIONAL + USDC_DEPOSIT_AMOUNT_FRACTIONAL
+    );
+
+    let obligation = setup.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposit_reserve,
+        setup.reserve("token").pubkey
+    );
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        amount_out * INITIAL_COLLATERAL_RATIO
+    );
+}
+
+#[tokio::test]
+async fn test_fail_exceeded_slippage_spl_token_swap() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // a second stablecoin priced by the USDC oracle
+    let token_mint_pubkey = add_mint(&mut test, Pubkey::new_unique(), 6);
+    let setup = TestSetupBuilder::default()
+        .usdc_reserve(AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .usdc_reserve(AddReserveArgs {
+            name: "token".to_owned(),
+            liquidity_amount: TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: token_mint_pubkey,
+            liquidity_mint_decimals: 6,
+            borrow_amount: TOKEN_BORROW_AMOUNT_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        })
+        .deposit(
+            "usdc",
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+        )
+        .borrow("token", TOKEN_BORROW_AMOUNT_FRACTIONAL)
+        .build(&mut test);
+    let swap = add_token_swap(
+        &mut test,
+        TestSwapProgram::SplTokenSwap,
+        setup.usdc_mint.pubkey,
+        SWAP_POOL_LIQUIDITY_FRACTIONAL,
+        token_mint_pubkey,
+        TOKEN_RESERVE_LIQUIDITY_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    approve_swap_program(&mut banks_client, &payer, recent_blockhash, &setup, &swap).await;
+
+    // the minimum amount out is enforced by the swap program itself
+    let mut transaction = Transaction::new_with_payer(
+        &swap_collateral_instructions(&setup, &swap, spl_token_swap_amount_out() + 1),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(SwapError::ExceededSlippage as u32)
+        )
+    );
+}